@@ -0,0 +1,54 @@
+use cgmath::{Point2, Point3};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use voxel_game::{block_vertex::Face, chunk::{ChunkManager, CHUNK_HEIGHT, CHUNK_SIZE, SUB_CHUNK_HEIGHT}, generator::{spawn_chunks, GeneratorPreset}, mesher::{is_face_visible, mesh_sub_chunk}};
+
+const SEED: u64 = 0x5eed;
+
+/// The spawn chunks of a preset, so the meshed chunk has neighbors on two sides.
+fn spawn(generator: GeneratorPreset) -> ChunkManager {
+    let mut chunks = ChunkManager::new();
+    for position in spawn_chunks() {
+        chunks.insert(generator.generate(position, SEED));
+    }
+    chunks
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate chunk");
+    for generator in GeneratorPreset::ALL {
+        group.bench_function(generator.name(), |b| b.iter(|| generator.generate(black_box(Point2::new(3, -2)), SEED)));
+    }
+    group.finish();
+}
+
+fn meshing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mesh chunk");
+    group.sample_size(20);
+    for generator in [GeneratorPreset::Random, GeneratorPreset::Flat] {
+        let chunks = spawn(generator);
+        let chunk = chunks.get(Point2::new(0, 0)).unwrap();
+        group.bench_function(generator.name(), |b| b.iter(|| {
+            (0..CHUNK_HEIGHT / SUB_CHUNK_HEIGHT).map(|index| mesh_sub_chunk(chunk, index, &chunks)).collect::<Vec<_>>()
+        }));
+    }
+    group.finish();
+}
+
+fn face_visibility(c: &mut Criterion) {
+    let chunks = spawn(GeneratorPreset::Random);
+    let chunk = chunks.get(Point2::new(0, 0)).unwrap();
+    // a diagonal through the chunk that touches its borders, so neighbor lookups are part of it
+    let positions: Vec<Point3<usize>> = (0..CHUNK_SIZE).flat_map(|x| (0..CHUNK_HEIGHT).step_by(8).map(move |y| Point3::new(x, y, x))).collect();
+    c.bench_function("face visibility", |b| b.iter(|| {
+        let mut visible = 0;
+        for position in &positions {
+            for face in Face::ALL {
+                visible += is_face_visible(chunk, face, position.x, position.y, position.z, &chunks) as usize;
+            }
+        }
+        visible
+    }));
+}
+
+criterion_group!(benches, generation, meshing, face_visibility);
+criterion_main!(benches);
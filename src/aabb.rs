@@ -0,0 +1,51 @@
+use cgmath::{Point3, Vector3};
+
+/// Axis aligned bounding box in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Whether the boxes overlap, touching doesn't count.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x && self.max.x > other.min.x
+            && self.min.y < other.max.y && self.max.y > other.min.y
+            && self.min.z < other.max.z && self.max.z > other.min.z
+    }
+
+    pub fn translated(&self, offset: Vector3<f32>) -> Aabb {
+        Aabb::new(self.min + offset, self.max + offset)
+    }
+
+    /// The box grown in the direction of `offset`, covering everything it passes when moved by it.
+    pub fn expanded_towards(&self, offset: Vector3<f32>) -> Aabb {
+        let mut expanded = *self;
+        for axis in 0..3 {
+            if offset[axis] < 0.0 { expanded.min[axis] += offset[axis]; } else { expanded.max[axis] += offset[axis]; }
+        }
+        expanded
+    }
+
+    /// Clamps a movement of `self` along one axis so it stops at `other` if it would run into it.
+    pub fn clip_offset(&self, other: &Aabb, axis: usize, offset: f32) -> f32 {
+        let overlaps = (0..3).filter(|&a| a != axis).all(|a| self.min[a] < other.max[a] && self.max[a] > other.min[a]);
+        if !overlaps { return offset; }
+
+        if offset > 0.0 && other.min[axis] >= self.max[axis] - CONTACT_EPSILON {
+            offset.min((other.min[axis] - self.max[axis]).max(0.0))
+        } else if offset < 0.0 && other.max[axis] <= self.min[axis] + CONTACT_EPSILON {
+            offset.max((other.max[axis] - self.min[axis]).min(0.0))
+        } else {
+            offset
+        }
+    }
+}
+
+/// how far a box may already be pushed into another and still count as touching it
+const CONTACT_EPSILON: f32 = 1e-4;
@@ -0,0 +1,289 @@
+use std::{io::Write, path::{Path, PathBuf}, time::Duration};
+
+use anyhow::Context;
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use serde::Serialize;
+
+use crate::{block::Material, chunk::{ChunkManager, CHUNK_HEIGHT, CHUNK_SIZE}, timestep::FrameTimings, renderer::DrawStats, world::World};
+
+/// Frames rendered before recording starts, the first ones include pipeline and texture uploads.
+pub const WARMUP_FRAMES: usize = 10;
+
+/// Closed Catmull-Rom spline through `points`, the camera path of the benchmark.
+pub struct CameraPath {
+    pub points: Vec<Point3<f32>>,
+}
+
+impl CameraPath {
+    /// Loop around the spawn chunks that rises and dips above `surface_height`, so the view sweeps across the terrain and sky.
+    /// It goes several chunks out, so chunks keep streaming in ahead of the camera and get meshed on the way.
+    pub fn around_spawn(surface_height: f32) -> Self {
+        let center = CHUNK_SIZE as f32;
+        let points = (0..8).map(|i| {
+            let angle = i as f32 / 8.0 * std::f32::consts::TAU;
+            let radius = if i % 2 == 0 { 6.0 * CHUNK_SIZE as f32 } else { 4.0 * CHUNK_SIZE as f32 };
+            let height = surface_height + if i % 4 < 2 { 6.0 } else { 20.0 };
+            Point3::new(center + radius * angle.cos(), height, center + radius * angle.sin())
+        }).collect();
+        Self { points }
+    }
+
+    /// Position at `t`, `0..1` goes once around the loop.
+    pub fn position(&self, t: f32) -> Point3<f32> {
+        let count = self.points.len();
+        let scaled = t.rem_euclid(1.0) * count as f32;
+        let segment = scaled.floor() as usize % count;
+        let s = scaled.fract();
+        let point = |offset: usize| self.points[(segment + offset + count - 1) % count].to_vec();
+        let (p0, p1, p2, p3) = (point(0), point(1), point(2), point(3));
+
+        let position = 0.5 * (2.0 * p1 + (p2 - p0) * s + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * s * s + (3.0 * p1 - p0 - 3.0 * p2 + p3) * s * s * s);
+        Point3::from_vec(position)
+    }
+
+    /// Direction of travel at `t`, tilted down towards the terrain.
+    pub fn direction(&self, t: f32) -> Vector3<f32> {
+        let ahead = self.position(t + 0.01) - self.position(t);
+        let horizontal = Vector3::new(ahead.x, 0.0, ahead.z).normalize();
+        (horizontal + Vector3::new(0.0, ahead.y.min(0.0) - 0.35, 0.0)).normalize()
+    }
+}
+
+/// Timings and counts of one recorded frame.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FrameSample {
+    pub frame: usize,
+    pub frame_ms: f64,
+    /// ticks and loading the chunks around the camera
+    pub update_ms: f64,
+    /// meshing the chunks that streamed in
+    pub mesh_ms: f64,
+    pub render_ms: f64,
+    pub vertices: u64,
+    pub draw_calls: u32,
+}
+
+/// Distribution of one column of the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Stats {
+    pub mean: f64,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn new(values: impl IntoIterator<Item = f64>) -> Self {
+        let mut values: Vec<f64> = values.into_iter().collect();
+        if values.is_empty() { return Self { mean: 0.0, min: 0.0, p50: 0.0, p90: 0.0, p99: 0.0, max: 0.0 }; }
+        values.sort_by(f64::total_cmp);
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            min: values[0],
+            p50: percentile(&values, 50.0),
+            p90: percentile(&values, 90.0),
+            p99: percentile(&values, 99.0),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// Nearest rank percentile of sorted values.
+pub fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub frame_ms: Stats,
+    pub update_ms: Stats,
+    pub mesh_ms: Stats,
+    pub render_ms: Stats,
+    pub vertices: Stats,
+    pub draw_calls: Stats,
+}
+
+/// Everything a run measured, written as JSON with the samples also as CSV.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub seed: u64,
+    pub generator: String,
+    pub frames: usize,
+    pub width: u32,
+    pub height: u32,
+    pub adapter: String,
+    pub backend: String,
+    /// meshing the spawn chunks before the first frame
+    pub initial_mesh_ms: f64,
+    pub summary: Summary,
+    pub samples: Vec<FrameSample>,
+}
+
+impl BenchmarkReport {
+    /// Writes `<output>.json` and `<output>.csv`, returning their paths.
+    pub fn write(&self, output: &Path) -> anyhow::Result<(PathBuf, PathBuf)> {
+        let (json_path, csv_path) = (output.with_extension("json"), output.with_extension("csv"));
+        std::fs::write(&json_path, serde_json::to_string_pretty(self)? + "\n").with_context(|| format!("writing {}", json_path.display()))?;
+        std::fs::write(&csv_path, self.csv()).with_context(|| format!("writing {}", csv_path.display()))?;
+        Ok((json_path, csv_path))
+    }
+
+    pub fn csv(&self) -> String {
+        let mut csv = Vec::new();
+        writeln!(csv, "frame,frame_ms,update_ms,mesh_ms,render_ms,vertices,draw_calls").unwrap();
+        for sample in &self.samples {
+            writeln!(csv, "{},{:.4},{:.4},{:.4},{:.4},{},{}", sample.frame, sample.frame_ms, sample.update_ms, sample.mesh_ms, sample.render_ms, sample.vertices, sample.draw_calls).unwrap();
+        }
+        String::from_utf8(csv).unwrap()
+    }
+
+    /// Percentiles of the timings, printed when the run ends.
+    pub fn table(&self) -> String {
+        let mut table = format!("{:<12}{:>10}{:>10}{:>10}{:>10}{:>10}\n", "", "mean", "p50", "p90", "p99", "max");
+        let summary = &self.summary;
+        for (name, stats) in [("frame ms", summary.frame_ms), ("update ms", summary.update_ms), ("mesh ms", summary.mesh_ms), ("render ms", summary.render_ms)] {
+            table += &format!("{name:<12}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10.3}\n", stats.mean, stats.p50, stats.p90, stats.p99, stats.max);
+        }
+        table
+    }
+}
+
+/// A scripted flythrough: every frame puts the camera at the next point of the path, so runs see the same frames.
+pub struct Benchmark {
+    pub path: CameraPath,
+    /// recorded frames, after the warmup
+    pub frames: usize,
+    pub rendered: usize,
+    pub samples: Vec<FrameSample>,
+    /// meshing the spawn chunks before the first frame
+    pub initial_mesh_time: Duration,
+}
+
+impl Benchmark {
+    pub fn new(world: &World, initial_mesh_time: Duration, frames: usize) -> Self {
+        Self { path: CameraPath::around_spawn(surface_height(&world.loaded_chunks)), frames, rendered: 0, samples: Vec::with_capacity(frames), initial_mesh_time }
+    }
+
+    /// Moves the camera to where the path is for the frame about to be rendered.
+    pub fn place_camera(&self, world: &mut World) {
+        let t = self.rendered.saturating_sub(WARMUP_FRAMES) as f32 / self.frames as f32;
+        world.player.follow_camera(self.path.position(t));
+        world.player.previous_position = world.player.position;
+        world.camera.look_in(self.path.direction(t));
+    }
+
+    /// Records a rendered frame, ignored during the warmup.
+    pub fn record(&mut self, timings: FrameTimings, draw_stats: DrawStats) {
+        self.rendered += 1;
+        if self.rendered <= WARMUP_FRAMES { return; }
+
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        self.samples.push(FrameSample {
+            frame: self.samples.len(),
+            frame_ms: ms(timings.frame_time),
+            update_ms: ms(timings.update_time),
+            mesh_ms: ms(timings.mesh_time),
+            render_ms: ms(timings.render_time),
+            vertices: draw_stats.vertices,
+            draw_calls: draw_stats.draw_calls,
+        });
+    }
+
+    pub fn finished(&self) -> bool {
+        self.samples.len() >= self.frames
+    }
+
+    pub fn report(&self, world: &World, config: &wgpu::SurfaceConfiguration, adapter: &wgpu::AdapterInfo) -> BenchmarkReport {
+        BenchmarkReport {
+            seed: world.seed,
+            generator: world.generator.to_string(),
+            frames: self.samples.len(),
+            width: config.width,
+            height: config.height,
+            adapter: adapter.name.clone(),
+            backend: format!("{:?}", adapter.backend),
+            initial_mesh_ms: self.initial_mesh_time.as_secs_f64() * 1000.0,
+            summary: self.summary(),
+            samples: self.samples.clone(),
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        let column = |value: fn(&FrameSample) -> f64| Stats::new(self.samples.iter().map(value));
+        Summary {
+            frame_ms: column(|sample| sample.frame_ms),
+            update_ms: column(|sample| sample.update_ms),
+            mesh_ms: column(|sample| sample.mesh_ms),
+            render_ms: column(|sample| sample.render_ms),
+            vertices: column(|sample| sample.vertices as f64),
+            draw_calls: column(|sample| sample.draw_calls as f64),
+        }
+    }
+}
+
+/// Height above the highest block around the middle of the spawn chunks.
+fn surface_height(chunks: &ChunkManager) -> f32 {
+    let center = CHUNK_SIZE as i32;
+    let mut highest = 0;
+    for (x, z) in [(-8, -8), (-8, 8), (8, -8), (8, 8), (0, 0)] {
+        let top = (0..CHUNK_HEIGHT as i32).rev().find(|y| chunks.block(Point3::new(center + x, *y, center + z)).is_some_and(|block| block.material != Material::Air));
+        highest = highest.max(top.map_or(0, |top| top + 1));
+    }
+    highest as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::{chunk_distance, chunk_local_position, UNLOAD_MARGIN}, settings::Settings};
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let values: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(percentile(&values, 50.0), 50.0);
+        assert_eq!(percentile(&values, 99.0), 99.0);
+        assert_eq!(percentile(&values, 100.0), 100.0);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&[7.0], 90.0), 7.0);
+    }
+
+    #[test]
+    fn stats_ignore_sample_order() {
+        let stats = Stats::new([4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(stats, Stats { mean: 2.5, min: 1.0, p50: 2.0, p90: 4.0, p99: 4.0, max: 4.0 });
+    }
+
+    #[test]
+    fn path_passes_through_its_points_and_loops() {
+        let path = CameraPath::around_spawn(64.0);
+        for (i, point) in path.points.iter().enumerate() {
+            let position = path.position(i as f32 / path.points.len() as f32);
+            assert!((position - point).magnitude() < 1e-3, "{position:?} != {point:?}");
+        }
+        assert!((path.position(1.0) - path.position(0.0)).magnitude() < 1e-3);
+        assert!(path.points.iter().all(|point| point.y > 64.0));
+    }
+
+    #[test]
+    fn path_leaves_the_chunks_loaded_at_its_start() {
+        // so chunks are unloaded behind the camera and loaded and meshed ahead of it all the way around
+        let path = CameraPath::around_spawn(64.0);
+        let chunk = |t: f32| chunk_local_position(path.position(t).map(|coordinate| coordinate.floor() as i32)).0;
+        let keep = Settings::default().render_distance as i32 + UNLOAD_MARGIN;
+        let farthest = (0..100).map(|i| chunk_distance(chunk(i as f32 / 100.0), chunk(0.0))).max().unwrap();
+        assert!(farthest > 2 * keep, "the path only goes {farthest} chunks from its start");
+    }
+
+    #[test]
+    fn path_looks_down_along_the_way() {
+        let path = CameraPath::around_spawn(64.0);
+        for i in 0..100 {
+            let direction = path.direction(i as f32 / 100.0);
+            assert!((direction.magnitude() - 1.0).abs() < 1e-4);
+            assert!(direction.y < 0.0);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Dedicated server, hosts a world without a window or GPU.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use voxel_game::{generator::GeneratorPreset, save::WorldSave, server::{serve, DEFAULT_ADDRESS}};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Dedicated voxel game server, type `help` into the console for commands")]
+struct Args {
+    /// Directory the world is loaded from and saved to
+    #[arg(long, value_name = "DIR", default_value = "world")]
+    world: PathBuf,
+
+    /// Seed of a new world's generator, random when not given, saved worlds keep theirs
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Generator of a new world for chunks that aren't in the save: random, flat or empty [default: random], saved worlds keep theirs
+    #[arg(long, value_name = "PRESET")]
+    generator: Option<GeneratorPreset>,
+
+    /// Address and port to listen on
+    #[arg(long, value_name = "ADDRESS", default_value = DEFAULT_ADDRESS)]
+    bind: String,
+}
+
+fn main() {
+    // connections and saves are logged at info
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let save = WorldSave::new(args.world);
+    let result = save.open(args.seed, args.generator).and_then(|metadata| serve(save, metadata.seed, metadata.generator, args.bind.as_str(), |reply| println!("{reply}")));
+    if let Err(error) = result {
+        eprintln!("error: {error:#}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,85 @@
+use egui::{Color32, RichText};
+
+use crate::input::{Action, Binding, InputMap};
+
+/// Window listing every action with its bindings, click a binding to replace it or right click to remove it.
+pub struct BindingsMenu {
+    pub open: bool,
+    /// action and binding index waiting for the next input, `None` index adds a binding
+    pub listening: Option<(Action, Option<usize>)>,
+    /// result of the last save, shown at the bottom
+    pub status: Option<String>,
+}
+
+impl BindingsMenu {
+    pub fn new() -> Self {
+        Self { open: false, listening: None, status: None }
+    }
+
+    /// Returns true when the bindings were changed.
+    pub fn ui(&mut self, ctx: &egui::Context, input_map: &mut InputMap) -> bool {
+        let mut changed = false;
+        let conflicts = input_map.conflicts();
+        let conflicting = |binding: &Binding| conflicts.iter().find(|(conflict, _)| conflict == binding).map(|(_, actions)| actions.clone());
+
+        egui::Window::new("Controls")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                egui::Grid::new("bindings").num_columns(2).striped(true).show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.name().replace('_', " "));
+                        ui.horizontal(|ui| {
+                            for (index, binding) in input_map.bindings(action).to_vec().into_iter().enumerate() {
+                                let mut text = RichText::new(binding.to_string());
+                                if self.listening == Some((action, Some(index))) {
+                                    text = RichText::new("press a key...").italics();
+                                }
+
+                                let others = conflicting(&binding);
+                                if others.is_some() { text = text.color(Color32::LIGHT_RED); }
+
+                                let mut response = ui.button(text);
+                                if let Some(others) = others {
+                                    let names: Vec<&str> = others.iter().filter(|other| **other != action).map(|other| other.name()).collect();
+                                    response = response.on_hover_text(format!("also bound to {}", names.join(", ")));
+                                }
+                                if response.clicked() { self.listening = Some((action, Some(index))); }
+                                if response.secondary_clicked() {
+                                    input_map.unbind(action, index);
+                                    changed = true;
+                                }
+                            }
+
+                            let adding = self.listening == Some((action, None));
+                            if ui.button(if adding { "press a key..." } else { "+" }).clicked() { self.listening = Some((action, None)); }
+                        });
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.separator();
+            for (binding, actions) in conflicts.iter() {
+                let names: Vec<&str> = actions.iter().map(|action| action.name()).collect();
+                ui.colored_label(Color32::LIGHT_RED, format!("{binding} is bound to {}", names.join(", ")));
+            }
+            if let Some(status) = &self.status { ui.label(status); }
+
+            ui.horizontal(|ui| {
+                if ui.button("Reset to defaults").clicked() {
+                    *input_map = InputMap::default();
+                    changed = true;
+                }
+                if ui.button("Done").clicked() {
+                    self.open = false;
+                    self.listening = None;
+                }
+            });
+        });
+
+        changed
+    }
+}
@@ -1,49 +1,79 @@
-use cgmath::Point3;
+use cgmath::{EuclideanSpace, Point3, Vector3};
 
-use crate::block_vertex::*;
+use crate::{aabb::Aabb, block_model::{BlockModel, ModelGeometry, ModelRotation}, block_vertex::{Face, SUB_VOXEL_PRECISION}};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Block {
     pub material: Material,
+    pub state: BlockState,
 }
 
 impl Block {
-    pub const FACE_VERTICES: [[BlockVertex; 4]; 6] = [
+    pub fn new(material: Material) -> Self {
+        Self { material, state: BlockState::default() }
+    }
+
+    pub fn with_state(material: Material, state: BlockState) -> Self {
+        Self { material, state }
+    }
+
+    /// Block placed against `clicked_face` of another block while looking along `look_direction`.
+    /// `hit_height` is the height of the hit point inside the clicked block, in the `0.0..1.0` range.
+    pub fn placed(material: Material, look_direction: Vector3<f32>, clicked_face: Face, hit_height: f32) -> Self {
+        let mut state = BlockState::default();
+        for property in material.properties() {
+            state = match property {
+                Property::Facing => state.with_facing(horizontal_facing(look_direction)),
+                Property::Axis => state.with_axis(Axis::of(clicked_face)),
+                Property::Half => state.with_half(match clicked_face {
+                    Face::NegativeY => Half::Top,
+                    Face::PositiveY => Half::Bottom,
+                    _ => if hit_height > 0.5 { Half::Top } else { Half::Bottom },
+                }),
+                Property::Open => state.with_open(false),
+            };
+        }
+
+        Self { material, state }
+    }
+
+    /// unit cube corners of every face, in `Face` order
+    pub const FACE_VERTICES: [[Point3<u8>; 4]; 6] = [
         [
-            BlockVertex { position: Point3::new(1, 0, 1), face: Face::PositiveX },
-            BlockVertex { position: Point3::new(1, 0, 0), face: Face::PositiveX },
-            BlockVertex { position: Point3::new(1, 1, 1), face: Face::PositiveX },
-            BlockVertex { position: Point3::new(1, 1, 0), face: Face::PositiveX },
+            Point3::new(1, 0, 1),
+            Point3::new(1, 0, 0),
+            Point3::new(1, 1, 1),
+            Point3::new(1, 1, 0),
         ],
         [
-            BlockVertex { position: Point3::new(0, 0, 0), face: Face::NegativeX },
-            BlockVertex { position: Point3::new(0, 0, 1), face: Face::NegativeX },
-            BlockVertex { position: Point3::new(0, 1, 0), face: Face::NegativeX },
-            BlockVertex { position: Point3::new(0, 1, 1), face: Face::NegativeX },
+            Point3::new(0, 0, 0),
+            Point3::new(0, 0, 1),
+            Point3::new(0, 1, 0),
+            Point3::new(0, 1, 1),
         ],
         [
-            BlockVertex { position: Point3::new(0, 0, 1), face: Face::PositiveZ },
-            BlockVertex { position: Point3::new(1, 0, 1), face: Face::PositiveZ },
-            BlockVertex { position: Point3::new(0, 1, 1), face: Face::PositiveZ },
-            BlockVertex { position: Point3::new(1, 1, 1), face: Face::PositiveZ },
+            Point3::new(0, 0, 1),
+            Point3::new(1, 0, 1),
+            Point3::new(0, 1, 1),
+            Point3::new(1, 1, 1),
         ],
         [
-            BlockVertex { position: Point3::new(1, 0, 0), face: Face::NegativeZ },
-            BlockVertex { position: Point3::new(0, 0, 0), face: Face::NegativeZ },
-            BlockVertex { position: Point3::new(1, 1, 0), face: Face::NegativeZ },
-            BlockVertex { position: Point3::new(0, 1, 0), face: Face::NegativeZ },
+            Point3::new(1, 0, 0),
+            Point3::new(0, 0, 0),
+            Point3::new(1, 1, 0),
+            Point3::new(0, 1, 0),
         ],
         [
-            BlockVertex { position: Point3::new(0, 1, 0), face: Face::PositiveY },
-            BlockVertex { position: Point3::new(0, 1, 1), face: Face::PositiveY },
-            BlockVertex { position: Point3::new(1, 1, 0), face: Face::PositiveY },
-            BlockVertex { position: Point3::new(1, 1, 1), face: Face::PositiveY },
+            Point3::new(0, 1, 0),
+            Point3::new(0, 1, 1),
+            Point3::new(1, 1, 0),
+            Point3::new(1, 1, 1),
         ],
         [
-            BlockVertex { position: Point3::new(1, 0, 0), face: Face::NegativeY },
-            BlockVertex { position: Point3::new(1, 0, 1), face: Face::NegativeY },
-            BlockVertex { position: Point3::new(0, 0, 0), face: Face::NegativeY },
-            BlockVertex { position: Point3::new(0, 0, 1), face: Face::NegativeY },
+            Point3::new(1, 0, 0),
+            Point3::new(1, 0, 1),
+            Point3::new(0, 0, 0),
+            Point3::new(0, 0, 1),
         ],
     ];
 
@@ -51,6 +81,200 @@ impl Block {
         2, 0, 1,
         3, 2, 1
     ];
+
+    /// Whether this block hides the face of `other` that touches this block's `side`.
+    pub fn hides_face_of(&self, other: &Block, side: Face) -> bool {
+        self.geometry().covers[side as usize] && (self.material.is_opaque() || (self.material == other.material && other.material.culls_same()))
+    }
+
+    /// Collision boxes of the block at `position` in the world, none for blocks that aren't solid.
+    pub fn collision_boxes(&self, position: Point3<i32>) -> impl Iterator<Item = Aabb> + '_ {
+        let origin = position.cast::<f32>().unwrap();
+        let boxes = if self.material.is_solid() { self.geometry().boxes.as_slice() } else { &[] };
+        boxes.iter().map(move |(min, max)| Aabb::new(
+            origin + min.cast::<f32>().unwrap().to_vec() / SUB_VOXEL_PRECISION as f32,
+            origin + max.cast::<f32>().unwrap().to_vec() / SUB_VOXEL_PRECISION as f32,
+        ))
+    }
+
+    pub fn geometry(&self) -> &'static ModelGeometry {
+        self.material.model().geometry(self.model_rotation())
+    }
+
+    /// Rotation of the material's model for the current state, the default model faces `PositiveZ` and sits in the bottom half.
+    pub fn model_rotation(&self) -> ModelRotation {
+        let facing = match self.state.facing() {
+            Face::PositiveZ => 0,
+            Face::NegativeX => 1,
+            Face::NegativeZ => 2,
+            _ => 3,
+        };
+
+        match self.material.model() {
+            BlockModel::Cube => match self.state.axis() {
+                Axis::X => ModelRotation::new(1, 1),
+                Axis::Y => ModelRotation::IDENTITY,
+                Axis::Z => ModelRotation::new(1, 0),
+            },
+            BlockModel::Slab => match self.state.half() {
+                Half::Bottom => ModelRotation::IDENTITY,
+                Half::Top => ModelRotation::new(2, 0),
+            },
+            // flipping around the x axis also turns the model around, compensate with another half turn
+            BlockModel::Stairs => match self.state.half() {
+                Half::Bottom => ModelRotation::new(0, facing),
+                Half::Top => ModelRotation::new(2, facing + 2),
+            },
+            BlockModel::Door => ModelRotation::new(0, facing + self.state.open() as u8),
+            BlockModel::Sign => ModelRotation::new(0, facing),
+            BlockModel::Fence | BlockModel::Cross => ModelRotation::IDENTITY,
+        }
+    }
+
+    pub fn atlas_index(&self, face: Face) -> u8 {
+        match self.material {
+            Material::Door => match self.state.half() {
+                Half::Bottom => 10,
+                Half::Top => 11,
+            },
+            material => material.atlas_index(face),
+        }
+    }
+}
+
+/// Horizontal face the direction points towards the most.
+pub fn horizontal_facing(direction: Vector3<f32>) -> Face {
+    if direction.x.abs() > direction.z.abs() {
+        if direction.x > 0.0 { Face::PositiveX } else { Face::NegativeX }
+    } else if direction.z > 0.0 {
+        Face::PositiveZ
+    } else {
+        Face::NegativeZ
+    }
+}
+
+/// A property a block can have in its state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    /// horizontal direction, one of the first four `Face`s
+    Facing,
+    Axis,
+    Half,
+    Open,
+}
+
+impl Property {
+    /// `(offset, bits)` of the property inside a `BlockState`
+    const fn layout(&self) -> (u8, u8) {
+        match self {
+            Property::Facing => (0, 2),
+            Property::Axis => (2, 2),
+            Property::Half => (4, 1),
+            Property::Open => (5, 1),
+        }
+    }
+
+    pub fn value_count(&self) -> u8 {
+        match self {
+            Property::Facing => 4,
+            Property::Axis => 3,
+            Property::Half | Property::Open => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn of(face: Face) -> Axis {
+        match face {
+            Face::PositiveX | Face::NegativeX => Axis::X,
+            Face::PositiveY | Face::NegativeY => Axis::Y,
+            Face::PositiveZ | Face::NegativeZ => Axis::Z,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+/// Properties of a block packed into a byte, see `Property::layout`.
+/// Properties a material doesn't define keep their default value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockState(u8);
+
+impl Default for BlockState {
+    fn default() -> Self {
+        // facing PositiveZ, axis Y, bottom half, closed
+        Self(0).with_facing(Face::PositiveZ).with_axis(Axis::Y)
+    }
+}
+
+impl BlockState {
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn get(&self, property: Property) -> u8 {
+        let (offset, bits) = property.layout();
+        (self.0 >> offset) & ((1 << bits) - 1)
+    }
+
+    pub fn with(self, property: Property, value: u8) -> Self {
+        debug_assert!(value < property.value_count(), "{value} is out of range for {property:?}");
+        let (offset, bits) = property.layout();
+        let mask = ((1 << bits) - 1) << offset;
+        Self((self.0 & !mask) | ((value << offset) & mask))
+    }
+
+    pub fn facing(&self) -> Face {
+        Face::ALL[self.get(Property::Facing) as usize]
+    }
+
+    pub fn with_facing(self, facing: Face) -> Self {
+        debug_assert!(facing.normal().y == 0, "facing has to be horizontal");
+        self.with(Property::Facing, facing as u8)
+    }
+
+    pub fn axis(&self) -> Axis {
+        match self.get(Property::Axis) {
+            0 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+
+    pub fn with_axis(self, axis: Axis) -> Self {
+        self.with(Property::Axis, axis as u8)
+    }
+
+    pub fn half(&self) -> Half {
+        if self.get(Property::Half) == 0 { Half::Bottom } else { Half::Top }
+    }
+
+    pub fn with_half(self, half: Half) -> Self {
+        self.with(Property::Half, half as u8)
+    }
+
+    pub fn open(&self) -> bool {
+        self.get(Property::Open) != 0
+    }
+
+    pub fn with_open(self, open: bool) -> Self {
+        self.with(Property::Open, open as u8)
+    }
 }
 
 #[repr(u8)]
@@ -60,4 +284,155 @@ pub enum Material {
     Cobblestone,
     Dirt,
     Grass,
+    Leaves,
+    Glass,
+    Water,
+    StainedGlass,
+    CobblestoneSlab,
+    CobblestoneStairs,
+    Fence,
+    TallGrass,
+    Log,
+    Door,
+    Sign,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    Opaque,
+    /// fully opaque or fully transparent texels, drawn in the opaque pass with alpha testing
+    Cutout,
+    /// blended, drawn back to front after the opaque pass
+    Translucent,
+}
+
+impl Material {
+    /// Every material, indexed by its id.
+    pub const ALL: [Material; 15] = [
+        Material::Air,
+        Material::Cobblestone,
+        Material::Dirt,
+        Material::Grass,
+        Material::Leaves,
+        Material::Glass,
+        Material::Water,
+        Material::StainedGlass,
+        Material::CobblestoneSlab,
+        Material::CobblestoneStairs,
+        Material::Fence,
+        Material::TallGrass,
+        Material::Log,
+        Material::Door,
+        Material::Sign,
+    ];
+
+    pub fn from_id(id: u8) -> Option<Material> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    /// Name used by console commands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Material::Air => "air",
+            Material::Cobblestone => "cobblestone",
+            Material::Dirt => "dirt",
+            Material::Grass => "grass",
+            Material::Leaves => "leaves",
+            Material::Glass => "glass",
+            Material::Water => "water",
+            Material::StainedGlass => "stained_glass",
+            Material::CobblestoneSlab => "cobblestone_slab",
+            Material::CobblestoneStairs => "cobblestone_stairs",
+            Material::Fence => "fence",
+            Material::TallGrass => "tall_grass",
+            Material::Log => "log",
+            Material::Door => "door",
+            Material::Sign => "sign",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Material> {
+        Self::ALL.iter().copied().find(|material| material.name() == name)
+    }
+
+    pub fn transparency(&self) -> Transparency {
+        match self {
+            Material::Air => Transparency::Translucent,
+            Material::Leaves | Material::Glass | Material::TallGrass | Material::Door => Transparency::Cutout,
+            Material::Water | Material::StainedGlass => Transparency::Translucent,
+            _ => Transparency::Opaque,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.transparency() == Transparency::Opaque
+    }
+
+    /// Whether the block stops the player.
+    pub fn is_solid(&self) -> bool {
+        !matches!(self, Material::Air | Material::Water | Material::TallGrass)
+    }
+
+    /// Whether placing a block can overwrite this one.
+    pub fn is_replaceable(&self) -> bool {
+        matches!(self, Material::Air | Material::Water | Material::TallGrass)
+    }
+
+    /// Whether the block can be picked with the crosshair.
+    pub fn is_targetable(&self) -> bool {
+        !matches!(self, Material::Air | Material::Water)
+    }
+
+    /// Whether faces between two blocks of this material are hidden, like water next to water.
+    pub fn culls_same(&self) -> bool {
+        matches!(self, Material::Glass | Material::Water | Material::StainedGlass)
+    }
+
+    /// Properties stored in the state of blocks of this material.
+    pub fn properties(&self) -> &'static [Property] {
+        match self {
+            Material::CobblestoneSlab => &[Property::Half],
+            Material::CobblestoneStairs => &[Property::Facing, Property::Half],
+            Material::Log => &[Property::Axis],
+            Material::Door => &[Property::Facing, Property::Half, Property::Open],
+            Material::Sign => &[Property::Facing],
+            _ => &[],
+        }
+    }
+
+    pub fn model(&self) -> BlockModel {
+        match self {
+            Material::Door => BlockModel::Door,
+            Material::Sign => BlockModel::Sign,
+            Material::CobblestoneSlab => BlockModel::Slab,
+            Material::CobblestoneStairs => BlockModel::Stairs,
+            Material::Fence => BlockModel::Fence,
+            Material::TallGrass => BlockModel::Cross,
+            _ => BlockModel::Cube,
+        }
+    }
+
+    pub fn atlas_index(&self, face: Face) -> u8 {
+        match self {
+            Material::Air => 0,
+            Material::Cobblestone | Material::CobblestoneSlab | Material::CobblestoneStairs => 0,
+            Material::Dirt => 1,
+            Material::Grass => match face {
+                Face::PositiveY => 3,
+                Face::NegativeY => 1,
+                _ => 2,
+            },
+            Material::Leaves => 4,
+            Material::Glass => 5,
+            Material::Water => 6,
+            Material::StainedGlass => 7,
+            Material::Fence | Material::Sign => 8,
+            Material::TallGrass => 9,
+            Material::Door => 10,
+            Material::Log => match face {
+                Face::PositiveY | Face::NegativeY => 13,
+                _ => 12,
+            },
+        }
+    }
 }
\ No newline at end of file
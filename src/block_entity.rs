@@ -0,0 +1,54 @@
+use std::any::Any;
+
+use crate::{block::Material, sign::Sign};
+
+/// Extra data attached to a single block, stored on the chunk next to the block array.
+pub trait BlockEntity: Any + std::fmt::Debug {
+    fn kind(&self) -> BlockEntityKind;
+
+    /// Called once per update with the time since the last one.
+    fn tick(&mut self, _dt: f32) {}
+
+    /// Appends the entity's data, read back by `BlockEntityKind::deserialize`.
+    fn serialize(&self, data: &mut Vec<u8>);
+
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockEntityKind {
+    Sign,
+}
+
+impl BlockEntityKind {
+    pub const ALL: [BlockEntityKind; 1] = [BlockEntityKind::Sign];
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    /// Entity for a freshly placed block.
+    pub fn create(&self) -> Box<dyn BlockEntity> {
+        match self {
+            BlockEntityKind::Sign => Box::new(Sign::default()),
+        }
+    }
+
+    pub fn deserialize(&self, data: &[u8]) -> anyhow::Result<Box<dyn BlockEntity>> {
+        match self {
+            BlockEntityKind::Sign => Ok(Box::new(Sign::deserialize(data)?)),
+        }
+    }
+}
+
+impl Material {
+    /// Kind of block entity blocks of this material carry.
+    pub fn block_entity(&self) -> Option<BlockEntityKind> {
+        match self {
+            Material::Sign => Some(BlockEntityKind::Sign),
+            _ => None,
+        }
+    }
+}
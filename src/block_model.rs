@@ -0,0 +1,231 @@
+use std::sync::OnceLock;
+
+use cgmath::{Point3, Vector3};
+
+use crate::{block::Block, block_vertex::{Face, SUB_VOXEL_PRECISION}};
+
+const FULL: u8 = SUB_VOXEL_PRECISION as u8;
+
+/// Part of a block model, in 1/16ths of a block.
+#[derive(Debug, Clone, Copy)]
+pub enum ModelElement {
+    Cuboid { from: [u8; 3], to: [u8; 3] },
+    /// two diagonal double sided quads, used for plants
+    Cross,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ModelFace {
+    /// corners in 1/16ths of a block, in the same winding as `Block::FACE_VERTICES`
+    pub positions: [Point3<u8>; 4],
+    pub uvs: [[u8; 2]; 4],
+    /// direction used for shading
+    pub face: Face,
+    /// side of the unrotated model, used to pick the texture so it rotates with the model
+    pub texture_face: Face,
+    /// neighbor that can hide this face, `None` for faces that don't touch the block boundary
+    pub cull: Option<Face>,
+}
+
+pub struct ModelGeometry {
+    pub faces: Vec<ModelFace>,
+    /// which sides of the block are completely covered by the model, in `Face` order
+    pub covers: [bool; 6],
+    /// `(min, max)` corners of the cuboids, in 1/16ths of a block
+    pub boxes: Vec<(Point3<u8>, Point3<u8>)>,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockModel {
+    Cube,
+    Slab,
+    Stairs,
+    Fence,
+    Cross,
+    Door,
+    Sign,
+}
+
+/// Quarter turns applied to a model, first around the x axis and then around the y axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModelRotation {
+    pub x: u8,
+    pub y: u8,
+}
+
+impl ModelRotation {
+    pub const IDENTITY: ModelRotation = ModelRotation { x: 0, y: 0 };
+
+    pub fn new(x: u8, y: u8) -> Self {
+        Self { x: x % 4, y: y % 4 }
+    }
+
+    /// Rotates a point in 1/16ths of a block around the block center.
+    pub fn rotate_position(&self, position: Point3<u8>) -> Point3<u8> {
+        let mut p = position;
+        for _ in 0..self.x {
+            p = Point3::new(p.x, FULL - p.z, p.y);
+        }
+        for _ in 0..self.y {
+            p = Point3::new(FULL - p.z, p.y, p.x);
+        }
+        p
+    }
+
+    /// Rotates a point in block units around the block center.
+    pub fn rotate_point(&self, point: Point3<f32>) -> Point3<f32> {
+        let mut p = point;
+        for _ in 0..self.x {
+            p = Point3::new(p.x, 1.0 - p.z, p.y);
+        }
+        for _ in 0..self.y {
+            p = Point3::new(1.0 - p.z, p.y, p.x);
+        }
+        p
+    }
+
+    pub fn rotate_face(&self, face: Face) -> Face {
+        let mut n = face.normal();
+        for _ in 0..self.x {
+            n = Vector3::new(n.x, -n.z, n.y);
+        }
+        for _ in 0..self.y {
+            n = Vector3::new(-n.z, n.y, n.x);
+        }
+        Face::from_normal(n).unwrap_or(face)
+    }
+
+    fn index(&self) -> usize {
+        self.x as usize * 4 + self.y as usize
+    }
+}
+
+impl BlockModel {
+    pub const ALL: [BlockModel; 7] = [BlockModel::Cube, BlockModel::Slab, BlockModel::Stairs, BlockModel::Fence, BlockModel::Cross, BlockModel::Door, BlockModel::Sign];
+
+    pub fn elements(&self) -> &'static [ModelElement] {
+        match self {
+            BlockModel::Cube => &[ModelElement::Cuboid { from: [0, 0, 0], to: [16, 16, 16] }],
+            BlockModel::Slab => &[ModelElement::Cuboid { from: [0, 0, 0], to: [16, 8, 16] }],
+            BlockModel::Stairs => &[
+                ModelElement::Cuboid { from: [0, 0, 0], to: [16, 8, 16] },
+                ModelElement::Cuboid { from: [0, 8, 8], to: [16, 16, 16] },
+            ],
+            BlockModel::Fence => &[ModelElement::Cuboid { from: [6, 0, 6], to: [10, 16, 10] }],
+            BlockModel::Cross => &[ModelElement::Cross],
+            BlockModel::Door => &[ModelElement::Cuboid { from: [0, 0, 13], to: [16, 16, 16] }],
+            // board hanging on the wall behind it
+            BlockModel::Sign => &[ModelElement::Cuboid { from: [0, 4, 14], to: [16, 12, 16] }],
+        }
+    }
+
+    /// Geometry of every model in all 16 rotations, built once.
+    pub fn geometry(&self, rotation: ModelRotation) -> &'static ModelGeometry {
+        static GEOMETRY: OnceLock<Vec<ModelGeometry>> = OnceLock::new();
+        let geometry = GEOMETRY.get_or_init(|| Self::ALL.iter().flat_map(|model| {
+            (0..16).map(|i| ModelGeometry::new(model.elements(), ModelRotation::new(i / 4, i % 4)))
+        }).collect());
+        &geometry[*self as usize * 16 + rotation.index()]
+    }
+}
+
+impl ModelGeometry {
+    pub fn new(elements: &[ModelElement], rotation: ModelRotation) -> Self {
+        let mut faces = vec![];
+        let mut boxes = vec![];
+        for element in elements {
+            match *element {
+                ModelElement::Cuboid { from, to } => {
+                    for face in Face::ALL {
+                        faces.push(cuboid_face(from, to, face));
+                    }
+
+                    let (a, b) = (rotation.rotate_position(from.into()), rotation.rotate_position(to.into()));
+                    boxes.push((Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)), Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))));
+                },
+                ModelElement::Cross => faces.extend(cross_faces()),
+            }
+        }
+
+        for model_face in faces.iter_mut() {
+            model_face.positions = model_face.positions.map(|position| rotation.rotate_position(position));
+            model_face.face = rotation.rotate_face(model_face.face);
+            model_face.cull = model_face.cull.map(|cull| rotation.rotate_face(cull));
+        }
+
+        let mut covers = [false; 6];
+        for face in Face::ALL {
+            let mut mask = [[false; FULL as usize]; FULL as usize];
+            for model_face in faces.iter().filter(|model_face| model_face.cull == Some(face)) {
+                let (u, v) = face_plane_axes(face);
+                let (min_u, max_u) = min_max(model_face.positions.map(|p| p[u]));
+                let (min_v, max_v) = min_max(model_face.positions.map(|p| p[v]));
+                for row in &mut mask[min_v as usize..max_v as usize] {
+                    row[min_u as usize..max_u as usize].fill(true);
+                }
+            }
+            covers[face as usize] = mask.iter().all(|row| row.iter().all(|&covered| covered));
+        }
+
+        Self { faces, covers, boxes }
+    }
+}
+
+fn cuboid_face(from: [u8; 3], to: [u8; 3], face: Face) -> ModelFace {
+    let positions = Block::FACE_VERTICES[face as usize].map(|corner| Point3::new(
+        if corner.x == 0 { from[0] } else { to[0] },
+        if corner.y == 0 { from[1] } else { to[1] },
+        if corner.z == 0 { from[2] } else { to[2] },
+    ));
+
+    let uvs = positions.map(|p| match face {
+        Face::PositiveX => [FULL - p.z, FULL - p.y],
+        Face::NegativeX => [p.z, FULL - p.y],
+        Face::PositiveZ => [p.x, FULL - p.y],
+        Face::NegativeZ => [FULL - p.x, FULL - p.y],
+        Face::PositiveY | Face::NegativeY => [p.x, p.z],
+    });
+
+    let on_boundary = match face {
+        Face::PositiveX => to[0] == FULL,
+        Face::NegativeX => from[0] == 0,
+        Face::PositiveZ => to[2] == FULL,
+        Face::NegativeZ => from[2] == 0,
+        Face::PositiveY => to[1] == FULL,
+        Face::NegativeY => from[1] == 0,
+    };
+
+    ModelFace { positions, uvs, face, texture_face: face, cull: on_boundary.then_some(face) }
+}
+
+fn cross_faces() -> [ModelFace; 4] {
+    let quad = |positions: [[u8; 3]; 4]| ModelFace {
+        positions: positions.map(|[x, y, z]| Point3::new(x, y, z)),
+        uvs: [[0, FULL], [FULL, FULL], [0, 0], [FULL, 0]],
+        // plants are shaded like the top of a block
+        face: Face::PositiveY,
+        texture_face: Face::PositiveY,
+        cull: None,
+    };
+
+    [
+        quad([[16, 0, 16], [0, 0, 0], [16, 16, 16], [0, 16, 0]]),
+        quad([[0, 0, 0], [16, 0, 16], [0, 16, 0], [16, 16, 16]]),
+        quad([[0, 0, 16], [16, 0, 0], [0, 16, 16], [16, 16, 0]]),
+        quad([[16, 0, 0], [0, 0, 16], [16, 16, 0], [0, 16, 16]]),
+    ]
+}
+
+/// The two axes spanning the plane of a face.
+fn face_plane_axes(face: Face) -> (usize, usize) {
+    match face {
+        Face::PositiveX | Face::NegativeX => (2, 1),
+        Face::PositiveZ | Face::NegativeZ => (0, 1),
+        Face::PositiveY | Face::NegativeY => (0, 2),
+    }
+}
+
+fn min_max(values: [u8; 4]) -> (u8, u8) {
+    (values.into_iter().min().unwrap_or(0), values.into_iter().max().unwrap_or(0))
+}
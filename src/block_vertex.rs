@@ -1,24 +1,30 @@
-use cgmath::Point3;
-use wgpu::vertex_attr_array;
+use cgmath::{Point3, Vector3};
 
-#[derive(Debug, Clone, Copy)]
+/// Vertex positions and texture coordinates are stored in 1/16ths of a block.
+pub const SUB_VOXEL_PRECISION: u16 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BlockVertex {
-    pub position: Point3<u8>,
+    /// position in sub chunk space, in 1/16ths of a block
+    pub position: Point3<u16>,
+    /// texture coordinates inside the atlas tile, in 1/16ths of a tile
+    pub uv: [u8; 2],
     pub face: Face,
+    pub atlas_index: u8,
 }
 
 impl BlockVertex {
-    pub fn new(position: Point3<u8>, face: Face) -> Self {
-        Self { position, face }
+    pub fn new(position: Point3<u16>, uv: [u8; 2], face: Face, atlas_index: u8) -> Self {
+        Self { position, uv, face, atlas_index }
     }
 
     pub fn pack(&self) -> PackedBlockVertex {
-        PackedBlockVertex::new(self.position, self.face)
+        PackedBlockVertex::new(self.position, self.uv, self.face, self.atlas_index)
     }
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Face {
     PositiveX,
     NegativeX,
@@ -28,38 +34,76 @@ pub enum Face {
     NegativeY,
 }
 
+impl Face {
+    pub const ALL: [Face; 6] = [Face::PositiveX, Face::NegativeX, Face::PositiveZ, Face::NegativeZ, Face::PositiveY, Face::NegativeY];
+
+    pub fn opposite(&self) -> Face {
+        match self {
+            Face::PositiveX => Face::NegativeX,
+            Face::NegativeX => Face::PositiveX,
+            Face::PositiveZ => Face::NegativeZ,
+            Face::NegativeZ => Face::PositiveZ,
+            Face::PositiveY => Face::NegativeY,
+            Face::NegativeY => Face::PositiveY,
+        }
+    }
+
+    pub fn from_normal(normal: Vector3<i32>) -> Option<Face> {
+        Face::ALL.into_iter().find(|face| face.normal() == normal)
+    }
+
+    pub fn normal(&self) -> Vector3<i32> {
+        match self {
+            Face::PositiveX => Vector3::new(1, 0, 0),
+            Face::NegativeX => Vector3::new(-1, 0, 0),
+            Face::PositiveZ => Vector3::new(0, 0, 1),
+            Face::NegativeZ => Vector3::new(0, 0, -1),
+            Face::PositiveY => Vector3::new(0, 1, 0),
+            Face::NegativeY => Vector3::new(0, -1, 0),
+        }
+    }
+}
+
 
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-pub struct PackedBlockVertex(u32);
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct PackedBlockVertex([u32; 2]);
 
 impl PackedBlockVertex {
-    pub fn new(position: Point3<u8>, face: Face) -> Self {
-        Self(position.x as u32 | (position.y as u32) << 6 | (position.z as u32) << 12 | (face as u32) << 18)
+    /// `[x: 10, y: 10, z: 10]`, `[face: 3, u: 5, v: 5, atlas index: 8]`
+    pub fn new(position: Point3<u16>, uv: [u8; 2], face: Face, atlas_index: u8) -> Self {
+        Self([
+            position.x as u32 | (position.y as u32) << 10 | (position.z as u32) << 20,
+            face as u32 | (uv[0] as u32) << 3 | (uv[1] as u32) << 8 | (atlas_index as u32) << 13,
+        ])
     }
 
-    const ATTRIBUTES: &'static [wgpu::VertexAttribute] = &vertex_attr_array![0 => Uint32];
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<PackedBlockVertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: Self::ATTRIBUTES,
+    pub fn unpack(&self) -> BlockVertex {
+        let [position, rest] = self.0;
+        BlockVertex {
+            position: Point3::new(position & 0x3ff, position >> 10 & 0x3ff, position >> 20 & 0x3ff).cast().unwrap(),
+            uv: [(rest >> 3 & 0x1f) as u8, (rest >> 8 & 0x1f) as u8],
+            face: Face::ALL[(rest & 0x7) as usize],
+            atlas_index: (rest >> 13) as u8,
         }
     }
 }
+
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct VertexConstant {
     pub chunk_translation_offset: [i32; 3],
 }
 
-impl VertexConstant {
-    const ATTRIBUTES: &'static [wgpu::VertexAttribute] = &vertex_attr_array![1 => Sint32x3];
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<[i32; 3]>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Instance,
-            attributes: Self::ATTRIBUTES
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packing_round_trips() {
+        for face in Face::ALL {
+            let vertex = BlockVertex::new(Point3::new(512, 1023, 16), [16, 0], face, 255);
+            assert_eq!(vertex.pack().unpack(), vertex);
         }
     }
-}
\ No newline at end of file
+}
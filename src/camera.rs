@@ -28,6 +28,14 @@ impl Camera {
         }
     }
 
+    /// Turns the camera to look along `direction`, keeping yaw and pitch in sync with it.
+    pub fn look_in(&mut self, direction: Vector3<f32>) {
+        let direction = direction.normalize();
+        self.yaw = Rad(direction.z.atan2(direction.x));
+        self.pitch = Rad(direction.y.asin().clamp(-PITCH_LIMIT, PITCH_LIMIT));
+        self.direction = direction;
+    }
+
     pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
         1.0, 0.0, 0.0, 0.0,
         0.0, 1.0, 0.0, 0.0,
@@ -41,22 +49,32 @@ impl Camera {
 
         Self::OPENGL_TO_WGPU_MATRIX * projection * view
     }
+
+    /// View projection without the camera translation, used to reconstruct view rays for the sky.
+    pub fn build_sky_view_projection_matrix(&self) -> Matrix4<f32> {
+        let view = Matrix4::look_to_rh(Point3::new(0.0, 0.0, 0.0), self.direction, self.up);
+        let projection = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+
+        Self::OPENGL_TO_WGPU_MATRIX * projection * view
+    }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct CameraUniform {
-    pub view_projection: [[f32; 4]; 4]
+    pub view_projection: [[f32; 4]; 4],
+    pub position: [f32; 4],
 }
 
 impl CameraUniform {
     pub fn new() -> Self {
         use cgmath::SquareMatrix;
-        Self { view_projection: cgmath::Matrix4::identity().into() }
+        Self { view_projection: cgmath::Matrix4::identity().into(), position: [0.0; 4] }
     }
 
     pub fn update_view_projection(&mut self, camera: &Camera) {
         self.view_projection = camera.build_view_projection_matrix().into();
+        self.position = camera.eye.to_homogeneous().into();
     }
 }
 
@@ -69,37 +87,76 @@ pub struct Controls {
     pub up_pressed: bool,
     pub down_pressed: bool,
     pub f1_toggled: bool,
+    pub chunk_borders_toggled: bool,
+    pub debug_overlay_toggled: bool,
+    pub sub_chunk_bounds_toggled: bool,
+    /// noclip flight instead of walking
+    pub spectator_toggled: bool,
+    /// set on click, cleared once the block was broken or placed
+    pub break_block: bool,
+    pub place_block: bool,
+    /// index into `player::HOTBAR`
+    pub selected_slot: usize,
+}
+
+impl Controls {
+    /// The held movement keys, for the player physics.
+    pub fn movement(&self) -> Movement {
+        Movement { forward: self.forward_pressed, backward: self.backward_pressed, left: self.left_pressed, right: self.right_pressed, jump: self.up_pressed }
+    }
+
+    /// Lets go of the movement keys and pending clicks, for when the game stops receiving input.
+    pub fn release_movement(&mut self) {
+        self.forward_pressed = false;
+        self.backward_pressed = false;
+        self.left_pressed = false;
+        self.right_pressed = false;
+        self.up_pressed = false;
+        self.down_pressed = false;
+        self.break_block = false;
+        self.place_block = false;
+    }
 }
 
 pub struct CameraController {
     pub speed: f32,
+    /// degrees turned per pixel of mouse movement
+    pub sensitivity: f32,
+    pub invert_y: bool,
     pub controls: Controls
 }
-use winit::{event::*, keyboard::{KeyCode, PhysicalKey}};
+use crate::{input::Action, player::{Movement, HOTBAR}};
 impl CameraController {
     pub fn new(speed: f32) -> Self {
         Self {
             speed,
+            sensitivity: 1.0 / 8.0,
+            invert_y: false,
             controls: Controls::default()
         }
     }
 
-    pub fn process_events(&mut self, event: &WindowEvent) {
-        match event {
-            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key_code), state, .. }, .. } => {
-                let pressed = state.is_pressed();
-                match key_code {
-                    KeyCode::KeyW => { self.controls.forward_pressed = pressed },
-                    KeyCode::KeyS => { self.controls.backward_pressed = pressed },
-                    KeyCode::KeyA => { self.controls.left_pressed = pressed },
-                    KeyCode::KeyD => { self.controls.right_pressed = pressed },
-                    KeyCode::Space => { self.controls.up_pressed = pressed },
-                    KeyCode::ShiftLeft => { self.controls.down_pressed = pressed },
-                    KeyCode::F1 if pressed => { self.controls.f1_toggled = !self.controls.f1_toggled }
-                    _ => ()
-                }
-            }
-            _ => ()
+    /// Applies an action pressed or released through the input map.
+    pub fn process_action(&mut self, action: Action, pressed: bool) {
+        let controls = &mut self.controls;
+        match action {
+            Action::MoveForward => { controls.forward_pressed = pressed },
+            Action::MoveBackward => { controls.backward_pressed = pressed },
+            Action::MoveLeft => { controls.left_pressed = pressed },
+            Action::MoveRight => { controls.right_pressed = pressed },
+            Action::Jump => { controls.up_pressed = pressed },
+            Action::Descend => { controls.down_pressed = pressed },
+            _ if !pressed => (),
+            Action::BreakBlock => { controls.break_block = true },
+            Action::PlaceBlock => { controls.place_block = true },
+            Action::HotbarNext => { controls.selected_slot = (controls.selected_slot + 1) % HOTBAR.len() },
+            Action::HotbarPrevious => { controls.selected_slot = (controls.selected_slot + HOTBAR.len() - 1) % HOTBAR.len() },
+            Action::ToggleWireframe => { controls.f1_toggled = !controls.f1_toggled },
+            Action::ToggleChunkBorders => { controls.chunk_borders_toggled = !controls.chunk_borders_toggled },
+            Action::ToggleDebug => { controls.debug_overlay_toggled = !controls.debug_overlay_toggled },
+            Action::ToggleSubChunkBounds => { controls.sub_chunk_bounds_toggled = !controls.sub_chunk_bounds_toggled },
+            Action::ToggleSpectator => { controls.spectator_toggled = !controls.spectator_toggled },
+            action => if let Some(slot) = Action::HOTBAR.iter().position(|hotbar| *hotbar == action) { controls.selected_slot = slot },
         }
     }
 
@@ -133,8 +190,9 @@ impl CameraController {
     }
 
     pub fn mouse_move(&mut self, delta_x: f32, delta_y: f32, camera: &mut Camera) {
-        camera.yaw += Deg(delta_x / 8.0).into();
-        camera.pitch -= Deg(delta_y / 8.0).into();
+        let delta_y = if self.invert_y { -delta_y } else { delta_y };
+        camera.yaw += Deg(delta_x * self.sensitivity).into();
+        camera.pitch -= Deg(delta_y * self.sensitivity).into();
         camera.pitch.0 = camera.pitch.0.clamp(-PITCH_LIMIT, PITCH_LIMIT);
 
         let (sin_pitch, cos_pitch) = camera.pitch.0.sin_cos();
@@ -1,279 +1,158 @@
-use cgmath::{Point2, Point3};
-use rand::Rng;
-use wgpu::util::DeviceExt;
-use std::{collections::HashMap, ops::{Index, IndexMut}};
+use cgmath::{Point2, Point3, Vector2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::{HashMap, HashSet}, ops::{Index, IndexMut}};
 
-use crate::{block::*, block_vertex::VertexConstant, camera::*};
+use crate::{block::*, block_entity::BlockEntity};
 
+#[derive(Default)]
 pub struct ChunkManager {
     pub chunks: HashMap<u64, Chunk>,
+    /// sub chunks whose blocks changed since they were last taken for remeshing
+    pub dirty_sub_chunks: HashSet<(Point2<i32>, usize)>,
+    /// chunks whose block entities changed since they were last taken, their sign text is rebuilt
+    pub dirty_block_entities: HashSet<Point2<i32>>,
+    /// positions set through `set_block` since they were last taken, sent to the other side of a connection
+    pub changed_blocks: Vec<Point3<i32>>,
+    /// whether `set_block` records into `changed_blocks`, only on while a connection drains it
+    pub track_changes: bool,
 }
 
 impl ChunkManager {
     pub fn new() -> Self {
-        Self { chunks: HashMap::new() }
+        Self { chunks: HashMap::new(), dirty_sub_chunks: HashSet::new(), dirty_block_entities: HashSet::new(), changed_blocks: vec![], track_changes: false }
+    }
+
+    /// Map key of a chunk position, the coordinates are reinterpreted as unsigned so negative ones don't overlap.
+    pub fn key(position: Point2<i32>) -> u64 {
+        position.x as u32 as u64 | (position.y as u32 as u64) << 32
     }
 
     pub fn get(&self, position: Point2<i32>) -> Option<&Chunk> {
-        self.chunks.get(&(position.x as u64 | (position.y as u64) << 32))
+        self.chunks.get(&Self::key(position))
     }
-}
 
-pub struct World {
-    pub loaded_chunks: ChunkManager,
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub camera: Camera,
-    pub camera_controller: CameraController,
-    pub camera_uniform: CameraUniform,
-    pub camera_buffer: wgpu::Buffer,
-    pub camera_bind_group: wgpu::BindGroup,
-    pub texture_atlas_bind_group: wgpu::BindGroup,
-    pub depth_texture: crate::texture::Texture,
-    pub material_texture_bind_group_layout: wgpu::BindGroupLayout,
-}
+    pub fn get_mut(&mut self, position: Point2<i32>) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&Self::key(position))
+    }
 
-impl World {
-    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, queue: &wgpu::Queue) -> Self {
-        // camera
-        let camera = Camera::default(config.width, config.height);
-
-        // camera controller
-        let camera_controller = CameraController::new(5.0);
-        
-        // camera uniform
-        let camera_uniform = CameraUniform::new();
-
-        // camera buffer
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("camera buffer"),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-        });
-
-        // camera bind group layout
-        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("camera bind group layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None
-                    },
-                    count: None,
-                    visibility: wgpu::ShaderStages::VERTEX
-                }
-            ]
-        });
-
-        // camera bind group
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("camera bind group"),
-            layout: &camera_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding()
-                }
-            ]
-        });
-        
-        // shader module
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("chunk shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("chunk.wgsl").into())
-        });
-
-        let texture_atlas_bytes = include_bytes!("textures/texture_atlas.png");
-        let texture_atlas = crate::texture::Texture::from_bytes(&device, &queue, texture_atlas_bytes, "texture").unwrap();
-
-        let texture_atlas_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("texture bind group"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
-                    },
-                    count: None
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None
-                }
-            ]
-        });
-
-        let texture_atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("diffuse bind group"),
-            layout: &texture_atlas_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_atlas.view)
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture_atlas.sampler)
-                }
-            ]
-        });
-
-        let material_texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("3d material texture bind group layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    count: None,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Uint,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                        multisampled: false
-                    },
-                    visibility: wgpu::ShaderStages::FRAGMENT
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    count: None,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    visibility: wgpu::ShaderStages::FRAGMENT
-                },
-            ]
-        });
-
-        // pipeline layout
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("chunk pipeline layout"),
-            bind_group_layouts: &[
-                &texture_atlas_bind_group_layout,
-                &camera_bind_group_layout,
-                &material_texture_bind_group_layout,
-            ],
-            push_constant_ranges: &[]
-        });
-
-        // pipeline
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                buffers: &[
-                    crate::block_vertex::PackedBlockVertex::desc(),
-                    VertexConstant::desc(),
-                ],
-                entry_point: "vs_main"
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL
-                })]
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: crate::texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false
-            },
-            multiview: None
-        });
-
-        let depth_texture = crate::texture::Texture::create_depth_texture(&device, &config, "depth texture");
-
-        Self { camera, camera_bind_group, camera_buffer, camera_controller, camera_uniform, loaded_chunks: ChunkManager::new(), render_pipeline, texture_atlas_bind_group, depth_texture, material_texture_bind_group_layout }
+    /// Adds or replaces a chunk, all of it is dirty.
+    pub fn insert(&mut self, chunk: Chunk) {
+        self.dirty_sub_chunks.extend((0..SUB_CHUNKS).map(|index| (chunk.position, index)));
+        self.dirty_block_entities.insert(chunk.position);
+        self.chunks.insert(Self::key(chunk.position), chunk);
     }
-    pub fn generate_chunks(&mut self) {
 
+    /// Unloads a chunk, nothing of it is left to remesh.
+    pub fn remove(&mut self, position: Point2<i32>) -> Option<Chunk> {
+        self.dirty_sub_chunks.retain(|(dirty, _)| *dirty != position);
+        self.dirty_block_entities.remove(&position);
+        self.chunks.remove(&Self::key(position))
     }
 
-    pub fn render(&self, device: &wgpu::Device, queue: &wgpu::Queue, surface: &wgpu::Surface, window: &winit::window::Window) {
-        let output = surface.get_current_texture().unwrap();
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("render encoder")
-        });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color { r: 123.0 / 255.0, g: 164.0 / 255.0, b: 1.0, a: 1.0 }),
-                        store: wgpu::StoreOp::Store
-                    }
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None
-            });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.texture_atlas_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-
-            for chunk in self.loaded_chunks.chunks.values() {
-                for sub_chunk in chunk.sub_chunks.iter() {
-                    if let Some(sub_chunk) = sub_chunk {
-                        render_pass.set_vertex_buffer(0, sub_chunk.mesh.vertex_buffer.slice(..));
-                        render_pass.set_vertex_buffer(1, sub_chunk.translation_buffer.slice(..));
-
-                        render_pass.set_index_buffer(sub_chunk.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-
-                        render_pass.set_bind_group(2, &sub_chunk.material_3d_texture_bind_group, &[]);
-                        
-                        render_pass.draw_indexed(0..sub_chunk.mesh.indices, 0, 0..1);
-                    }
+    /// Block at a world position, `None` if its chunk isn't loaded or it's above or below the world.
+    pub fn block(&self, position: Point3<i32>) -> Option<&Block> {
+        if position.y < 0 || position.y >= CHUNK_HEIGHT as i32 { return None; }
+        let (chunk_position, local) = chunk_local_position(position);
+        Some(&self.get(chunk_position)?[local])
+    }
+
+    /// Replaces a block, marking its sub chunk and the neighboring sub chunks whose faces it touches dirty.
+    /// Returns false if the position isn't in a loaded chunk.
+    pub fn set_block(&mut self, position: Point3<i32>, block: Block) -> bool {
+        let Some(had_block_entity) = self.replace_block(position, block) else { return false; };
+
+        self.dirty_sub_chunks.extend(dirty_sub_chunks(position));
+        if self.track_changes { self.changed_blocks.push(position); }
+        if had_block_entity || block.material.block_entity().is_some() {
+            self.dirty_block_entities.insert(chunk_local_position(position).0);
+        }
+        true
+    }
+
+    /// Sets every block in the box between two corners, returning how many blocks were in loaded chunks.
+    pub fn fill(&mut self, from: Point3<i32>, to: Point3<i32>, block: Block) -> usize {
+        let min = Point3::new(from.x.min(to.x), from.y.min(to.y), from.z.min(to.z));
+        let max = Point3::new(from.x.max(to.x), from.y.max(to.y), from.z.max(to.z));
+
+        let mut changed = 0;
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    if self.set_block(Point3::new(x, y, z), block) { changed += 1; }
                 }
             }
         }
+        changed
+    }
+
+    /// Sets a block without marking anything dirty, returning whether the old block had a block entity or `None` outside the loaded chunks.
+    fn replace_block(&mut self, position: Point3<i32>, block: Block) -> Option<bool> {
+        if position.y < 0 || position.y >= CHUNK_HEIGHT as i32 { return None; }
+        let (chunk_position, local) = chunk_local_position(position);
+        let chunk = self.get_mut(chunk_position)?;
 
-        queue.submit(std::iter::once(encoder.finish()));
-        window.pre_present_notify();
-        output.present();
+        let had_block_entity = chunk[local].material.block_entity().is_some();
+        chunk.set_block(local, block);
+        Some(had_block_entity)
     }
 }
 
+/// The sub chunk of a block and the neighboring ones whose faces it touches.
+fn dirty_sub_chunks(position: Point3<i32>) -> Vec<(Point2<i32>, usize)> {
+    let (chunk_position, local) = chunk_local_position(position);
+    let index = local.1 / SUB_CHUNK_HEIGHT;
+    let mut dirty = vec![(chunk_position, index)];
+    if local.1 % SUB_CHUNK_HEIGHT == 0 && index > 0 {
+        dirty.push((chunk_position, index - 1));
+    }
+    if local.1 % SUB_CHUNK_HEIGHT == SUB_CHUNK_HEIGHT - 1 && index + 1 < SUB_CHUNKS {
+        dirty.push((chunk_position, index + 1));
+    }
+    for (local, offset) in [(local.0, Vector2::new(1, 0)), (local.2, Vector2::new(0, 1))] {
+        if local == 0 { dirty.push((chunk_position - offset, index)); }
+        if local == CHUNK_SIZE - 1 { dirty.push((chunk_position + offset, index)); }
+    }
+    dirty
+}
+
+/// Splits a world block position into the position of its chunk and the position inside it.
+pub fn chunk_local_position(position: Point3<i32>) -> (Point2<i32>, (usize, usize, usize)) {
+    let size = CHUNK_SIZE as i32;
+    let chunk_position = Point2::new(position.x.div_euclid(size), position.z.div_euclid(size));
+    (chunk_position, (position.x.rem_euclid(size) as usize, position.y as usize, position.z.rem_euclid(size) as usize))
+}
+
+/// Chunks between two chunk positions along the axis they're farthest apart on, the chunks loaded around a player are a square.
+pub fn chunk_distance(a: Point2<i32>, b: Point2<i32>) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+/// Chunk positions at most `distance` from `center`, nearest first.
+pub fn chunks_around(center: Point2<i32>, distance: i32) -> Vec<Point2<i32>> {
+    let mut positions: Vec<Point2<i32>> = (-distance..=distance).flat_map(|x| (-distance..=distance).map(move |z| center + Vector2::new(x, z))).collect();
+    positions.sort_by_key(|position| (position.x - center.x).pow(2) + (position.y - center.y).pow(2));
+    positions
+}
+
+/// Chunks stay loaded this far past the view distance, so walking back and forth over a chunk border doesn't unload and reload them.
+pub const UNLOAD_MARGIN: i32 = 1;
+
 pub const CHUNK_SIZE: usize = 32;
 pub const CHUNK_HEIGHT: usize = 256;
 pub const SUB_CHUNK_HEIGHT: usize = 32;
+/// Sub chunks in a chunk's column.
+pub const SUB_CHUNKS: usize = CHUNK_HEIGHT / SUB_CHUNK_HEIGHT;
+
+pub fn sub_chunk_origin(chunk_position: Point2<i32>, index: usize) -> Point3<f32> {
+    Point3::new((chunk_position.x * CHUNK_SIZE as i32) as f32, (index * SUB_CHUNK_HEIGHT) as f32, (chunk_position.y * CHUNK_SIZE as i32) as f32)
+}
 
+#[derive(Debug)]
 pub struct Chunk {
     pub position: Point2<i32>,
     pub blocks: Box<[Block]>,
-    pub sub_chunks: [Option<SubChunk>; 8]
+    /// keyed by position inside the chunk
+    pub block_entities: HashMap<Point3<u8>, Box<dyn BlockEntity>>,
 }
 
 impl Index<(usize, usize, usize)> for Chunk {
@@ -290,113 +169,19 @@ impl IndexMut<(usize, usize, usize)> for Chunk {
 }
 
 impl Chunk {
-    pub fn load_subchunk(&mut self, index: usize, device: &wgpu::Device, queue: &wgpu::Queue) {
-        let mut index_offset = 0;
-        let mut vertices = vec![];
-        let mut indices = vec![];
-        let y_offset = index * SUB_CHUNK_HEIGHT;
-
-        let mut i = 0;
-        let mut material_data = [0; SUB_CHUNK_HEIGHT * CHUNK_SIZE * CHUNK_SIZE];
-
-        for y in y_offset..y_offset + SUB_CHUNK_HEIGHT {
-            for z in 0..CHUNK_SIZE {
-                for x in 0..CHUNK_SIZE {
-                    let block = &self[(x, y, z)];
-                    material_data[i] = block.material as u8;
-                    i += 1;
-                    if block.material == Material::Air { continue; }
-                    
-                    for (i, face) in Block::FACE_VERTICES.iter().cloned().enumerate() {
-                        if !self.is_face_visible(i, x, y, z) { continue; }
-                        for mut vertex in face {
-                            vertex.position.x += x as u8;
-                            vertex.position.y += (y - y_offset) as u8;
-                            vertex.position.z += z as u8;
-
-                            vertices.push(vertex.pack());
-                        }
-
-                        for index in Block::FACE_INDICES {
-                            indices.push(index + index_offset);
-                        }
-
-                        index_offset += 4;
-                    }
-                }
-            }
-        }
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("chunk mesh vertex buffer"),
-            usage: wgpu::BufferUsages::VERTEX,
-            contents: bytemuck::cast_slice(vertices.as_slice())
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("chunk mesh index buffer"),
-            usage: wgpu::BufferUsages::INDEX,
-            contents: bytemuck::cast_slice(indices.as_slice())
-        });
-
-        let texture = crate::texture::Texture::create_3d_material_texture(device, queue, &material_data);
-        let material_texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("3d material texture bind group layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    count: None,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Uint,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                        multisampled: false
-                    },
-                    visibility: wgpu::ShaderStages::FRAGMENT
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    count: None,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    visibility: wgpu::ShaderStages::FRAGMENT
-                },
-            ]
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("3d material texture bind group"),
-            layout: &material_texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view)
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler)
-                }
-            ]
-        });
-
-        let translation_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("chunk_translation_buffer"),
-            usage: wgpu::BufferUsages::VERTEX,
-            contents: bytemuck::cast_slice(&[VertexConstant { chunk_translation_offset: [self.position.x * CHUNK_SIZE as i32, y_offset as i32, self.position.y * CHUNK_SIZE as i32]}])
-        });
-
-        self.sub_chunks[index] = Some(SubChunk { mesh: ChunkMesh { vertex_buffer, index_buffer, indices: indices.len() as u32 }, material_3d_texture_bind_group: bind_group, translation_buffer })
-    }
-
     pub fn new(position: Point2<i32>) -> Self {
         Self { 
             position,
-            blocks: vec![Block { material: Material::Cobblestone }; CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT].into_boxed_slice(),
-            sub_chunks: [None, None, None, None, None, None, None, None]
+            blocks: vec![Block::new(Material::Cobblestone); CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT].into_boxed_slice(),
+            block_entities: HashMap::new(),
         }
     }
 
-    pub fn randomized(position: Point2<i32>) -> Self {
+    /// Random blocks, the same for the same seed and position.
+    pub fn randomized(position: Point2<i32>, seed: u64) -> Self {
         let mut blocks = vec![];
 
-        let mut rng = rand::thread_rng();
+        let mut rng = StdRng::seed_from_u64(seed ^ (ChunkManager::key(position)).wrapping_mul(0x9e37_79b9_7f4a_7c15));
 
         for _ in 0..CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT {
             let material = match rng.gen_range(0..4) {
@@ -407,70 +192,98 @@ impl Chunk {
                 _ => unreachable!()
             };
     
-            blocks.push(Block { material });
+            blocks.push(Block::new(material));
         }
 
-        Self { position, blocks: blocks.into_boxed_slice(), sub_chunks: [None, None, None, None, None, None, None, None] }
+        Self { position, blocks: blocks.into_boxed_slice(), block_entities: HashMap::new() }
     }
 
-    fn is_face_visible(&self, face_index: usize, x: usize, y: usize, z: usize) -> bool {
-        
-        match face_index {
-            0 => {
-                if x + 1 < CHUNK_SIZE {
-                    self[(x + 1, y, z)].material == Material::Air
-                } else {
-                    true
-                }
-            },
-            1 => {
-                if x > 0 {
-                    self[(x - 1, y, z)].material == Material::Air
-                } else {
-                    true
-                }
-            },
-            2 => {
-                if z + 1 < CHUNK_SIZE {
-                    self[(x, y, z + 1)].material == Material::Air
-                } else {
-                    true
-                }
-            },
-            3 => {
-                if z > 0 {
-                    self[(x, y, z - 1)].material == Material::Air
-                } else {
-                    true
-                }
-            },
-            4 => {
-                if y + 1 < CHUNK_HEIGHT {
-                    self[(x, y + 1, z)].material == Material::Air
-                } else {
-                    true
-                }
-            },
-            5 => {
-                if y > 0 {
-                    self[(x, y - 1, z)].material == Material::Air
-                } else {
-                    true
-                }
-            },
-            _ => unreachable!()
+    /// Replaces a block. When the material changes the old block entity is dropped and the new material's one is created.
+    pub fn set_block(&mut self, position: (usize, usize, usize), block: Block) {
+        let old = std::mem::replace(&mut self[position], block);
+        if old.material == block.material { return; }
+
+        let key = block_entity_key(position);
+        self.block_entities.remove(&key);
+        if let Some(kind) = block.material.block_entity() {
+            self.block_entities.insert(key, kind.create());
         }
     }
+
+    pub fn block_entity<T: BlockEntity>(&self, position: (usize, usize, usize)) -> Option<&T> {
+        self.block_entities.get(&block_entity_key(position))?.as_any().downcast_ref()
+    }
+
+    pub fn block_entity_mut<T: BlockEntity>(&mut self, position: (usize, usize, usize)) -> Option<&mut T> {
+        self.block_entities.get_mut(&block_entity_key(position))?.as_any_mut().downcast_mut()
+    }
+
+    pub fn tick_block_entities(&mut self, dt: f32) {
+        for block_entity in self.block_entities.values_mut() {
+            block_entity.tick(dt);
+        }
+    }
+
+    /// World position of the chunk's first block.
+    pub fn world_origin(&self) -> Point3<i32> {
+        Point3::new(self.position.x * CHUNK_SIZE as i32, 0, self.position.y * CHUNK_SIZE as i32)
+    }
 }
 
-pub struct SubChunk {
-    pub material_3d_texture_bind_group: wgpu::BindGroup,
-    pub mesh: ChunkMesh,
-    pub translation_buffer: wgpu::Buffer,
+fn block_entity_key(position: (usize, usize, usize)) -> Point3<u8> {
+    Point3::new(position.0 as u8, position.1 as u8, position.2 as u8)
 }
 
-pub struct ChunkMesh {
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    indices: u32,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::GeneratorPreset;
+
+    fn empty_chunks() -> ChunkManager {
+        let mut chunks = ChunkManager::new();
+        for x in -1..=0 {
+            chunks.insert(GeneratorPreset::Empty.generate(Point2::new(x, 0), 0));
+        }
+        chunks.dirty_sub_chunks.clear();
+        chunks.dirty_block_entities.clear();
+        chunks
+    }
+
+    #[test]
+    fn inserting_a_chunk_dirties_all_of_it() {
+        let mut chunks = ChunkManager::new();
+        chunks.insert(Chunk::new(Point2::new(3, -2)));
+        assert_eq!(chunks.dirty_sub_chunks.len(), SUB_CHUNKS);
+        assert!(chunks.dirty_block_entities.contains(&Point2::new(3, -2)));
+    }
+
+    #[test]
+    fn setting_a_block_dirties_the_sub_chunks_it_touches() {
+        let mut chunks = empty_chunks();
+        assert!(chunks.set_block(Point3::new(0, 32, 5), Block::new(Material::Dirt)));
+        let expected = HashSet::from([(Point2::new(0, 0), 1), (Point2::new(0, 0), 0), (Point2::new(-1, 0), 1)]);
+        assert_eq!(chunks.dirty_sub_chunks, expected);
+        assert!(chunks.dirty_block_entities.is_empty());
+
+        assert!(!chunks.set_block(Point3::new(40, 32, 5), Block::new(Material::Dirt)));
+        assert!(!chunks.set_block(Point3::new(0, -1, 5), Block::new(Material::Dirt)));
+    }
+
+    #[test]
+    fn changes_are_only_recorded_while_tracked() {
+        let mut chunks = empty_chunks();
+        chunks.fill(Point3::new(0, 0, 0), Point3::new(3, 3, 3), Block::new(Material::Dirt));
+        assert!(chunks.changed_blocks.is_empty());
+
+        chunks.track_changes = true;
+        assert!(chunks.set_block(Point3::new(0, 32, 5), Block::new(Material::Dirt)));
+        assert_eq!(chunks.changed_blocks, [Point3::new(0, 32, 5)]);
+    }
+
+    #[test]
+    fn fill_counts_only_loaded_blocks() {
+        let mut chunks = empty_chunks();
+        assert_eq!(chunks.fill(Point3::new(33, 0, 0), Point3::new(-2, 1, 1), Block::new(Material::Sign)), 34 * 2 * 2);
+        assert_eq!(chunks.dirty_block_entities.len(), 2);
+    }
+}
@@ -0,0 +1,192 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use voxel_game::server::check_name;
+
+use crate::{generator::GeneratorPreset, settings::Settings};
+
+/// World directory when `--world` isn't given.
+pub const DEFAULT_WORLD_DIRECTORY: &str = "world";
+
+/// Seed of the benchmark world when `--seed` isn't given, so runs compare.
+pub const DEFAULT_BENCHMARK_SEED: u64 = 0x5eed;
+
+#[derive(Parser, Debug, Clone, Default)]
+#[command(version, about = "A voxel game")]
+pub struct Cli {
+    /// Directory the world is loaded from and saved to [default: world]
+    #[arg(long, value_name = "DIR")]
+    pub world: Option<PathBuf>,
+
+    /// Seed of a new world's generator, random when not given, saved worlds keep theirs
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Generator of a new world for chunks that aren't in the save: random, flat or empty [default: random], saved worlds keep theirs
+    #[arg(long, value_name = "PRESET")]
+    pub generator: Option<GeneratorPreset>,
+
+    /// Chunks loaded and drawn around the player, limited by the server when joining one, overrides the settings file
+    #[arg(long, value_name = "CHUNKS", value_parser = clap::value_parser!(u32).range(*Settings::RENDER_DISTANCE_RANGE.start() as i64..=*Settings::RENDER_DISTANCE_RANGE.end() as i64))]
+    pub render_distance: Option<u32>,
+
+    /// Window size as WIDTHxHEIGHT, overrides the settings file
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_window_size)]
+    pub window_size: Option<(u32, u32)>,
+
+    /// Hosts the world without a window on the default server port, type `stop` or close stdin to save and quit
+    #[arg(long, visible_alias = "headless", conflicts_with_all = ["window_size", "render_distance", "benchmark"])]
+    pub server: bool,
+
+    /// Flies through a fresh world along a fixed path and writes a report of the frame timings, nothing is saved
+    #[arg(long, conflicts_with = "world")]
+    pub benchmark: bool,
+
+    /// Joins the server at ADDRESS instead of playing a local world, e.g. 127.0.0.1:27500
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["world", "seed", "generator", "server", "benchmark"])]
+    pub connect: Option<String>,
+
+    /// Name the server shows for this player
+    #[arg(long, value_name = "NAME", default_value = "player", requires = "connect", value_parser = parse_name)]
+    pub name: String,
+
+    /// Frames the benchmark records
+    #[arg(long, value_name = "FRAMES", default_value_t = 1000, requires = "benchmark", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub benchmark_frames: usize,
+
+    /// Where the benchmark report goes, written as <FILE>.json and <FILE>.csv
+    #[arg(long, value_name = "FILE", default_value = "benchmark", requires = "benchmark")]
+    pub benchmark_output: PathBuf,
+}
+
+/// What the game was launched to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Windowed,
+    Server,
+    Benchmark,
+    /// playing on a server, the world comes from it
+    Client,
+}
+
+impl Cli {
+    pub fn mode(&self) -> Mode {
+        if self.server {
+            Mode::Server
+        } else if self.benchmark {
+            Mode::Benchmark
+        } else if self.connect.is_some() {
+            Mode::Client
+        } else {
+            Mode::Windowed
+        }
+    }
+
+    pub fn world_directory(&self) -> PathBuf {
+        self.world.clone().unwrap_or_else(|| DEFAULT_WORLD_DIRECTORY.into())
+    }
+
+    /// The given seed, a fixed one for benchmarks and a random one otherwise.
+    pub fn seed(&self) -> u64 {
+        match (self.seed, self.mode()) {
+            (Some(seed), _) => seed,
+            (None, Mode::Benchmark) => DEFAULT_BENCHMARK_SEED,
+            (None, _) => rand::random(),
+        }
+    }
+
+    /// Overrides the loaded settings with the ones given on the command line.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(render_distance) = self.render_distance {
+            settings.render_distance = render_distance;
+        }
+        if let Some((width, height)) = self.window_size {
+            settings.window_width = width;
+            settings.window_height = height;
+            settings.fullscreen = false;
+        }
+    }
+}
+
+fn parse_window_size(size: &str) -> Result<(u32, u32), String> {
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(|| format!("{size:?} isn't WIDTHxHEIGHT"))?;
+    let width: u32 = width.trim().parse().map_err(|_| format!("{width:?} isn't a width"))?;
+    let height: u32 = height.trim().parse().map_err(|_| format!("{height:?} isn't a height"))?;
+    if width < 320 || height < 240 { return Err(format!("{width}x{height} is smaller than 320x240")); }
+    Ok((width, height))
+}
+
+fn parse_name(name: &str) -> Result<String, String> {
+    check_name(name).map(str::to_string).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(arguments: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("voxel_game").chain(arguments.iter().copied()))
+    }
+
+    #[test]
+    fn defaults_to_a_windowed_game() {
+        let cli = parse(&[]).unwrap();
+        assert_eq!(cli.mode(), Mode::Windowed);
+        assert_eq!(cli.world_directory(), PathBuf::from(DEFAULT_WORLD_DIRECTORY));
+        assert_eq!(cli.generator, None);
+    }
+
+    #[test]
+    fn parses_every_option() {
+        let cli = parse(&["--world", "saves/test", "--seed", "42", "--generator", "flat", "--render-distance", "8", "--window-size", "1280x720"]).unwrap();
+        assert_eq!(cli.world_directory(), PathBuf::from("saves/test"));
+        assert_eq!(cli.seed(), 42);
+        assert_eq!(cli.generator, Some(GeneratorPreset::Flat));
+
+        let mut settings = Settings { fullscreen: true, ..Settings::default() };
+        cli.apply(&mut settings);
+        assert_eq!((settings.render_distance, settings.window_width, settings.window_height, settings.fullscreen), (8, 1280, 720, false));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(parse(&["--generator", "mountains"]).is_err());
+        assert!(parse(&["--render-distance", "1000"]).is_err());
+        assert!(parse(&["--window-size", "1280"]).is_err());
+        assert!(parse(&["--window-size", "10x10"]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_combinations() {
+        assert!(parse(&["--server", "--benchmark"]).is_err());
+        assert!(parse(&["--headless", "--window-size", "800x600"]).is_err());
+        assert!(parse(&["--benchmark", "--world", "world"]).is_err());
+        assert_eq!(parse(&["--headless", "--seed", "1"]).unwrap().mode(), Mode::Server);
+    }
+
+    #[test]
+    fn connecting_replaces_the_local_world() {
+        let cli = parse(&["--connect", "127.0.0.1:27500", "--name", "alice", "--render-distance", "6"]).unwrap();
+        assert_eq!((cli.mode(), cli.connect.as_deref(), cli.name.as_str()), (Mode::Client, Some("127.0.0.1:27500"), "alice"));
+        assert_eq!(parse(&["--connect", "localhost:27500"]).unwrap().name, "player");
+        assert!(parse(&["--connect", "localhost:27500", "--world", "world"]).is_err());
+        assert!(parse(&["--connect", "localhost:27500", "--seed", "1"]).is_err());
+        assert!(parse(&["--connect", "localhost:27500", "--server"]).is_err());
+        assert!(parse(&["--name", "alice"]).is_err());
+        assert!(parse(&["--connect", "localhost:27500", "--name", "a name far too long"]).is_err());
+        assert!(parse(&["--connect", "localhost:27500", "--name", "al\u{7}ice"]).is_err());
+    }
+
+    #[test]
+    fn benchmarks_use_a_fixed_seed() {
+        assert_eq!(parse(&["--benchmark"]).unwrap().seed(), DEFAULT_BENCHMARK_SEED);
+    }
+
+    #[test]
+    fn benchmark_options_need_a_benchmark() {
+        assert!(parse(&["--benchmark-frames", "10"]).is_err());
+        assert!(parse(&["--benchmark", "--benchmark-frames", "0"]).is_err());
+        let cli = parse(&["--benchmark", "--benchmark-frames", "10", "--benchmark-output", "out/run"]).unwrap();
+        assert_eq!((cli.benchmark_frames, cli.benchmark_output), (10, PathBuf::from("out/run")));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, net::{TcpStream, ToSocketAddrs}, time::{Duration, Instant}};
+
+use anyhow::{bail, Context};
+use cgmath::Point3;
+
+use crate::{chunk::ChunkManager, protocol::{Connection, Packet, PROTOCOL_VERSION}, wire::sub_chunk_block_position};
+
+/// The client's end of a connection to a server, keeping a local `ChunkManager` in sync with the server's world.
+pub struct ServerConnection {
+    pub connection: Connection,
+    pub player_id: u32,
+    /// feet position the server spawned the player at
+    pub spawn: Point3<f32>,
+    /// most chunks around the player the server sends, whatever view distance is asked for
+    pub max_view_distance: u32,
+    /// feet positions of the other players, by id
+    pub players: HashMap<u32, Point3<f32>>,
+    /// where the server moved the player back to after refusing its movement, taken by the game
+    pub teleport: Option<Point3<f32>>,
+    /// why the connection ended, `None` while connected
+    pub disconnect_reason: Option<String>,
+    /// packets that arrived together with the login, handled by the first update
+    pending: Vec<Packet>,
+    /// movement is only sent when the position changed
+    last_position: Option<Point3<f32>>,
+    /// the view distance last asked for, only sent again when it changes
+    view_distance: u32,
+}
+
+impl ServerConnection {
+    /// Connects, shakes hands and logs in as `name` asking for the chunks up to `view_distance` around the player, waiting up to `timeout` for the server to let the player in.
+    pub fn connect(address: impl ToSocketAddrs, name: &str, view_distance: u32, timeout: Duration) -> anyhow::Result<Self> {
+        let address = address.to_socket_addrs().context("resolving the server address")?.next().context("the server address didn't resolve")?;
+        let stream = TcpStream::connect_timeout(&address, timeout).with_context(|| format!("connecting to {address}"))?;
+        let mut connection = Connection::new(stream)?;
+        connection.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+        connection.send(&Packet::Login { name: name.to_string(), view_distance: wire_view_distance(view_distance) });
+
+        let start = Instant::now();
+        loop {
+            connection.flush()?;
+            let mut packets = connection.receive()?.into_iter();
+            while let Some(packet) = packets.next() {
+                match packet {
+                    Packet::Handshake { version } if version != PROTOCOL_VERSION => bail!("the server speaks protocol version {version}, this client {PROTOCOL_VERSION}"),
+                    Packet::Handshake { .. } => {}
+                    Packet::LoginSuccess { player_id, position, view_distance: max_view_distance } => {
+                        return Ok(Self { connection, player_id, spawn: position, max_view_distance: max_view_distance.into(), players: HashMap::new(), teleport: None, disconnect_reason: None, pending: packets.collect(), last_position: None, view_distance });
+                    }
+                    Packet::Disconnect { reason } => bail!("the server refused the login: {reason}"),
+                    packet => bail!("unexpected packet {} before the login", packet.id()),
+                }
+            }
+            if connection.closed { bail!("the server closed the connection during the login"); }
+            if start.elapsed() > timeout { bail!("the server didn't answer the login within {timeout:?}"); }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.disconnect_reason.is_none()
+    }
+
+    /// Sends the blocks set locally since the last update and applies what the server sent.
+    /// Errors once when the connection ends, later updates only stop `chunks` from recording changes.
+    pub fn update(&mut self, chunks: &mut ChunkManager) -> anyhow::Result<()> {
+        chunks.track_changes = self.is_connected();
+        if !self.is_connected() {
+            chunks.changed_blocks.clear();
+            return Ok(());
+        }
+        let result = self.exchange(chunks);
+        if let Err(error) = &result {
+            self.disconnect_reason = Some(format!("{error:#}"));
+            self.connection.close("connection error");
+            chunks.track_changes = false;
+            chunks.changed_blocks.clear();
+        }
+        result
+    }
+
+    fn exchange(&mut self, chunks: &mut ChunkManager) -> anyhow::Result<()> {
+        let changed = std::mem::take(&mut chunks.changed_blocks);
+        for (_, packet) in Packet::block_changes(chunks, changed) {
+            self.connection.send(&packet);
+        }
+        self.connection.flush()?;
+
+        let mut packets = std::mem::take(&mut self.pending);
+        packets.extend(self.connection.receive()?);
+        for packet in packets {
+            match packet {
+                Packet::ChunkData { chunk } => chunks.insert(*chunk),
+                Packet::BlockChange { position, block } => { chunks.set_block(position, block); }
+                Packet::MultiBlockChange { chunk, sub_chunk, changes } => {
+                    for (index, block) in changes {
+                        chunks.set_block(sub_chunk_block_position(chunk, sub_chunk, index), block);
+                    }
+                }
+                Packet::PlayerMovement { player_id, position } if player_id == self.player_id => {
+                    self.teleport = Some(position);
+                    self.last_position = Some(position);
+                }
+                Packet::PlayerMovement { player_id, position } => { self.players.insert(player_id, position); }
+                Packet::UnloadChunk { position } => { chunks.remove(position); }
+                Packet::Disconnect { reason } => bail!("disconnected by the server: {reason}"),
+                packet => bail!("unexpected packet {} from the server", packet.id()),
+            }
+        }
+        // the server's changes are already applied on its side, they aren't sent back
+        chunks.changed_blocks.clear();
+
+        if self.connection.closed { bail!("the server closed the connection"); }
+        Ok(())
+    }
+
+    /// Tells the server where the player's feet are, nothing is sent if they didn't move.
+    pub fn send_movement(&mut self, position: Point3<f32>) {
+        if !self.is_connected() || self.last_position == Some(position) { return; }
+        self.last_position = Some(position);
+        self.connection.send(&Packet::PlayerMovement { player_id: self.player_id, position });
+    }
+
+    /// Asks the server for the chunks up to `chunks` around the player, nothing is sent if it didn't change.
+    pub fn send_view_distance(&mut self, chunks: u32) {
+        if !self.is_connected() || self.view_distance == chunks { return; }
+        self.view_distance = chunks;
+        self.connection.send(&Packet::ViewDistance { chunks: wire_view_distance(chunks) });
+    }
+
+    /// Leaves the server.
+    pub fn disconnect(&mut self, reason: &str) {
+        if !self.is_connected() { return; }
+        self.connection.close(reason);
+        self.disconnect_reason = Some(reason.to_string());
+    }
+}
+
+/// View distances are a byte on the wire, more than that is more than any server sends anyway.
+fn wire_view_distance(chunks: u32) -> u8 {
+    chunks.min(u8::MAX.into()) as u8
+}
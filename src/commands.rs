@@ -0,0 +1,178 @@
+use anyhow::{bail, Context};
+use cgmath::Point3;
+
+use crate::{block::{Block, Material}, chunk::CHUNK_HEIGHT, console::{expect_arguments, Command, CommandRegistry, UsageError}, save::WorldMetadata, settings::Settings, time::WorldTime};
+
+/// Largest box `/fill` changes at once.
+const MAX_FILL_VOLUME: i64 = 32 * 32 * 256;
+
+/// Farthest x and z commands accept, well inside what `i32` block and chunk math can handle.
+const MAX_HORIZONTAL_COORDINATE: f32 = 30_000_000.0;
+
+pub fn register_builtin_commands(registry: &mut CommandRegistry) {
+    registry.register(Command::new("help", "", "lists the commands", |state, arguments| {
+        expect_arguments(arguments, 0, 0)?;
+        let help: Vec<String> = state.console.registry.commands().map(|command| format!("/{} {} - {}", command.name, command.usage, command.description)).collect();
+        Ok(help.join("\n"))
+    }));
+
+    registry.register(Command::new("tp", "<x> <y> <z>", "moves the player's feet to a position, ~ is relative", |state, arguments| {
+        expect_arguments(arguments, 3, 3)?;
+        let position = parse_position(arguments, state.world.player.position)?;
+        state.world.player.teleport(position);
+        Ok(format!("teleported to {:.2} {:.2} {:.2}", position.x, position.y, position.z))
+    }));
+
+    registry.register(Command::new("setblock", "<x> <y> <z> <block>", "replaces a block", |state, arguments| {
+        expect_arguments(arguments, 4, 4)?;
+        let position = parse_block_position(&arguments[0..3], state.world.player.position)?;
+        let material = parse_material(arguments[3])?;
+        if !state.world.loaded_chunks.set_block(position, Block::new(material)) {
+            bail!("{} {} {} isn't in a loaded chunk", position.x, position.y, position.z);
+        }
+        Ok(format!("set {} {} {} to {}", position.x, position.y, position.z, material.name()))
+    }).with_completer(|index| if index == 3 { material_names() } else { vec!["~".to_string()] }));
+
+    registry.register(Command::new("fill", "<x1> <y1> <z1> <x2> <y2> <z2> <block>", "sets every block in a box", |state, arguments| {
+        expect_arguments(arguments, 7, 7)?;
+        let from = parse_block_position(&arguments[0..3], state.world.player.position)?;
+        let to = parse_block_position(&arguments[3..6], state.world.player.position)?;
+        let material = parse_material(arguments[6])?;
+
+        let volume = fill_volume(from, to);
+        if volume > MAX_FILL_VOLUME { bail!("{volume} blocks is more than the limit of {MAX_FILL_VOLUME}"); }
+
+        let changed = state.world.loaded_chunks.fill(from, to, Block::new(material));
+        Ok(format!("filled {changed} blocks with {}", material.name()))
+    }).with_completer(|index| if index == 6 { material_names() } else { vec!["~".to_string()] }));
+
+    registry.register(Command::new("time", "[set <sunrise|noon|sunset|midnight|0..1>]", "shows or sets the time of day", |state, arguments| {
+        expect_arguments(arguments, 0, 2)?;
+        match arguments {
+            [] => Ok(format!("time of day is {:.3}", state.world.time.time_of_day)),
+            ["set", value] => {
+                let time_of_day = match *value {
+                    "sunrise" => WorldTime::SUNRISE,
+                    "day" | "noon" => WorldTime::NOON,
+                    "sunset" => WorldTime::SUNSET,
+                    "night" | "midnight" => WorldTime::MIDNIGHT,
+                    value => value.parse().with_context(|| format!("{value:?} isn't a time of day"))?,
+                };
+                state.world.time.set(time_of_day);
+                Ok(format!("set the time of day to {:.3}", state.world.time.time_of_day))
+            }
+            _ => Err(UsageError("expected `set` and a time".to_string()).into()),
+        }
+    }).with_completer(|index| match index {
+        0 => vec!["set".to_string()],
+        1 => ["sunrise", "noon", "sunset", "midnight"].map(String::from).to_vec(),
+        _ => vec![],
+    }));
+
+    registry.register(Command::new("seed", "", "shows the world seed", |state, arguments| {
+        expect_arguments(arguments, 0, 0)?;
+        Ok(format!("seed: {} ({})", state.world.seed, state.world.generator))
+    }));
+
+    registry.register(Command::new("regen", "[seed]", "regenerates the loaded chunks, dropping all changes", |state, arguments| {
+        expect_arguments(arguments, 0, 1)?;
+        let seed = match arguments.first() {
+            Some(seed) => seed.parse().with_context(|| format!("{seed:?} isn't a seed"))?,
+            None => state.world.seed,
+        };
+        state.world.regenerate(seed);
+        // chunks loaded later are generated with the new seed too
+        if state.server.is_none() && !state.benchmark {
+            state.save.save_metadata(WorldMetadata { seed, generator: state.world.generator })?;
+        }
+        Ok(format!("regenerated {} chunks with seed {seed}", state.world.loaded_chunks.chunks.len()))
+    }));
+
+    registry.register(Command::new("speed", "[blocks per second]", "shows or sets the spectator flying speed", |state, arguments| {
+        expect_arguments(arguments, 0, 1)?;
+        let settings = &mut state.settings;
+        if let Some(speed) = arguments.first() {
+            let speed: f32 = speed.parse().with_context(|| format!("{speed:?} isn't a number"))?;
+            if !Settings::FLY_SPEED_RANGE.contains(&speed) { bail!("the speed has to be between {} and {}", Settings::FLY_SPEED_RANGE.start(), Settings::FLY_SPEED_RANGE.end()); }
+            settings.fly_speed = speed;
+            state.settings_menu.unsaved = true;
+        }
+        Ok(format!("speed: {}", settings.fly_speed))
+    }));
+
+    registry.register(Command::new("save", "", "writes the loaded chunks to the world save", |state, arguments| {
+        expect_arguments(arguments, 0, 0)?;
+        let chunks = state.save()?;
+        Ok(format!("saved {chunks} chunks to {}", state.save.directory.display()))
+    }));
+}
+
+/// A number, or `~` followed by an optional offset from `base`.
+fn parse_coordinate(argument: &str, base: f32) -> anyhow::Result<f32> {
+    let (relative, number) = match argument.strip_prefix('~') {
+        Some(offset) => (true, offset),
+        None => (false, argument),
+    };
+    let value = match number {
+        "" if relative => 0.0,
+        number => number.parse::<f32>().ok().filter(|value| value.is_finite()).with_context(|| format!("{argument:?} isn't a coordinate"))?,
+    };
+    Ok(if relative { base + value } else { value })
+}
+
+/// Three coordinates inside the world: y between the bottom and the top, x and z up to `MAX_HORIZONTAL_COORDINATE`.
+fn parse_position(arguments: &[&str], base: Point3<f32>) -> anyhow::Result<Point3<f32>> {
+    let position = Point3::new(
+        parse_coordinate(arguments[0], base.x)?,
+        parse_coordinate(arguments[1], base.y)?,
+        parse_coordinate(arguments[2], base.z)?,
+    );
+    if position.x.abs() > MAX_HORIZONTAL_COORDINATE || position.z.abs() > MAX_HORIZONTAL_COORDINATE {
+        bail!("{} {} is past the edge of the world at {MAX_HORIZONTAL_COORDINATE}", position.x, position.z);
+    }
+    if !(0.0..CHUNK_HEIGHT as f32).contains(&position.y) { bail!("y {} is outside the world, it has to be between 0 and {CHUNK_HEIGHT}", position.y); }
+    Ok(position)
+}
+
+fn parse_block_position(arguments: &[&str], base: Point3<f32>) -> anyhow::Result<Point3<i32>> {
+    Ok(parse_position(arguments, base)?.map(|coordinate| coordinate.floor() as i32))
+}
+
+/// Blocks in the box between two corners, computed wide and saturating so opposite corners far apart don't overflow.
+fn fill_volume(from: Point3<i32>, to: Point3<i32>) -> i64 {
+    let side = |a: i32, b: i32| (a as i64 - b as i64).abs() + 1;
+    side(from.x, to.x).saturating_mul(side(from.y, to.y)).saturating_mul(side(from.z, to.z))
+}
+
+fn parse_material(name: &str) -> anyhow::Result<Material> {
+    Material::from_name(name).with_context(|| format!("unknown block {name:?}"))
+}
+
+fn material_names() -> Vec<String> {
+    Material::ALL.iter().map(|material| material.name().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_outside_the_world_are_rejected() {
+        let base = Point3::new(0.5, 64.0, -10.0);
+        assert_eq!(parse_block_position(&["~", "~1", "-3.5"], base).unwrap(), Point3::new(0, 65, -4));
+        assert!(parse_block_position(&["3e9", "0", "0"], base).is_err());
+        assert!(parse_block_position(&["0", "0", "-3e9"], base).is_err());
+        assert!(parse_block_position(&["0", "-1", "0"], base).is_err());
+        assert!(parse_block_position(&["0", "256", "0"], base).is_err());
+        assert!(parse_position(&["~", "~300", "~"], base).is_err());
+    }
+
+    #[test]
+    fn fill_volume_of_far_apart_corners_doesnt_overflow() {
+        assert_eq!(fill_volume(Point3::new(3, 0, 0), Point3::new(-3, 0, 0)), 7);
+        let volume = fill_volume(Point3::new(i32::MAX, 0, 0), Point3::new(i32::MIN, 0, 0));
+        assert_eq!(volume, 1 << 32);
+        assert!(volume > MAX_FILL_VOLUME);
+        assert!(fill_volume(Point3::new(i32::MIN, 0, i32::MIN), Point3::new(i32::MAX, 255, i32::MAX)) > MAX_FILL_VOLUME);
+    }
+}
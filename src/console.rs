@@ -0,0 +1,325 @@
+use std::{collections::BTreeMap, fmt, rc::Rc};
+
+use egui::{text::{CCursor, CCursorRange}, Color32, Key, Modifiers, RichText, TextEdit};
+
+use crate::state::State;
+
+/// lines kept in the output
+const MAX_LINES: usize = 200;
+const MAX_HISTORY: usize = 100;
+
+/// Runs a command with its arguments, returning the text to print.
+pub type CommandHandler = Rc<dyn Fn(&mut State, &[&str]) -> anyhow::Result<String>>;
+/// Possible values of the argument at an index, the registry filters them by what was typed.
+pub type ArgumentCompleter = fn(usize) -> Vec<String>;
+
+pub struct Command {
+    pub name: &'static str,
+    /// arguments shown by `/help` and after usage errors, like `<x> <y> <z>`
+    pub usage: &'static str,
+    pub description: &'static str,
+    pub handler: CommandHandler,
+    pub completer: Option<ArgumentCompleter>,
+}
+
+impl Command {
+    pub fn new(name: &'static str, usage: &'static str, description: &'static str, handler: impl Fn(&mut State, &[&str]) -> anyhow::Result<String> + 'static) -> Self {
+        Self { name, usage, description, handler: Rc::new(handler), completer: None }
+    }
+
+    pub fn with_completer(mut self, completer: ArgumentCompleter) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+}
+
+/// Returned by handlers when the arguments don't fit the command, the console adds the usage.
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Fails with a `UsageError` unless there are between `min` and `max` arguments.
+pub fn expect_arguments(arguments: &[&str], min: usize, max: usize) -> anyhow::Result<()> {
+    if arguments.len() < min || arguments.len() > max {
+        return Err(UsageError(format!("expected {} arguments, got {}", if min == max { min.to_string() } else { format!("{min} to {max}") }, arguments.len())).into());
+    }
+    Ok(())
+}
+
+/// Every command the console knows, any subsystem can add its own.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, Command>,
+}
+
+impl CommandRegistry {
+    /// Adds a command, replacing an earlier one with the same name.
+    pub fn register(&mut self, command: Command) {
+        if self.commands.insert(command.name, command).is_some() {
+            log::warn!("console command registered twice");
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+
+    /// Commands in alphabetical order.
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.commands.values()
+    }
+
+    /// Possible values of the word being typed at the end of `input`.
+    pub fn complete(&self, input: &str) -> Vec<String> {
+        let Some((name, arguments)) = parse(input) else {
+            return self.commands.keys().map(|name| name.to_string()).collect();
+        };
+        let ends_word = input.ends_with(char::is_whitespace);
+
+        if arguments.is_empty() && !ends_word {
+            return self.commands.keys().filter(|candidate| candidate.starts_with(name)).map(|name| name.to_string()).collect();
+        }
+
+        let Some(completer) = self.get(name).and_then(|command| command.completer) else { return vec![]; };
+        let (index, prefix) = if ends_word { (arguments.len(), "") } else { (arguments.len() - 1, arguments[arguments.len() - 1]) };
+        completer(index).into_iter().filter(|candidate| candidate.starts_with(prefix)).collect()
+    }
+}
+
+/// Splits a line into the command name and its arguments, the leading slash is optional.
+pub fn parse(line: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words = line.trim_start().trim_start_matches('/').split_whitespace();
+    let name = words.next()?;
+    Some((name, words.collect()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Input,
+    Output,
+    Error,
+}
+
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub lines: Vec<(LineKind, String)>,
+    /// submitted lines, oldest first
+    pub history: Vec<String>,
+    /// entry shown while browsing the history with the arrow keys
+    history_index: Option<usize>,
+    /// submitted lines waiting to be run by `State::run_pending_commands`
+    pub pending: Vec<String>,
+    pub registry: CommandRegistry,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self { open: false, input: String::new(), lines: vec![], history: vec![], history_index: None, pending: vec![], registry: CommandRegistry::default() }
+    }
+
+    pub fn print(&mut self, kind: LineKind, text: impl Into<String>) {
+        for line in text.into().lines() {
+            self.lines.push((kind, line.to_string()));
+        }
+        let overflow = self.lines.len().saturating_sub(MAX_LINES);
+        self.lines.drain(..overflow);
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        if line.trim().is_empty() { return; }
+
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            let overflow = self.history.len().saturating_sub(MAX_HISTORY);
+            self.history.drain(..overflow);
+        }
+        self.history_index = None;
+        self.pending.push(line);
+    }
+
+    /// Steps through the history, `back` towards older entries.
+    fn browse_history(&mut self, back: bool) {
+        if self.history.is_empty() { return; }
+        self.history_index = match (self.history_index, back) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => (index + 1 < self.history.len()).then_some(index + 1),
+        };
+        self.input = self.history_index.map(|index| self.history[index].clone()).unwrap_or_default();
+    }
+
+    /// Completes the last word as far as all candidates agree and lists them if there are several.
+    fn complete(&mut self) {
+        let candidates = self.registry.complete(&self.input);
+        let Some(first) = candidates.first() else { return; };
+
+        let common = candidates.iter().fold(first.as_str(), |common, candidate| {
+            let length = common.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a.len_utf8()).sum();
+            &common[..length]
+        }).to_string();
+
+        let word_start = self.input.rfind(|c: char| c.is_whitespace() || c == '/').map_or(0, |index| index + 1);
+        self.input.truncate(word_start);
+        self.input.push_str(&common);
+        if candidates.len() == 1 {
+            self.input.push(' ');
+        } else {
+            self.print(LineKind::Output, candidates.join("  "));
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("console")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_BOTTOM, [4.0, -4.0])
+        .default_width(600.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().max_height(240.0).stick_to_bottom(true).show(ui, |ui| {
+                ui.set_min_width(600.0);
+                for (kind, line) in self.lines.iter() {
+                    let color = match kind {
+                        LineKind::Input => Color32::GRAY,
+                        LineKind::Output => Color32::WHITE,
+                        LineKind::Error => Color32::LIGHT_RED,
+                    };
+                    ui.label(RichText::new(line).monospace().color(color));
+                }
+            });
+
+            // taken before the text field sees them, it would move the cursor or focus instead
+            let (tab, up, down) = ui.input_mut(|input| (input.consume_key(Modifiers::NONE, Key::Tab), input.consume_key(Modifiers::NONE, Key::ArrowUp), input.consume_key(Modifiers::NONE, Key::ArrowDown)));
+            if tab { self.complete(); }
+            if up || down { self.browse_history(up); }
+
+            let id = ui.make_persistent_id("console input");
+            if tab || up || down {
+                if let Some(mut state) = TextEdit::load_state(ui.ctx(), id) {
+                    state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(self.input.chars().count()))));
+                    TextEdit::store_state(ui.ctx(), id, state);
+                }
+            }
+
+            let response = ui.add(TextEdit::singleline(&mut self.input).id(id).font(egui::TextStyle::Monospace).desired_width(f32::INFINITY).lock_focus(true));
+            if response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                self.submit();
+            }
+            response.request_focus();
+        });
+    }
+}
+
+impl State {
+    /// Runs the lines submitted in the console since the last call.
+    pub fn run_pending_commands(&mut self) {
+        for line in std::mem::take(&mut self.console.pending) {
+            self.run_command(&line);
+        }
+    }
+
+    /// Runs a console line and prints its output or error.
+    pub fn run_command(&mut self, line: &str) {
+        self.console.print(LineKind::Input, line);
+        let Some((name, arguments)) = parse(line) else { return; };
+        let Some(command) = self.console.registry.get(name) else {
+            self.console.print(LineKind::Error, format!("unknown command /{name}, see /help"));
+            return;
+        };
+
+        let (handler, usage) = (command.handler.clone(), format!("usage: /{} {}", command.name, command.usage));
+        match handler(self, &arguments) {
+            Ok(output) => if !output.is_empty() { self.console.print(LineKind::Output, output) },
+            Err(error) => {
+                self.console.print(LineKind::Error, format!("{error:#}"));
+                if error.is::<UsageError>() { self.console.print(LineKind::Error, usage); }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::default();
+        registry.register(Command::new("time", "", "", |_, _| Ok(String::new())).with_completer(|index| match index {
+            0 => vec!["set".to_string()],
+            1 => vec!["sunrise".to_string(), "sunset".to_string(), "noon".to_string()],
+            _ => vec![],
+        }));
+        registry.register(Command::new("tp", "", "", |_, _| Ok(String::new())));
+        registry.register(Command::new("save", "", "", |_, _| Ok(String::new())));
+        registry
+    }
+
+    #[test]
+    fn parses_name_and_arguments() {
+        assert_eq!(parse("/tp 1 ~2  -3"), Some(("tp", vec!["1", "~2", "-3"])));
+        assert_eq!(parse("  save"), Some(("save", vec![])));
+        assert_eq!(parse("/"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn completes_command_names() {
+        let registry = registry();
+        assert_eq!(registry.complete("/t"), vec!["time", "tp"]);
+        assert_eq!(registry.complete("/sa"), vec!["save"]);
+        assert_eq!(registry.complete("/"), vec!["save", "time", "tp"]);
+        assert!(registry.complete("/x").is_empty());
+    }
+
+    #[test]
+    fn completes_arguments() {
+        let registry = registry();
+        assert_eq!(registry.complete("/time "), vec!["set"]);
+        assert_eq!(registry.complete("/time set su"), vec!["sunrise", "sunset"]);
+        assert!(registry.complete("/tp ").is_empty());
+    }
+
+    #[test]
+    fn completion_extends_the_common_prefix() {
+        let mut console = Console::new();
+        console.registry = registry();
+        console.input = "/time set su".to_string();
+        console.complete();
+        assert_eq!(console.input, "/time set sun");
+        assert_eq!(console.lines.last().unwrap().1, "sunrise  sunset");
+
+        console.input = "/ti".to_string();
+        console.complete();
+        assert_eq!(console.input, "/time ");
+    }
+
+    #[test]
+    fn browses_history() {
+        let mut console = Console::new();
+        for line in ["/tp 0 0 0", "/save", "/save"] {
+            console.input = line.to_string();
+            console.submit();
+        }
+        assert_eq!(console.history, vec!["/tp 0 0 0", "/save"]);
+        assert_eq!(console.pending.len(), 3);
+
+        console.browse_history(true);
+        assert_eq!(console.input, "/save");
+        console.browse_history(true);
+        console.browse_history(true);
+        assert_eq!(console.input, "/tp 0 0 0");
+        console.browse_history(false);
+        assert_eq!(console.input, "/save");
+        console.browse_history(false);
+        assert_eq!(console.input, "");
+    }
+}
@@ -0,0 +1,80 @@
+use cgmath::Point2;
+
+use crate::{block::{Block, Material}, chunk::{Chunk, CHUNK_HEIGHT, CHUNK_SIZE}};
+
+/// Height of the grass layer of the flat preset.
+pub const FLAT_SURFACE_HEIGHT: usize = 64;
+
+/// How chunks that aren't in the save are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeneratorPreset {
+    /// random blocks everywhere, see `Chunk::randomized`
+    #[default]
+    Random,
+    /// cobblestone with dirt and a grass surface at `FLAT_SURFACE_HEIGHT`
+    Flat,
+    /// only air
+    Empty,
+}
+
+impl GeneratorPreset {
+    pub const ALL: [GeneratorPreset; 3] = [GeneratorPreset::Random, GeneratorPreset::Flat, GeneratorPreset::Empty];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorPreset::Random => "random",
+            GeneratorPreset::Flat => "flat",
+            GeneratorPreset::Empty => "empty",
+        }
+    }
+
+    /// Generates a chunk, the same for the same seed and position.
+    pub fn generate(&self, position: Point2<i32>, seed: u64) -> Chunk {
+        match self {
+            GeneratorPreset::Random => Chunk::randomized(position, seed),
+            GeneratorPreset::Flat => layered(position, |y| match y {
+                y if y < FLAT_SURFACE_HEIGHT - 4 => Material::Cobblestone,
+                y if y < FLAT_SURFACE_HEIGHT => Material::Dirt,
+                y if y == FLAT_SURFACE_HEIGHT => Material::Grass,
+                _ => Material::Air,
+            }),
+            GeneratorPreset::Empty => layered(position, |_| Material::Air),
+        }
+    }
+}
+
+impl std::str::FromStr for GeneratorPreset {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|preset| preset.name()).collect();
+            format!("unknown generator {name:?}, expected one of {}", names.join(", "))
+        })
+    }
+}
+
+impl std::fmt::Display for GeneratorPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Chunks loaded around the spawn.
+pub fn spawn_chunks() -> impl Iterator<Item = Point2<i32>> {
+    (0..2).flat_map(|x| (0..2).map(move |z| Point2::new(x, z)))
+}
+
+/// A chunk with the same material in every block of a layer.
+fn layered(position: Point2<i32>, material: impl Fn(usize) -> Material) -> Chunk {
+    let mut chunk = Chunk::new(position);
+    for y in 0..CHUNK_HEIGHT {
+        let block = Block::new(material(y));
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk[(x, y, z)] = block;
+            }
+        }
+    }
+    chunk
+}
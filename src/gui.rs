@@ -1,26 +1,103 @@
+use std::collections::VecDeque;
 
+use cgmath::{Point3, Rad, Vector3};
+use egui::{Color32, Stroke};
+
+use crate::{block::horizontal_facing, renderer::{chunk_mesh::{MemoryUsage, MeshManager}, DrawStats}, timestep::FrameTimings, world::World};
+
+/// frames kept for the frame time graph
+const FRAME_HISTORY: usize = 240;
+/// frame time at the top of the graph
+const GRAPH_MAX_FRAME_TIME: f32 = 1.0 / 20.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+
+/// Debug overlay toggled with F3.
 pub struct Gui {
-    pub position: [f32; 3],
-    pub direction: [f32; 3],
-    pub render_time: std::time::Duration,
-    pub update_time: std::time::Duration,
-    pub blocks: usize,
-    pub vertices: usize,
-    pub memory_usage: usize,
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub timings: FrameTimings,
+    /// seconds per frame, oldest first
+    pub frame_times: VecDeque<f32>,
+    pub loaded_chunks: usize,
+    pub sub_chunks: usize,
+    pub draw_stats: DrawStats,
+    pub memory_usage: MemoryUsage,
 }
 
 impl Gui {
+    pub fn new() -> Self {
+        Self {
+            position: Point3::new(0.0, 0.0, 0.0),
+            direction: Vector3::unit_x(),
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            timings: FrameTimings::default(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            loaded_chunks: 0,
+            sub_chunks: 0,
+            draw_stats: DrawStats::default(),
+            memory_usage: MemoryUsage::default(),
+        }
+    }
+
+    /// Takes the stats of the last frame.
+    pub fn update(&mut self, world: &World, meshes: &MeshManager, timings: FrameTimings, draw_stats: DrawStats) {
+        self.position = world.camera.eye;
+        self.direction = world.camera.direction;
+        self.yaw = world.camera.yaw;
+        self.pitch = world.camera.pitch;
+        self.timings = timings;
+        if self.frame_times.len() == FRAME_HISTORY { self.frame_times.pop_front(); }
+        self.frame_times.push_back(timings.frame_time.as_secs_f32());
+        self.loaded_chunks = world.loaded_chunks.chunks.len();
+        self.sub_chunks = meshes.sub_chunks().count();
+        self.draw_stats = draw_stats;
+        self.memory_usage = meshes.memory_usage(&world.loaded_chunks);
+    }
+
     pub fn ui(&self, ctx: &egui::Context) {
-        egui::Window::new("test")
+        egui::Window::new("debug")
         .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_TOP, [4.0, 4.0])
         .show(ctx, |ui| {
-            ui.label(format!("x: {:.2}, y: {:.2}, z: {:.2}", self.position[0], self.position[1], self.position[2]));
-            ui.label(format!("render_time: {:.2?}", self.render_time));
-            ui.label(format!("update_time: {:.2?}", self.update_time));
-            ui.label(format!("fps: {:.2}", 1.0 / self.render_time.as_secs_f32()));
-            ui.label(format!("blocks: {}", self.blocks));
-            ui.label(format!("vertices: {}", self.vertices));
-            ui.label(format!("memory_usage: {:.2} MiB", self.memory_usage as f32 / 1_048_576.0))
+            let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+            ui.label(format!("fps: {:.0} ({:.2} ms average)", 1.0 / average, average * 1000.0));
+            ui.label(format!("frame: {:.2?}, update: {:.2?} ({} ticks), mesh: {:.2?}, render: {:.2?}", self.timings.frame_time, self.timings.update_time, self.timings.ticks, self.timings.mesh_time, self.timings.render_time));
+            self.frame_time_graph(ui);
+            ui.separator();
+
+            ui.label(format!("x: {:.2}, y: {:.2}, z: {:.2}", self.position.x, self.position.y, self.position.z));
+            ui.label(format!("block: {} {} {}", self.position.x.floor(), self.position.y.floor(), self.position.z.floor()));
+            ui.label(format!("facing: {:?} (yaw {:.1}, pitch {:.1})", horizontal_facing(self.direction), cgmath::Deg::from(self.yaw).0.rem_euclid(360.0), cgmath::Deg::from(self.pitch).0));
+            ui.separator();
+
+            ui.label(format!("chunks: {}, sub chunks: {}", self.loaded_chunks, self.sub_chunks));
+            ui.label(format!("vertices: {}", self.draw_stats.vertices));
+            ui.label(format!("draw calls: {}", self.draw_stats.draw_calls));
+            ui.label(format!("chunk memory: {:.2} MiB", self.memory_usage.chunk_storage as f32 / 1_048_576.0));
+            ui.label(format!("gpu buffers: {:.2} MiB", self.memory_usage.gpu_buffers as f32 / 1_048_576.0));
         });
     }
+
+    /// One bar per frame, with lines at 60 and 30 fps.
+    fn frame_time_graph(&self, ui: &mut egui::Ui) {
+        let (response, painter) = ui.allocate_painter(egui::vec2(FRAME_HISTORY as f32, GRAPH_HEIGHT), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::from_black_alpha(120));
+
+        for (i, frame_time) in self.frame_times.iter().enumerate() {
+            let height = (frame_time / GRAPH_MAX_FRAME_TIME).min(1.0) * rect.height();
+            let color = if *frame_time > 1.0 / 30.0 { Color32::RED } else if *frame_time > 1.0 / 60.0 { Color32::YELLOW } else { Color32::GREEN };
+            let x = rect.left() + i as f32 + 0.5;
+            painter.line_segment([egui::pos2(x, rect.bottom()), egui::pos2(x, rect.bottom() - height)], Stroke::new(1.0, color));
+        }
+
+        for fps in [60.0, 30.0] {
+            let y = rect.bottom() - (1.0 / fps) / GRAPH_MAX_FRAME_TIME * rect.height();
+            painter.hline(rect.x_range(), y, Stroke::new(1.0, Color32::from_white_alpha(80)));
+        }
+    }
 }
@@ -0,0 +1,370 @@
+use std::{collections::BTreeMap, fmt, path::Path, str::FromStr};
+
+use anyhow::Context;
+use serde::{de::{value::StrDeserializer, IntoDeserializer}, Deserialize, Serialize};
+use winit::{event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent}, keyboard::{KeyCode, PhysicalKey}};
+
+/// Key bindings file, next to the world save.
+pub const CONTROLS_PATH: &str = "controls.toml";
+
+/// Something the player can do, bound to any number of keys, mouse buttons or scroll directions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Descend,
+    BreakBlock,
+    PlaceBlock,
+    HotbarNext,
+    HotbarPrevious,
+    Hotbar1,
+    Hotbar2,
+    Hotbar3,
+    Hotbar4,
+    Hotbar5,
+    Hotbar6,
+    Hotbar7,
+    Hotbar8,
+    Hotbar9,
+    ToggleSpectator,
+    ToggleWireframe,
+    ToggleChunkBorders,
+    ToggleDebug,
+    ToggleSubChunkBounds,
+    ToggleConsole,
+    OpenCommand,
+    OpenControls,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 28] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Descend,
+        Action::BreakBlock,
+        Action::PlaceBlock,
+        Action::HotbarNext,
+        Action::HotbarPrevious,
+        Action::Hotbar1,
+        Action::Hotbar2,
+        Action::Hotbar3,
+        Action::Hotbar4,
+        Action::Hotbar5,
+        Action::Hotbar6,
+        Action::Hotbar7,
+        Action::Hotbar8,
+        Action::Hotbar9,
+        Action::ToggleSpectator,
+        Action::ToggleWireframe,
+        Action::ToggleChunkBorders,
+        Action::ToggleDebug,
+        Action::ToggleSubChunkBounds,
+        Action::ToggleConsole,
+        Action::OpenCommand,
+        Action::OpenControls,
+        Action::Pause,
+    ];
+
+    /// Hotbar slot actions in slot order.
+    pub const HOTBAR: [Action; 9] = [
+        Action::Hotbar1,
+        Action::Hotbar2,
+        Action::Hotbar3,
+        Action::Hotbar4,
+        Action::Hotbar5,
+        Action::Hotbar6,
+        Action::Hotbar7,
+        Action::Hotbar8,
+        Action::Hotbar9,
+    ];
+
+    /// Key of the action in the bindings file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Jump => "jump",
+            Action::Descend => "descend",
+            Action::BreakBlock => "break_block",
+            Action::PlaceBlock => "place_block",
+            Action::HotbarNext => "hotbar_next",
+            Action::HotbarPrevious => "hotbar_previous",
+            Action::Hotbar1 => "hotbar_1",
+            Action::Hotbar2 => "hotbar_2",
+            Action::Hotbar3 => "hotbar_3",
+            Action::Hotbar4 => "hotbar_4",
+            Action::Hotbar5 => "hotbar_5",
+            Action::Hotbar6 => "hotbar_6",
+            Action::Hotbar7 => "hotbar_7",
+            Action::Hotbar8 => "hotbar_8",
+            Action::Hotbar9 => "hotbar_9",
+            Action::ToggleSpectator => "toggle_spectator",
+            Action::ToggleWireframe => "toggle_wireframe",
+            Action::ToggleChunkBorders => "toggle_chunk_borders",
+            Action::ToggleDebug => "toggle_debug",
+            Action::ToggleSubChunkBounds => "toggle_sub_chunk_bounds",
+            Action::ToggleConsole => "toggle_console",
+            Action::OpenCommand => "open_command",
+            Action::OpenControls => "open_controls",
+            Action::Pause => "pause",
+        }
+    }
+
+    /// Also accepts `exit`, what `pause` was called before the pause menu, so older bindings files keep working.
+    pub fn from_name(name: &str) -> Option<Action> {
+        if name == "exit" { return Some(Action::Pause); }
+        Self::ALL.iter().copied().find(|action| action.name() == name)
+    }
+
+    fn default_bindings(&self) -> Vec<Binding> {
+        use Binding::Key;
+        match self {
+            Action::MoveForward => vec![Key(KeyCode::KeyW)],
+            Action::MoveBackward => vec![Key(KeyCode::KeyS)],
+            Action::MoveLeft => vec![Key(KeyCode::KeyA)],
+            Action::MoveRight => vec![Key(KeyCode::KeyD)],
+            Action::Jump => vec![Key(KeyCode::Space)],
+            Action::Descend => vec![Key(KeyCode::ShiftLeft)],
+            Action::BreakBlock => vec![Binding::Mouse(MouseButton::Left)],
+            Action::PlaceBlock => vec![Binding::Mouse(MouseButton::Right)],
+            Action::HotbarNext => vec![Binding::ScrollDown],
+            Action::HotbarPrevious => vec![Binding::ScrollUp],
+            Action::Hotbar1 => vec![Key(KeyCode::Digit1)],
+            Action::Hotbar2 => vec![Key(KeyCode::Digit2)],
+            Action::Hotbar3 => vec![Key(KeyCode::Digit3)],
+            Action::Hotbar4 => vec![Key(KeyCode::Digit4)],
+            Action::Hotbar5 => vec![Key(KeyCode::Digit5)],
+            Action::Hotbar6 => vec![Key(KeyCode::Digit6)],
+            Action::Hotbar7 => vec![Key(KeyCode::Digit7)],
+            Action::Hotbar8 => vec![Key(KeyCode::Digit8)],
+            Action::Hotbar9 => vec![Key(KeyCode::Digit9)],
+            Action::ToggleSpectator => vec![Key(KeyCode::KeyN)],
+            Action::ToggleWireframe => vec![Key(KeyCode::F1)],
+            Action::ToggleChunkBorders => vec![Key(KeyCode::F2)],
+            Action::ToggleDebug => vec![Key(KeyCode::F3)],
+            Action::ToggleSubChunkBounds => vec![Key(KeyCode::F4)],
+            Action::ToggleConsole => vec![Key(KeyCode::Backquote)],
+            Action::OpenCommand => vec![Key(KeyCode::Slash)],
+            Action::OpenControls => vec![Key(KeyCode::F6)],
+            Action::Pause => vec![Key(KeyCode::Escape)],
+        }
+    }
+}
+
+/// A physical input an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    ScrollUp,
+    ScrollDown,
+}
+
+impl Binding {
+    /// The binding an event presses or releases, key repeats are ignored.
+    pub fn from_event(event: &WindowEvent) -> Option<(Binding, bool)> {
+        match event {
+            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key_code), state, repeat: false, .. }, .. } => Some((Binding::Key(*key_code), state.is_pressed())),
+            WindowEvent::MouseInput { state, button, .. } => Some((Binding::Mouse(*button), *state == ElementState::Pressed)),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                if y > 0.0 { Some((Binding::ScrollUp, true)) } else if y < 0.0 { Some((Binding::ScrollDown, true)) } else { None }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Keys are written like winit's `KeyCode` variants (`KeyW`, `Space`, `F3`), mouse buttons as `MouseLeft`, `MouseRight`,
+/// `MouseMiddle`, `MouseBack`, `MouseForward` or `Mouse<number>`, and the wheel as `ScrollUp` and `ScrollDown`.
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key_code) => write!(f, "{key_code:?}"),
+            Binding::Mouse(MouseButton::Left) => f.write_str("MouseLeft"),
+            Binding::Mouse(MouseButton::Right) => f.write_str("MouseRight"),
+            Binding::Mouse(MouseButton::Middle) => f.write_str("MouseMiddle"),
+            Binding::Mouse(MouseButton::Back) => f.write_str("MouseBack"),
+            Binding::Mouse(MouseButton::Forward) => f.write_str("MouseForward"),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{button}"),
+            Binding::ScrollUp => f.write_str("ScrollUp"),
+            Binding::ScrollDown => f.write_str("ScrollDown"),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        Ok(match text {
+            "MouseLeft" => Binding::Mouse(MouseButton::Left),
+            "MouseRight" => Binding::Mouse(MouseButton::Right),
+            "MouseMiddle" => Binding::Mouse(MouseButton::Middle),
+            "MouseBack" => Binding::Mouse(MouseButton::Back),
+            "MouseForward" => Binding::Mouse(MouseButton::Forward),
+            "ScrollUp" => Binding::ScrollUp,
+            "ScrollDown" => Binding::ScrollDown,
+            text => match text.strip_prefix("Mouse").and_then(|button| button.parse().ok()) {
+                Some(button) => Binding::Mouse(MouseButton::Other(button)),
+                None => {
+                    let deserializer: StrDeserializer<serde::de::value::Error> = text.into_deserializer();
+                    Binding::Key(KeyCode::deserialize(deserializer).with_context(|| format!("unknown key {text:?}"))?)
+                }
+            },
+        })
+    }
+}
+
+/// Layout of the bindings file, action names to binding names.
+#[derive(Serialize, Deserialize)]
+struct ControlsFile {
+    bindings: BTreeMap<String, Vec<String>>,
+}
+
+/// Which bindings trigger each action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self { bindings: Action::ALL.iter().map(|action| (*action, action.default_bindings())).collect() }
+    }
+}
+
+impl InputMap {
+    /// Reads the bindings file, the defaults if there is none.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() { return Ok(Self::default()); }
+
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_toml()?).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Actions missing from the file keep their default bindings, unknown ones are skipped with a warning.
+    pub fn from_toml(text: &str) -> anyhow::Result<Self> {
+        let file: ControlsFile = toml::from_str(text)?;
+        let mut map = Self::default();
+        for (name, bindings) in file.bindings {
+            let Some(action) = Action::from_name(&name) else {
+                log::warn!("skipping bindings of unknown action {name:?}");
+                continue;
+            };
+            let bindings = bindings.iter().map(|binding| binding.parse()).collect::<anyhow::Result<Vec<Binding>>>().with_context(|| format!("in the bindings of {name}"))?;
+            map.bindings.insert(action, bindings);
+        }
+        Ok(map)
+    }
+
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        let bindings = self.bindings.iter().map(|(action, bindings)| (action.name().to_string(), bindings.iter().map(Binding::to_string).collect())).collect();
+        Ok(toml::to_string(&ControlsFile { bindings })?)
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Actions triggered by a binding, more than one means the binding is in conflict.
+    pub fn actions(&self, binding: Binding) -> impl Iterator<Item = Action> + '_ {
+        self.bindings.iter().filter(move |(_, bindings)| bindings.contains(&binding)).map(|(action, _)| *action)
+    }
+
+    /// Bindings shared by several actions, with those actions.
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let mut conflicts: Vec<(Binding, Vec<Action>)> = vec![];
+        for binding in self.bindings.values().flatten() {
+            if conflicts.iter().any(|(conflict, _)| conflict == binding) { continue; }
+            let actions: Vec<Action> = self.actions(*binding).collect();
+            if actions.len() > 1 { conflicts.push((*binding, actions)); }
+        }
+        conflicts
+    }
+
+    /// Replaces the binding at `index`, or adds one when there is no `index`.
+    pub fn rebind(&mut self, action: Action, index: Option<usize>, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        match index.filter(|index| *index < bindings.len()) {
+            Some(index) => bindings[index] = binding,
+            None if !bindings.contains(&binding) => bindings.push(binding),
+            None => (),
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, index: usize) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            if index < bindings.len() { bindings.remove(index); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_round_trip_through_text() {
+        for text in ["KeyW", "Space", "F3", "Backquote", "MouseLeft", "MouseForward", "Mouse7", "ScrollUp", "ScrollDown"] {
+            assert_eq!(text.parse::<Binding>().unwrap().to_string(), text);
+        }
+        assert!("NotAKey".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn defaults_round_trip_through_toml() {
+        let map = InputMap::default();
+        assert_eq!(InputMap::from_toml(&map.to_toml().unwrap()).unwrap(), map);
+    }
+
+    #[test]
+    fn missing_actions_keep_their_defaults() {
+        let map = InputMap::from_toml("[bindings]\njump = [\"KeyJ\", \"MouseMiddle\"]\nfly = [\"KeyF\"]\n").unwrap();
+        assert_eq!(map.bindings(Action::Jump), &[Binding::Key(KeyCode::KeyJ), Binding::Mouse(MouseButton::Middle)]);
+        assert_eq!(map.bindings(Action::MoveForward), &[Binding::Key(KeyCode::KeyW)]);
+    }
+
+    #[test]
+    fn exit_bindings_load_as_pause() {
+        let map = InputMap::from_toml("[bindings]\nexit = [\"KeyP\"]\n").unwrap();
+        assert_eq!(map.bindings(Action::Pause), &[Binding::Key(KeyCode::KeyP)]);
+    }
+
+    #[test]
+    fn invalid_bindings_are_errors() {
+        assert!(InputMap::from_toml("[bindings]\njump = [\"Spacebar\"]\n").is_err());
+        assert!(InputMap::from_toml("bindings = 3").is_err());
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let mut map = InputMap::default();
+        assert!(map.conflicts().is_empty());
+
+        map.rebind(Action::Jump, Some(0), Binding::Key(KeyCode::KeyW));
+        assert_eq!(map.conflicts(), vec![(Binding::Key(KeyCode::KeyW), vec![Action::MoveForward, Action::Jump])]);
+        assert_eq!(map.actions(Binding::Key(KeyCode::Space)).count(), 0);
+
+        map.unbind(Action::MoveForward, 0);
+        assert!(map.conflicts().is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use cgmath::{InnerSpace, Point2, Vector3};
+
+use crate::{block::{Block, Half, Material}, player::player_aabb, world::World};
+
+impl World {
+    /// Removes the block under the crosshair, both halves for doors.
+    pub fn break_targeted_block(&mut self) -> bool {
+        let Some(hit) = self.targeted_block() else { return false; };
+        let Some(&block) = self.loaded_chunks.block(hit.position) else { return false; };
+
+        self.loaded_chunks.set_block(hit.position, Block::new(Material::Air));
+        if block.material == Material::Door {
+            let other_half = hit.position + match block.state.half() {
+                Half::Bottom => Vector3::new(0, 1, 0),
+                Half::Top => Vector3::new(0, -1, 0),
+            };
+            if self.loaded_chunks.block(other_half).is_some_and(|other| other.material == Material::Door) {
+                self.loaded_chunks.set_block(other_half, Block::new(Material::Air));
+            }
+        }
+        true
+    }
+
+    /// Places a block against the face under the crosshair, oriented by the look direction.
+    /// Nothing is placed when the spot is occupied or the block would intersect the player.
+    pub fn place_targeted_block(&mut self, material: Material) -> bool {
+        let Some(hit) = self.targeted_block() else { return false; };
+        let Some(face) = hit.face else { return false; };
+
+        let hit_point = self.camera.eye + self.camera.direction.normalize() * hit.distance;
+        let block = Block::placed(material, self.camera.direction, face, hit_point.y - hit.position.y as f32);
+        let position = hit.adjacent();
+        let mut blocks = vec![(position, block)];
+        if material == Material::Door {
+            // doors are two blocks tall, placed from the bottom
+            blocks[0].1.state = block.state.with_half(Half::Bottom);
+            blocks.push((position + Vector3::new(0, 1, 0), Block::with_state(material, block.state.with_half(Half::Top))));
+        }
+
+        let player = player_aabb(self.camera.eye);
+        for (position, block) in blocks.iter() {
+            if !self.loaded_chunks.block(*position).is_some_and(|existing| existing.material.is_replaceable()) { return false; }
+            if block.collision_boxes(*position).any(|collision_box| collision_box.intersects(&player)) { return false; }
+        }
+
+        for (position, block) in blocks {
+            self.loaded_chunks.set_block(position, block);
+        }
+        true
+    }
+
+    /// Replaces every loaded chunk with a freshly generated one for `seed`, dropping all changes.
+    pub fn regenerate(&mut self, seed: u64) {
+        self.seed = seed;
+        let positions: Vec<Point2<i32>> = self.loaded_chunks.chunks.values().map(|chunk| chunk.position).collect();
+        for position in positions {
+            self.loaded_chunks.insert(self.generator.generate(position, seed));
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! World data, generation, meshing to CPU arrays, physics and saving, without a window or GPU.
+//! The client's `renderer` uploads the meshes, servers and tools use this crate headlessly.
+
+pub mod aabb;
+pub mod block;
+pub mod block_entity;
+pub mod block_model;
+pub mod block_vertex;
+pub mod chunk;
+pub mod client;
+pub mod generator;
+pub mod mesher;
+pub mod player;
+pub mod protocol;
+pub mod raycast;
+pub mod save;
+pub mod server;
+pub mod sign;
+pub mod time;
+pub mod timestep;
+pub mod wire;
@@ -1,13 +1,42 @@
 mod window;
 mod state;
-mod texture;
 mod camera;
 mod egui_renderer;
 mod gui;
-mod chunk;
-mod block;
-mod block_vertex;
+mod cli;
+mod commands;
+mod console;
+mod benchmark;
+mod bindings_menu;
+mod input;
+mod interaction;
+mod pause_menu;
+mod renderer;
+mod settings;
+mod settings_menu;
+mod world;
+
+use clap::Parser;
+use voxel_game::{block, block_entity, block_vertex, chunk, generator, mesher, player, raycast, save, sign, time, timestep};
 
 fn main() {
-    window::run()
+    env_logger::init();
+    let mut cli = cli::Cli::parse();
+    // saved worlds keep the seed and generator they were created with
+    if matches!(cli.mode(), cli::Mode::Windowed | cli::Mode::Server) {
+        match save::WorldSave::new(cli.world_directory()).open(cli.seed, cli.generator) {
+            Ok(metadata) => (cli.seed, cli.generator) = (Some(metadata.seed), Some(metadata.generator)),
+            Err(error) => {
+                eprintln!("error: {error:#}");
+                std::process::exit(1);
+            }
+        }
+    }
+    match cli.mode() {
+        cli::Mode::Server => if let Err(error) = voxel_game::server::serve(save::WorldSave::new(cli.world_directory()), cli.seed(), cli.generator.unwrap_or_default(), voxel_game::server::DEFAULT_ADDRESS, |reply| println!("{reply}")) {
+            eprintln!("error: {error:#}");
+            std::process::exit(1);
+        },
+        cli::Mode::Windowed | cli::Mode::Benchmark | cli::Mode::Client => window::run(cli),
+    }
 }
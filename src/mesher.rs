@@ -0,0 +1,162 @@
+use cgmath::{EuclideanSpace, Point3, Vector3};
+
+use crate::{block::{Block, Material, Transparency}, block_model::ModelFace, block_vertex::{BlockVertex, Face, PackedBlockVertex, SUB_VOXEL_PRECISION}, chunk::{Chunk, ChunkManager, CHUNK_HEIGHT, CHUNK_SIZE, SUB_CHUNK_HEIGHT}};
+
+/// Vertices and indices of a mesh, four vertices and six indices per face.
+#[derive(Debug, Default, Clone)]
+pub struct MeshData {
+    pub vertices: Vec<PackedBlockVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn push_face(&mut self, model_face: &ModelFace, block: &Block, offset: Point3<u16>) {
+        let index_offset = self.vertices.len() as u32;
+        let atlas_index = block.atlas_index(model_face.texture_face);
+        for (position, uv) in model_face.positions.iter().zip(model_face.uvs) {
+            let position = offset + position.cast::<u16>().unwrap().to_vec();
+            self.vertices.push(BlockVertex::new(position, uv, model_face.face, atlas_index).pack());
+        }
+
+        for index in Block::FACE_INDICES {
+            self.indices.push(index + index_offset);
+        }
+    }
+
+    pub fn faces(&self) -> usize {
+        self.vertices.len() / 4
+    }
+}
+
+/// A meshed sub chunk, in sub chunk space.
+#[derive(Debug, Default, Clone)]
+pub struct SubChunkMesh {
+    /// opaque and cutout faces
+    pub mesh: MeshData,
+    /// blended faces, drawn back to front
+    pub translucent_mesh: MeshData,
+    /// centers of the translucent faces, in vertex buffer order
+    pub translucent_faces: Vec<Point3<f32>>,
+}
+
+/// Meshes a sub chunk, `chunks` provides the blocks next to the chunk borders.
+pub fn mesh_sub_chunk(chunk: &Chunk, index: usize, chunks: &ChunkManager) -> SubChunkMesh {
+    let mut sub_chunk = SubChunkMesh::default();
+    let y_offset = index * SUB_CHUNK_HEIGHT;
+
+    for y in y_offset..y_offset + SUB_CHUNK_HEIGHT {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let block = &chunk[(x, y, z)];
+                if block.material == Material::Air { continue; }
+                let translucent = block.material.transparency() == Transparency::Translucent;
+                let offset = Point3::new(x as u16, (y - y_offset) as u16, z as u16) * SUB_VOXEL_PRECISION;
+
+                for model_face in block.geometry().faces.iter() {
+                    if let Some(cull) = model_face.cull {
+                        if !is_face_visible(chunk, cull, x, y, z, chunks) { continue; }
+                    }
+
+                    if translucent {
+                        sub_chunk.translucent_mesh.push_face(model_face, block, offset);
+                        sub_chunk.translucent_faces.push(face_center(model_face, offset));
+                    } else {
+                        sub_chunk.mesh.push_face(model_face, block, offset);
+                    }
+                }
+            }
+        }
+    }
+    sub_chunk
+}
+
+/// Whether the `face` side of the block at `x, y, z` can be seen, blocks in chunks that aren't loaded don't hide it.
+pub fn is_face_visible(chunk: &Chunk, face: Face, x: usize, y: usize, z: usize, chunks: &ChunkManager) -> bool {
+    let normal = face.normal();
+    let (nx, ny, nz) = (x as i32 + normal.x, y as i32 + normal.y, z as i32 + normal.z);
+    if ny < 0 || ny >= CHUNK_HEIGHT as i32 {
+        return true;
+    }
+
+    let neighbor = if nx < 0 || nx >= CHUNK_SIZE as i32 || nz < 0 || nz >= CHUNK_SIZE as i32 {
+        let origin = chunk.world_origin();
+        match chunks.block(Point3::new(origin.x + nx, ny, origin.z + nz)) {
+            Some(neighbor) => neighbor,
+            None => return true,
+        }
+    } else {
+        &chunk[(nx as usize, ny as usize, nz as usize)]
+    };
+
+    !neighbor.hides_face_of(&chunk[(x, y, z)], face.opposite())
+}
+
+fn face_center(model_face: &ModelFace, offset: Point3<u16>) -> Point3<f32> {
+    let sum = model_face.positions.iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, position| sum + position.cast::<f32>().unwrap().to_vec());
+    (offset.cast::<f32>().unwrap() + sum / 4.0) / SUB_VOXEL_PRECISION as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point2;
+
+    use super::*;
+    use crate::generator::GeneratorPreset;
+
+    fn empty_chunks(positions: &[(i32, i32)]) -> ChunkManager {
+        let mut chunks = ChunkManager::new();
+        for position in positions {
+            chunks.insert(GeneratorPreset::Empty.generate(Point2::from(*position), 0));
+        }
+        chunks
+    }
+
+    fn set(chunks: &mut ChunkManager, position: (i32, i32, i32), material: Material) {
+        let (chunk_position, local) = crate::chunk::chunk_local_position(position.into());
+        chunks.get_mut(chunk_position).unwrap()[local] = Block::new(material);
+    }
+
+    fn mesh(chunks: &ChunkManager, position: (i32, i32), index: usize) -> SubChunkMesh {
+        mesh_sub_chunk(chunks.get(position.into()).unwrap(), index, chunks)
+    }
+
+    #[test]
+    fn single_cube_has_six_faces() {
+        let mut chunks = empty_chunks(&[(0, 0)]);
+        set(&mut chunks, (4, 4, 4), Material::Cobblestone);
+        let sub_chunk = mesh(&chunks, (0, 0), 0);
+        assert_eq!(sub_chunk.mesh.faces(), 6);
+        assert_eq!(sub_chunk.mesh.indices.len(), 36);
+        assert!(sub_chunk.translucent_mesh.vertices.is_empty());
+    }
+
+    #[test]
+    fn touching_cubes_hide_the_faces_between_them() {
+        let mut chunks = empty_chunks(&[(0, 0)]);
+        set(&mut chunks, (4, 4, 4), Material::Cobblestone);
+        set(&mut chunks, (5, 4, 4), Material::Dirt);
+        assert_eq!(mesh(&chunks, (0, 0), 0).mesh.faces(), 10);
+    }
+
+    #[test]
+    fn neighboring_chunks_and_sub_chunks_cull_faces() {
+        let mut chunks = empty_chunks(&[(0, 0), (1, 0)]);
+        set(&mut chunks, (31, 31, 4), Material::Cobblestone);
+        set(&mut chunks, (32, 31, 4), Material::Cobblestone);
+        set(&mut chunks, (31, 32, 4), Material::Cobblestone);
+        assert_eq!(mesh(&chunks, (0, 0), 0).mesh.faces(), 4);
+        assert_eq!(mesh(&chunks, (1, 0), 0).mesh.faces(), 5);
+        assert_eq!(mesh(&chunks, (0, 0), 1).mesh.faces(), 5);
+    }
+
+    #[test]
+    fn translucent_blocks_go_in_their_own_mesh() {
+        let mut chunks = empty_chunks(&[(0, 0)]);
+        set(&mut chunks, (4, 4, 4), Material::Water);
+        set(&mut chunks, (5, 4, 4), Material::Water);
+        let sub_chunk = mesh(&chunks, (0, 0), 0);
+        assert!(sub_chunk.mesh.vertices.is_empty());
+        assert_eq!(sub_chunk.translucent_mesh.faces(), 10);
+        assert_eq!(sub_chunk.translucent_faces.len(), 10);
+    }
+}
@@ -0,0 +1,38 @@
+/// What the player picked in the pause menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuAction {
+    Resume,
+    Settings,
+    SaveAndQuit,
+}
+
+/// Shown while the game is paused, the simulation doesn't run while it's open.
+pub struct PauseMenu {
+    pub open: bool,
+    /// error of a failed save, shown under the buttons
+    pub status: Option<String>,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self { open: false, status: None }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) -> Option<PauseMenuAction> {
+        let mut action = None;
+        egui::Window::new("Paused")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.vertical_centered_justified(|ui| {
+                ui.set_width(200.0);
+                if ui.button("Resume").clicked() { action = Some(PauseMenuAction::Resume); }
+                if ui.button("Settings").clicked() { action = Some(PauseMenuAction::Settings); }
+                if ui.button("Save and quit").clicked() { action = Some(PauseMenuAction::SaveAndQuit); }
+            });
+            if let Some(status) = &self.status { ui.colored_label(egui::Color32::LIGHT_RED, status); }
+        });
+        action
+    }
+}
@@ -0,0 +1,196 @@
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+
+use crate::{aabb::Aabb, block::Material, chunk::{ChunkManager, CHUNK_HEIGHT}};
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// height of the camera above the player's feet
+pub const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+const WALK_SPEED: f32 = 4.3;
+const GRAVITY: f32 = 32.0;
+const TERMINAL_VELOCITY: f32 = 78.0;
+/// high enough to clear a bit more than one block
+const JUMP_SPEED: f32 = 9.0;
+/// walking into a ledge at most this high climbs it without jumping
+const STEP_HEIGHT: f32 = 1.0;
+/// how quickly the horizontal velocity approaches the wanted one, per second
+const GROUND_ACCELERATION: f32 = 14.0;
+const AIR_ACCELERATION: f32 = 2.5;
+
+/// Blocks selected with the number keys.
+pub const HOTBAR: [Material; 9] = [
+    Material::Cobblestone,
+    Material::Dirt,
+    Material::Grass,
+    Material::Log,
+    Material::CobblestoneSlab,
+    Material::CobblestoneStairs,
+    Material::Glass,
+    Material::Door,
+    Material::Sign,
+];
+
+/// Bounding box of the player whose camera is at `eye`.
+pub fn player_aabb(eye: Point3<f32>) -> Aabb {
+    let feet = eye - Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+    let half_width = PLAYER_WIDTH / 2.0;
+    Aabb::new(feet - Vector3::new(half_width, 0.0, half_width), feet + Vector3::new(half_width, PLAYER_HEIGHT, half_width))
+}
+
+/// Movement keys held during a tick.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Movement {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+}
+
+pub struct Player {
+    /// center of the bottom of the bounding box
+    pub position: Point3<f32>,
+    /// position at the start of the last tick, rendering interpolates from it
+    pub previous_position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub on_ground: bool,
+    /// noclip flight, moved by the camera controller instead of physics
+    pub spectator: bool,
+}
+
+impl Player {
+    pub fn new(eye: Point3<f32>) -> Self {
+        let position = eye - Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+        Self { position, previous_position: position, velocity: Vector3::zero(), on_ground: false, spectator: false }
+    }
+
+    /// Moves the feet to `position` without interpolating to it and stops the player.
+    pub fn teleport(&mut self, position: Point3<f32>) {
+        self.position = position;
+        self.previous_position = position;
+        self.velocity = Vector3::zero();
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.position + Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0)
+    }
+
+    /// Eye position `alpha` of the way from the last tick to the current one.
+    pub fn interpolated_eye(&self, alpha: f32) -> Point3<f32> {
+        let position = self.previous_position + (self.position - self.previous_position) * alpha;
+        position + Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0)
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        player_aabb(self.eye())
+    }
+
+    /// Moves the player to the camera of the spectator mode.
+    pub fn follow_camera(&mut self, eye: Point3<f32>) {
+        self.position = eye - Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+        self.velocity = Vector3::zero();
+        self.on_ground = false;
+    }
+
+    /// Whether the player's box is inside solid blocks.
+    pub fn is_obstructed(&self, chunks: &ChunkManager) -> bool {
+        let aabb = self.aabb();
+        collision_boxes(chunks, &aabb).iter().any(|collision_box| collision_box.intersects(&aabb))
+    }
+
+    /// Walks towards the pressed directions relative to the horizontal look direction, jumps and falls.
+    pub fn update(&mut self, movement: Movement, direction: Vector3<f32>, chunks: &ChunkManager, dt: f32) {
+        let forward = Vector3::new(direction.x, 0.0, direction.z).normalize();
+        let right = forward.cross(Vector3::unit_y());
+
+        let mut wish = Vector3::zero();
+        if movement.forward { wish += forward; }
+        if movement.backward { wish -= forward; }
+        if movement.right { wish += right; }
+        if movement.left { wish -= right; }
+        if wish.magnitude2() > 0.0 { wish = wish.normalize() * WALK_SPEED; }
+
+        // without input this is the friction bringing the player to a halt
+        let acceleration = if self.on_ground { GROUND_ACCELERATION } else { AIR_ACCELERATION };
+        let blend = (acceleration * dt).min(1.0);
+        self.velocity.x += (wish.x - self.velocity.x) * blend;
+        self.velocity.z += (wish.z - self.velocity.z) * blend;
+
+        if self.on_ground && movement.jump {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+
+        let offset = self.velocity * dt;
+        let moved = self.move_and_collide(offset, chunks);
+
+        self.on_ground = offset.y < 0.0 && moved.y > offset.y;
+        for axis in 0..3 {
+            if (moved[axis] - offset[axis]).abs() > 1e-6 {
+                self.velocity[axis] = 0.0;
+            }
+        }
+        self.position += moved;
+    }
+
+    /// Sweeps the box one axis at a time, vertical first, stepping up onto ledges when walking into them on the ground.
+    /// Returns the offset actually moved.
+    fn move_and_collide(&self, offset: Vector3<f32>, chunks: &ChunkManager) -> Vector3<f32> {
+        let aabb = self.aabb();
+        let region = aabb.expanded_towards(offset).expanded_towards(Vector3::new(0.0, STEP_HEIGHT, 0.0));
+        let boxes = collision_boxes(chunks, &region);
+
+        let moved = sweep(&aabb, offset, &boxes);
+        let blocked_horizontally = (moved.x - offset.x).abs() > 1e-6 || (moved.z - offset.z).abs() > 1e-6;
+        if !self.on_ground || !blocked_horizontally { return moved; }
+
+        let up = sweep(&aabb, Vector3::new(0.0, STEP_HEIGHT, 0.0), &boxes);
+        let raised = aabb.translated(up);
+        let horizontal = sweep(&raised, Vector3::new(offset.x, 0.0, offset.z), &boxes);
+        let down = sweep(&raised.translated(horizontal), Vector3::new(0.0, -up.y, 0.0), &boxes);
+
+        let stepped = up + horizontal + down;
+        if horizontal.x * horizontal.x + horizontal.z * horizontal.z > moved.x * moved.x + moved.z * moved.z + 1e-6 { stepped } else { moved }
+    }
+}
+
+/// Moves `aabb` by `offset` along y, x and then z, stopping each axis at the first box in the way.
+fn sweep(aabb: &Aabb, offset: Vector3<f32>, boxes: &[Aabb]) -> Vector3<f32> {
+    let mut aabb = *aabb;
+    let mut moved = Vector3::zero();
+    for axis in [1, 0, 2] {
+        if offset[axis] == 0.0 { continue; }
+
+        let distance = boxes.iter().fold(offset[axis], |distance, collision_box| aabb.clip_offset(collision_box, axis, distance));
+        aabb.min[axis] += distance;
+        aabb.max[axis] += distance;
+        moved[axis] = distance;
+    }
+    moved
+}
+
+/// Collision boxes of every block touching `region`. Blocks below the world and in unloaded chunks are solid,
+/// so the player can neither fall out of the world nor walk into chunks that aren't there yet.
+fn collision_boxes(chunks: &ChunkManager, region: &Aabb) -> Vec<Aabb> {
+    // one block lower for fences, whose boxes reach into the block above
+    let min = region.min.map(|c| c.floor() as i32) - Vector3::new(0, 1, 0);
+    let max = region.max.map(|c| c.floor() as i32);
+
+    let mut boxes = vec![];
+    for x in min.x..=max.x {
+        for y in min.y..=max.y.min(CHUNK_HEIGHT as i32 - 1) {
+            for z in min.z..=max.z {
+                let position = Point3::new(x, y, z);
+                match chunks.block(position).filter(|_| y >= 0) {
+                    Some(block) => boxes.extend(block.collision_boxes(position)),
+                    None => {
+                        let corner = position.cast::<f32>().unwrap();
+                        boxes.push(Aabb::new(corner, corner + Vector3::new(1.0, 1.0, 1.0)));
+                    }
+                }
+            }
+        }
+    }
+    boxes
+}
@@ -0,0 +1,443 @@
+use std::{collections::BTreeMap, io::{ErrorKind, Read, Write}, net::{Shutdown, SocketAddr, TcpStream}, time::Duration};
+
+use anyhow::{bail, Context};
+use cgmath::{Point2, Point3};
+
+use crate::{block::{Block, BlockState, Material}, chunk::{chunk_local_position, Chunk, ChunkManager, SUB_CHUNKS}, save::Reader, wire::{self, sub_chunk_block_position, sub_chunk_index, SUB_CHUNK_VOLUME}};
+
+/// Bumped whenever a packet changes, the server only lets in clients with the same version.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Largest packet body accepted, a longer length prefix means the stream is broken.
+pub const MAX_PACKET_SIZE: usize = 4 * 1024 * 1024;
+
+/// How long closing waits for the last queued data to be written.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Most data queued for a peer before it counts as backed up, one that stops reading would otherwise be buffered for forever.
+pub const MAX_QUEUED_BYTES: usize = 32 * 1024 * 1024;
+
+/// Everything sent between the server and its clients.
+/// On the wire a packet is its body length as a little endian `u32`, then the packet id and its fields.
+#[derive(Debug)]
+pub enum Packet {
+    /// first packet both ways, answered by the server with its own version
+    Handshake { version: u16 },
+    /// client to server after the handshake, with the chunks around the player it wants
+    Login { name: String, view_distance: u8 },
+    /// server to client, the id the client is known by, the feet position it spawns at and the most chunks around the player the server sends
+    LoginSuccess { player_id: u32, position: Point3<f32>, view_distance: u8 },
+    /// server to client, a chunk around the player, see [`wire::encode_chunk`]
+    ChunkData { chunk: Box<Chunk> },
+    /// clients ask for a block to be set, the server broadcasts the blocks that were
+    BlockChange { position: Point3<i32>, block: Block },
+    /// clients send their own feet position, the server relays it to the others and sends a client its own id to move it back after refusing a movement
+    PlayerMovement { player_id: u32, position: Point3<f32> },
+    /// both ways, the connection is closed after it
+    Disconnect { reason: String },
+    /// server to client, a chunk the player moved away from, block changes aren't sent for it anymore
+    UnloadChunk { position: Point2<i32> },
+    /// client to server, a new view distance after the login
+    ViewDistance { chunks: u8 },
+    /// like `BlockChange` for several blocks of one sub chunk, each addressed by [`wire::sub_chunk_index`]
+    MultiBlockChange { chunk: Point2<i32>, sub_chunk: u8, changes: Vec<(u16, Block)> },
+}
+
+impl Packet {
+    pub fn id(&self) -> u8 {
+        match self {
+            Packet::Handshake { .. } => 0,
+            Packet::Login { .. } => 1,
+            Packet::LoginSuccess { .. } => 2,
+            Packet::ChunkData { .. } => 3,
+            Packet::BlockChange { .. } => 4,
+            Packet::PlayerMovement { .. } => 5,
+            Packet::Disconnect { .. } => 6,
+            Packet::UnloadChunk { .. } => 7,
+            Packet::ViewDistance { .. } => 8,
+            Packet::MultiBlockChange { .. } => 9,
+        }
+    }
+
+    /// The packet framed for the wire.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = vec![self.id()];
+        match self {
+            Packet::Handshake { version } => body.extend_from_slice(&version.to_le_bytes()),
+            Packet::Login { name, view_distance } => {
+                write_string(&mut body, name);
+                body.push(*view_distance);
+            }
+            Packet::LoginSuccess { player_id, position, view_distance } => {
+                body.extend_from_slice(&player_id.to_le_bytes());
+                write_position(&mut body, *position);
+                body.push(*view_distance);
+            }
+            Packet::ChunkData { chunk } => return Self::encode_chunk_data(chunk),
+            Packet::BlockChange { position, block } => {
+                for coordinate in [position.x, position.y, position.z] {
+                    body.extend_from_slice(&coordinate.to_le_bytes());
+                }
+                body.extend_from_slice(&[block.material as u8, block.state.bits()]);
+            }
+            Packet::PlayerMovement { player_id, position } => {
+                body.extend_from_slice(&player_id.to_le_bytes());
+                write_position(&mut body, *position);
+            }
+            Packet::Disconnect { reason } => write_string(&mut body, reason),
+            Packet::UnloadChunk { position } => {
+                body.extend_from_slice(&position.x.to_le_bytes());
+                body.extend_from_slice(&position.y.to_le_bytes());
+            }
+            Packet::ViewDistance { chunks } => body.push(*chunks),
+            Packet::MultiBlockChange { chunk, sub_chunk, changes } => {
+                body.extend_from_slice(&chunk.x.to_le_bytes());
+                body.extend_from_slice(&chunk.y.to_le_bytes());
+                body.push(*sub_chunk);
+                body.extend_from_slice(&(changes.len() as u16).to_le_bytes());
+                for (index, block) in changes {
+                    body.extend_from_slice(&index.to_le_bytes());
+                    body.extend_from_slice(&[block.material as u8, block.state.bits()]);
+                }
+            }
+        }
+        frame(&body)
+    }
+
+    /// A framed `ChunkData` packet for a chunk that stays where it is, chunks can't be cloned into a packet.
+    pub fn encode_chunk_data(chunk: &Chunk) -> Vec<u8> {
+        let mut body = vec![3];
+        body.extend_from_slice(&wire::encode_chunk(chunk));
+        frame(&body)
+    }
+
+    /// Decodes a packet body, the id and fields without the length prefix.
+    pub fn decode(body: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader::new(body);
+        let id = reader.u8().context("empty packet")?;
+        let packet = match id {
+            0 => Packet::Handshake { version: reader.u16()? },
+            1 => Packet::Login { name: read_string(&mut reader)?, view_distance: reader.u8()? },
+            2 => Packet::LoginSuccess { player_id: reader.u32()?, position: read_position(&mut reader)?, view_distance: reader.u8()? },
+            3 => {
+                let chunk = wire::decode_chunk(reader.data).context("decoding chunk data")?;
+                reader.data = &[];
+                Packet::ChunkData { chunk: Box::new(chunk) }
+            }
+            4 => {
+                let position = Point3::new(reader.i32()?, reader.i32()?, reader.i32()?);
+                Packet::BlockChange { position, block: read_block(&mut reader)? }
+            }
+            5 => Packet::PlayerMovement { player_id: reader.u32()?, position: read_position(&mut reader)? },
+            6 => Packet::Disconnect { reason: read_string(&mut reader)? },
+            7 => Packet::UnloadChunk { position: Point2::new(reader.i32()?, reader.i32()?) },
+            8 => Packet::ViewDistance { chunks: reader.u8()? },
+            9 => {
+                let chunk = Point2::new(reader.i32()?, reader.i32()?);
+                let sub_chunk = reader.u8()?;
+                if sub_chunk as usize >= SUB_CHUNKS { bail!("sub chunk {sub_chunk} is above the top of the chunk"); }
+                let changes = (0..reader.u16()?).map(|_| {
+                    let index = reader.u16()?;
+                    if index as usize >= SUB_CHUNK_VOLUME { bail!("block {index} is outside the sub chunk"); }
+                    Ok((index, read_block(&mut reader)?))
+                }).collect::<anyhow::Result<_>>()?;
+                Packet::MultiBlockChange { chunk, sub_chunk, changes }
+            }
+            id => bail!("unknown packet id {id}"),
+        };
+        if !reader.data.is_empty() { bail!("{} bytes of trailing data after packet {id}", reader.data.len()); }
+        Ok(packet)
+    }
+
+    /// Packets telling the other side about blocks changed in `chunks`, each with the chunk it's about.
+    /// Changes are batched into a `MultiBlockChange` per sub chunk, a lone changed block gets a `BlockChange`.
+    /// Repeated positions are sent once and positions outside the loaded chunks not at all.
+    pub fn block_changes(chunks: &ChunkManager, positions: impl IntoIterator<Item = Point3<i32>>) -> Vec<(Point2<i32>, Packet)> {
+        let mut sub_chunks: BTreeMap<(i32, i32, u8), BTreeMap<u16, Block>> = BTreeMap::new();
+        for position in positions {
+            let Some(&block) = chunks.block(position) else { continue; };
+            let (chunk, local) = chunk_local_position(position);
+            let (sub_chunk, index) = sub_chunk_index(local);
+            sub_chunks.entry((chunk.x, chunk.y, sub_chunk)).or_default().insert(index, block);
+        }
+
+        sub_chunks.into_iter().map(|((x, z, sub_chunk), changes)| {
+            let chunk = Point2::new(x, z);
+            let packet = match changes.first_key_value() {
+                Some((&index, &block)) if changes.len() == 1 => Packet::BlockChange { position: sub_chunk_block_position(chunk, sub_chunk, index), block },
+                _ => Packet::MultiBlockChange { chunk, sub_chunk, changes: changes.into_iter().collect() },
+            };
+            (chunk, packet)
+        }).collect()
+    }
+}
+
+fn frame(body: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(4 + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// `u16` byte length, then UTF-8. Longer strings are cut after the last whole character that fits.
+fn write_string(data: &mut Vec<u8>, string: &str) {
+    let mut length = string.len().min(u16::MAX as usize);
+    while !string.is_char_boundary(length) { length -= 1; }
+    let bytes = &string.as_bytes()[..length];
+    data.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+    data.extend_from_slice(bytes);
+}
+
+fn read_string(reader: &mut Reader) -> anyhow::Result<String> {
+    let length = reader.u16()? as usize;
+    Ok(String::from_utf8(reader.take(length)?.to_vec())?)
+}
+
+fn write_position(data: &mut Vec<u8>, position: Point3<f32>) {
+    for coordinate in [position.x, position.y, position.z] {
+        data.extend_from_slice(&coordinate.to_le_bytes());
+    }
+}
+
+/// `material, state`
+fn read_block(reader: &mut Reader) -> anyhow::Result<Block> {
+    let id = reader.u8()?;
+    let material = Material::from_id(id).with_context(|| format!("unknown material {id}"))?;
+    Ok(Block::with_state(material, BlockState::from_bits(reader.u8()?)))
+}
+
+fn read_position(reader: &mut Reader) -> anyhow::Result<Point3<f32>> {
+    let position = Point3::new(reader.f32()?, reader.f32()?, reader.f32()?);
+    if ![position.x, position.y, position.z].iter().all(|coordinate| coordinate.is_finite()) { bail!("position {position:?} isn't finite"); }
+    Ok(position)
+}
+
+/// Packets over a non-blocking TCP stream, buffering partial frames in both directions.
+pub struct Connection {
+    pub stream: TcpStream,
+    pub address: SocketAddr,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    /// set once the other side closed the stream, packets that arrived before are still returned
+    pub closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { address: stream.peer_addr()?, stream, incoming: vec![], outgoing: vec![], closed: false })
+    }
+
+    /// Queues a packet, it's written by the next `flush`.
+    pub fn send(&mut self, packet: &Packet) {
+        self.outgoing.extend_from_slice(&packet.encode());
+    }
+
+    /// Queues a chunk, see [`Packet::encode_chunk_data`].
+    pub fn send_chunk(&mut self, chunk: &Chunk) {
+        self.outgoing.extend_from_slice(&Packet::encode_chunk_data(chunk));
+    }
+
+    /// Writes as much of the queued data as the stream takes without blocking.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        while written < self.outgoing.len() {
+            match self.stream.write(&self.outgoing[written..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(bytes) => written += bytes,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        self.outgoing.drain(..written);
+        Ok(())
+    }
+
+    /// Whether queued data is still waiting to be written.
+    pub fn has_pending_writes(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    /// Whether more than `MAX_QUEUED_BYTES` are waiting to be written, the peer isn't keeping up with what it's sent.
+    pub fn is_backed_up(&self) -> bool {
+        self.outgoing.len() > MAX_QUEUED_BYTES
+    }
+
+    /// Reads what arrived and returns the complete packets, a partial one stays buffered for the next call.
+    pub fn receive(&mut self) -> anyhow::Result<Vec<Packet>> {
+        let mut buffer = [0; 16 * 1024];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(bytes) => self.incoming.extend_from_slice(&buffer[..bytes]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        let mut packets = vec![];
+        let mut start = 0;
+        while self.incoming.len() - start >= 4 {
+            let length = u32::from_le_bytes(self.incoming[start..start + 4].try_into()?) as usize;
+            if length > MAX_PACKET_SIZE { bail!("packet of {length} bytes is larger than the maximum of {MAX_PACKET_SIZE}"); }
+            if self.incoming.len() - start - 4 < length { break; }
+            packets.push(Packet::decode(&self.incoming[start + 4..start + 4 + length])?);
+            start += 4 + length;
+        }
+        self.incoming.drain(..start);
+        Ok(packets)
+    }
+
+    /// Sends a last `Disconnect` and closes the stream, the other side may already be gone so failures are ignored.
+    /// A backed up peer wouldn't read the goodbye either, its queue is dropped instead of waiting for it to drain.
+    pub fn close(&mut self, reason: &str) {
+        if self.is_backed_up() {
+            self.outgoing.clear();
+        } else {
+            self.send(&Packet::Disconnect { reason: reason.to_string() });
+            let _ = self.stream.set_nonblocking(false).and_then(|_| self.stream.set_write_timeout(Some(CLOSE_TIMEOUT))).and_then(|_| self.flush());
+        }
+        let _ = self.stream.shutdown(Shutdown::Both);
+        self.closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use cgmath::Point2;
+
+    use super::*;
+    use crate::{block::Half, generator::GeneratorPreset, sign::Sign};
+
+    fn packets() -> Vec<Packet> {
+        let mut chunk = GeneratorPreset::Flat.generate(Point2::new(-3, 7), 0);
+        chunk.set_block((1, 70, 2), Block::new(Material::Sign));
+        chunk.block_entity_mut::<Sign>((1, 70, 2)).unwrap().set_line(0, "hello");
+        vec![
+            Packet::Handshake { version: PROTOCOL_VERSION },
+            Packet::Login { name: "Ünïcode player".to_string(), view_distance: 12 },
+            Packet::LoginSuccess { player_id: 7, position: Point3::new(1.5, 64.0, -2.25), view_distance: 8 },
+            Packet::ChunkData { chunk: Box::new(chunk) },
+            Packet::BlockChange { position: Point3::new(-40, 255, 12), block: Block::with_state(Material::Door, BlockState::default().with_half(Half::Top)) },
+            Packet::PlayerMovement { player_id: 3, position: Point3::new(-0.5, 80.0, 1e6) },
+            Packet::Disconnect { reason: String::new() },
+            Packet::UnloadChunk { position: Point2::new(i32::MIN, 9) },
+            Packet::ViewDistance { chunks: 255 },
+            Packet::MultiBlockChange { chunk: Point2::new(-1, 4), sub_chunk: 7, changes: vec![(0, Block::new(Material::Air)), (SUB_CHUNK_VOLUME as u16 - 1, Block::new(Material::Glass))] },
+        ]
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        for packet in packets() {
+            let frame = packet.encode();
+            if let Packet::ChunkData { chunk } = &packet { assert_eq!(Packet::encode_chunk_data(chunk), frame); }
+            assert_eq!(u32::from_le_bytes(frame[..4].try_into().unwrap()) as usize, frame.len() - 4);
+            let decoded = Packet::decode(&frame[4..]).unwrap();
+            assert_eq!(decoded.encode(), frame, "{:?}", decoded.id());
+        }
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        assert!(Packet::decode(&[]).is_err());
+        assert!(Packet::decode(&[200]).is_err());
+        let frame = Packet::Handshake { version: 1 }.encode();
+        assert!(Packet::decode(&frame[4..frame.len() - 1]).is_err());
+        assert!(Packet::decode(&[&frame[4..], &[0]].concat()).is_err());
+        assert!(Packet::decode(&[4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255, 0]).is_err());
+        assert!(Packet::decode(&[5, 0, 0, 0, 0, 0, 0, 192, 127, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(Packet::decode(&[9, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0]).is_err());
+        assert!(Packet::decode(&[9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 128, 0, 0]).is_err());
+        assert!(Packet::decode(&[9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn long_strings_are_cut_between_characters() {
+        let reason = format!("{}é", "x".repeat(u16::MAX as usize - 1));
+        let frame = Packet::Disconnect { reason }.encode();
+        let Packet::Disconnect { reason } = Packet::decode(&frame[4..]).unwrap() else { panic!("not a disconnect") };
+        assert_eq!(reason, "x".repeat(u16::MAX as usize - 1));
+    }
+
+    #[test]
+    fn block_changes_are_batched_per_sub_chunk() {
+        let mut chunks = ChunkManager::new();
+        chunks.insert(GeneratorPreset::Flat.generate(Point2::new(0, 0), 0));
+        chunks.insert(GeneratorPreset::Flat.generate(Point2::new(-1, 0), 0));
+        chunks.track_changes = true;
+        chunks.fill(Point3::new(-1, 30, 0), Point3::new(1, 33, 0), Block::new(Material::Glass));
+        let positions = chunks.changed_blocks.iter().copied().chain([Point3::new(0, 30, 0), Point3::new(100, 30, 0)]);
+
+        let packets = Packet::block_changes(&chunks, positions);
+        let summary: Vec<_> = packets.iter().map(|(chunk, packet)| match packet {
+            Packet::BlockChange { position, .. } => (*chunk, position.y as usize / 32, 1),
+            Packet::MultiBlockChange { sub_chunk, changes, .. } => (*chunk, *sub_chunk as usize, changes.len()),
+            packet => panic!("unexpected packet {packet:?}"),
+        }).collect();
+        // the repeated block is sent once, the unloaded one not at all
+        assert_eq!(summary, [(Point2::new(-1, 0), 0, 2), (Point2::new(-1, 0), 1, 2), (Point2::new(0, 0), 0, 4), (Point2::new(0, 0), 1, 4)]);
+        assert!(matches!(&packets[2].1, Packet::MultiBlockChange { changes, .. } if changes.iter().all(|(_, block)| block.material == Material::Glass)));
+
+        let (_, packet) = &Packet::block_changes(&chunks, [Point3::new(5, 64, 5)])[0];
+        assert!(matches!(packet, Packet::BlockChange { position, .. } if *position == Point3::new(5, 64, 5)));
+    }
+
+    #[test]
+    fn frames_split_across_reads_are_reassembled() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut receiver = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        let data: Vec<u8> = packets().iter().flat_map(Packet::encode).collect();
+        let mut received = vec![];
+        for piece in data.chunks(1000) {
+            sender.write_all(piece).unwrap();
+            sender.flush().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            received.extend(receiver.receive().unwrap());
+        }
+        drop(sender);
+        while !receiver.closed {
+            received.extend(receiver.receive().unwrap());
+        }
+        assert_eq!(received.iter().map(Packet::id).collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn oversized_frames_fail_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut receiver = Connection::new(listener.accept().unwrap().0).unwrap();
+        sender.write_all(&(MAX_PACKET_SIZE as u32 + 1).to_le_bytes()).unwrap();
+        let start = std::time::Instant::now();
+        loop {
+            if receiver.receive().is_err() { break; }
+            assert!(start.elapsed().as_secs() < 5);
+        }
+    }
+
+    #[test]
+    fn peers_that_stop_reading_back_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = Connection::new(TcpStream::connect(listener.local_addr().unwrap()).unwrap()).unwrap();
+        let _receiver = listener.accept().unwrap().0;
+        // close to the longest string a packet carries
+        let packet = Packet::Disconnect { reason: "x".repeat(60_000) };
+        while !sender.is_backed_up() {
+            sender.send(&packet);
+            sender.flush().unwrap();
+        }
+        assert!(sender.has_pending_writes());
+
+        // closing doesn't wait for the full queue to drain
+        let start = std::time::Instant::now();
+        sender.close("too slow");
+        assert!(start.elapsed() < CLOSE_TIMEOUT);
+    }
+}
@@ -0,0 +1,257 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{block_vertex::Face, chunk::ChunkManager};
+
+/// How far away blocks can be picked.
+pub const MAX_REACH: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub position: Point3<i32>,
+    /// face the ray entered the block through, `None` if it started inside the block
+    pub face: Option<Face>,
+    /// distance from the ray origin to where it entered the block
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// Normal of the hit face, zero if the ray started inside the block.
+    pub fn normal(&self) -> Vector3<i32> {
+        self.face.map_or(Vector3::new(0, 0, 0), |face| face.normal())
+    }
+
+    /// The block in front of the hit face, where a new block would be placed.
+    pub fn adjacent(&self) -> Point3<i32> {
+        self.position + self.normal()
+    }
+}
+
+impl ChunkManager {
+    /// First targetable block along the ray, blocks in unloaded chunks count as empty.
+    pub fn raycast(&self, origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<RaycastHit> {
+        voxel_raycast(origin, direction, max_distance, |position| self.block(position).is_some_and(|block| block.material.is_targetable()))
+    }
+}
+
+/// Amanatides–Woo traversal of the unit grid, visiting every voxel the ray passes through in order
+/// until `is_hit` returns true or the ray gets longer than `max_distance`.
+pub fn voxel_raycast(origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32, mut is_hit: impl FnMut(Point3<i32>) -> bool) -> Option<RaycastHit> {
+    if direction.magnitude2() == 0.0 || !direction.magnitude2().is_finite() { return None; }
+    let direction = direction.normalize();
+
+    let mut position = Point3::new(origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32);
+    if is_hit(position) {
+        return Some(RaycastHit { position, face: None, distance: 0.0 });
+    }
+
+    let step = Vector3::new(direction.x.signum() as i32, direction.y.signum() as i32, direction.z.signum() as i32);
+    // distance along the ray to the next boundary on each axis, and between boundaries
+    let mut t_max = [0.0f32; 3];
+    let mut t_delta = [0.0f32; 3];
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            t_max[axis] = f32::INFINITY;
+            t_delta[axis] = f32::INFINITY;
+            continue;
+        }
+
+        let boundary = if direction[axis] > 0.0 { origin[axis].floor() + 1.0 } else { origin[axis].floor() };
+        t_max[axis] = (boundary - origin[axis]) / direction[axis];
+        t_delta[axis] = 1.0 / direction[axis].abs();
+    }
+
+    // faces entered when stepping along each axis
+    let entered_faces = [
+        if step.x > 0 { Face::NegativeX } else { Face::PositiveX },
+        if step.y > 0 { Face::NegativeY } else { Face::PositiveY },
+        if step.z > 0 { Face::NegativeZ } else { Face::PositiveZ },
+    ];
+
+    loop {
+        let axis = if t_max[0] < t_max[1] {
+            if t_max[0] < t_max[2] { 0 } else { 2 }
+        } else if t_max[1] < t_max[2] { 1 } else { 2 };
+
+        let distance = t_max[axis];
+        if distance > max_distance { return None; }
+
+        position[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        if is_hit(position) {
+            return Some(RaycastHit { position, face: Some(entered_faces[axis]), distance });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use cgmath::{InnerSpace, Point2, Point3, Vector3};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{block::{Block, Material}, chunk::{Chunk, CHUNK_SIZE}};
+
+    fn cast(solid: &[[i32; 3]], origin: [f32; 3], direction: [f32; 3], max_distance: f32) -> Option<RaycastHit> {
+        let solid: HashSet<Point3<i32>> = solid.iter().map(|&p| p.into()).collect();
+        voxel_raycast(origin.into(), direction.into(), max_distance, |position| solid.contains(&position))
+    }
+
+    fn assert_hit(hit: Option<RaycastHit>, position: [i32; 3], face: Option<Face>, distance: f32) {
+        let hit = hit.expect("expected a hit");
+        assert_eq!(hit.position, position.into());
+        assert_eq!(hit.face, face);
+        assert!((hit.distance - distance).abs() < 1e-4, "distance {} != {}", hit.distance, distance);
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_facing_side() {
+        let origin = [0.5, 0.5, 0.5];
+        assert_hit(cast(&[[3, 0, 0]], origin, [1.0, 0.0, 0.0], 10.0), [3, 0, 0], Some(Face::NegativeX), 2.5);
+        assert_hit(cast(&[[-3, 0, 0]], origin, [-1.0, 0.0, 0.0], 10.0), [-3, 0, 0], Some(Face::PositiveX), 2.5);
+        assert_hit(cast(&[[0, 3, 0]], origin, [0.0, 1.0, 0.0], 10.0), [0, 3, 0], Some(Face::NegativeY), 2.5);
+        assert_hit(cast(&[[0, -3, 0]], origin, [0.0, -1.0, 0.0], 10.0), [0, -3, 0], Some(Face::PositiveY), 2.5);
+        assert_hit(cast(&[[0, 0, 3]], origin, [0.0, 0.0, 1.0], 10.0), [0, 0, 3], Some(Face::NegativeZ), 2.5);
+        assert_hit(cast(&[[0, 0, -3]], origin, [0.0, 0.0, -1.0], 10.0), [0, 0, -3], Some(Face::PositiveZ), 2.5);
+    }
+
+    #[test]
+    fn axis_aligned_rays_miss_blocks_next_to_them() {
+        let solid = [[1, 1, 0], [1, -1, 0], [1, 0, 1], [1, 0, -1]];
+        assert_eq!(cast(&solid, [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 20.0), None);
+    }
+
+    #[test]
+    fn negative_coordinates_round_down() {
+        assert_hit(cast(&[[-1, -1, -1]], [-0.2, -0.7, -0.1], [0.0, 1.0, 0.0], 10.0), [-1, -1, -1], None, 0.0);
+        assert_hit(cast(&[[-5, -2, -8]], [-0.5, -1.5, -7.5], [-1.0, 0.0, 0.0], 10.0), [-5, -2, -8], Some(Face::PositiveX), 3.5);
+        assert_hit(cast(&[[-2, 0, 0]], [1.5, 0.5, 0.5], [-1.0, 0.0, 0.0], 10.0), [-2, 0, 0], Some(Face::PositiveX), 2.5);
+    }
+
+    #[test]
+    fn origin_on_a_boundary() {
+        // starting exactly on the boundary between 0 and 1, the ray starts in voxel 1
+        assert_hit(cast(&[[3, 0, 0]], [1.0, 0.5, 0.5], [1.0, 0.0, 0.0], 10.0), [3, 0, 0], Some(Face::NegativeX), 2.0);
+        assert_hit(cast(&[[-3, 0, 0]], [-1.0, 0.5, 0.5], [-1.0, 0.0, 0.0], 10.0), [-3, 0, 0], Some(Face::PositiveX), 1.0);
+    }
+
+    #[test]
+    fn starting_inside_a_block() {
+        assert_hit(cast(&[[0, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 10.0), [0, 0, 0], None, 0.0);
+    }
+
+    #[test]
+    fn diagonal_rays() {
+        let d = 3.0f32.sqrt();
+        // through the shared corner of the cube, both neighbors along the way are skipped
+        let hit = cast(&[[3, 3, 3]], [0.5, 0.5, 0.5], [1.0, 1.0, 1.0], 10.0).unwrap();
+        assert_eq!(hit.position, Point3::new(3, 3, 3));
+        assert!((hit.distance - 2.5 * d).abs() < 1e-4);
+
+        assert_hit(cast(&[[2, 1, 0]], [0.5, 0.5, 0.5], [1.0, 0.5, 0.0], 10.0), [2, 1, 0], Some(Face::NegativeX), 1.5 * 1.25f32.sqrt());
+        assert_hit(cast(&[[1, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.5, 0.0], 10.0), [1, 0, 0], Some(Face::NegativeX), 0.5 * 1.25f32.sqrt());
+        assert_hit(cast(&[[-2, 0, -4]], [-0.5, 0.5, -0.5], [-1.0, 0.0, -2.0], 10.0), [-2, 0, -4], Some(Face::PositiveZ), 1.25 * 5.0f32.sqrt());
+        assert_hit(cast(&[[-1, -3, 2]], [0.5, 0.5, 0.5], [-0.5, -2.0, 1.0], 10.0), [-1, -3, 2], Some(Face::NegativeZ), 1.5 * 5.25f32.sqrt());
+    }
+
+    #[test]
+    fn direction_does_not_have_to_be_normalized() {
+        assert_hit(cast(&[[4, 0, 0]], [0.5, 0.5, 0.5], [0.01, 0.0, 0.0], 10.0), [4, 0, 0], Some(Face::NegativeX), 3.5);
+    }
+
+    #[test]
+    fn max_distance() {
+        assert_eq!(cast(&[[7, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 6.0), None);
+        assert_hit(cast(&[[6, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 6.0), [6, 0, 0], Some(Face::NegativeX), 5.5);
+        assert_hit(cast(&[[6, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 5.5), [6, 0, 0], Some(Face::NegativeX), 5.5);
+        assert_eq!(cast(&[[6, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 5.49), None);
+    }
+
+    #[test]
+    fn zero_or_invalid_direction_never_hits() {
+        assert_eq!(cast(&[[1, 0, 0]], [0.5, 0.5, 0.5], [0.0, 0.0, 0.0], 10.0), None);
+        assert_eq!(cast(&[[1, 0, 0]], [0.5, 0.5, 0.5], [f32::NAN, 0.0, 0.0], 10.0), None);
+    }
+
+    #[test]
+    fn visits_every_voxel_along_the_ray_in_order() {
+        let mut visited = vec![];
+        voxel_raycast(Point3::new(0.5, 0.5, 0.5), Vector3::new(2.0, 1.0, 0.0), 3.0, |position| { visited.push(position); false });
+        let expected: Vec<Point3<i32>> = [[0, 0, 0], [1, 0, 0], [1, 1, 0], [2, 1, 0], [3, 1, 0]].iter().map(|&p| p.into()).collect();
+        assert_eq!(visited, expected);
+
+        // consecutive voxels always share a face
+        let mut visited: Vec<Point3<i32>> = vec![];
+        voxel_raycast(Point3::new(-3.3, 7.1, 2.9), Vector3::new(-0.3, -0.8, 0.5), 30.0, |position| { visited.push(position); false });
+        for pair in visited.windows(2) {
+            let difference = pair[1] - pair[0];
+            assert_eq!(difference.x.abs() + difference.y.abs() + difference.z.abs(), 1, "{:?} -> {:?}", pair[0], pair[1]);
+        }
+    }
+
+    /// Compares against marching along the ray in tiny steps, on random rays through random blocks.
+    #[test]
+    fn matches_ray_marching() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let step = 1e-3;
+        for _ in 0..300 {
+            let solid: HashSet<Point3<i32>> = (0..40).map(|_| Point3::new(rng.gen_range(-6..6), rng.gen_range(-6..6), rng.gen_range(-6..6))).collect();
+            let origin = Point3::new(rng.gen_range(-3.0..3.0), rng.gen_range(-3.0..3.0), rng.gen_range(-3.0..3.0));
+            let direction = Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0f32..1.0));
+            if direction.magnitude() < 0.1 { continue; }
+            let normalized = direction.normalize();
+            let max_distance = 8.0;
+
+            let hit = voxel_raycast(origin, direction, max_distance, |position| solid.contains(&position));
+            let marched = (0..(max_distance / step) as usize).map(|i| i as f32 * step).find(|&t| {
+                let p = origin + normalized * t;
+                solid.contains(&Point3::new(p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32))
+            });
+
+            match (hit, marched) {
+                (Some(hit), Some(t)) => {
+                    // the traversal may catch an edge the marching stepped over, but never hits later
+                    assert!(hit.distance <= t + 1e-3, "{origin:?} {direction:?}: hit at {} but marched to {t}", hit.distance);
+                    let p = origin + normalized * (hit.distance + 1e-4);
+                    let inside = (0..3).all(|axis| p[axis] >= hit.position[axis] as f32 - 1e-3 && p[axis] <= hit.position[axis] as f32 + 1.0 + 1e-3);
+                    assert!(inside, "{origin:?} {direction:?}: {p:?} is not inside {:?}", hit.position);
+                },
+                (None, Some(t)) => panic!("{origin:?} {direction:?}: missed but marched into a block at {t}"),
+                (Some(hit), None) => assert!(hit.distance > max_distance - 1e-2 || (0..3).any(|axis| {
+                    // grazing an edge or corner between two marching steps
+                    let p = origin + normalized * hit.distance;
+                    (p[axis] - p[axis].round()).abs() < 1e-3
+                }), "{origin:?} {direction:?}: hit {hit:?} the marching never reached"),
+                (None, None) => {},
+            }
+        }
+    }
+
+    #[test]
+    fn raycast_through_chunks_with_negative_positions() {
+        let mut chunks = ChunkManager::new();
+        for x in -1..=0 {
+            for z in -1..=0 {
+                let mut chunk = Chunk::new(Point2::new(x, z));
+                chunk.blocks.fill(Block::new(Material::Air));
+                chunks.insert(chunk);
+            }
+        }
+
+        let size = CHUNK_SIZE as i32;
+        chunks.get_mut(Point2::new(-1, -1)).unwrap()[((size - 3) as usize, 10, (size - 1) as usize)] = Block::new(Material::Dirt);
+        chunks.get_mut(Point2::new(-1, 0)).unwrap()[(0, 10, 0)] = Block::new(Material::Water);
+
+        assert_eq!(chunks.block(Point3::new(-3, 10, -1)).unwrap().material, Material::Dirt);
+        assert_eq!(chunks.block(Point3::new(-size, 10, 0)).unwrap().material, Material::Water);
+        assert!(chunks.block(Point3::new(0, -1, 0)).is_none());
+        assert!(chunks.block(Point3::new(size, 10, 0)).is_none());
+
+        assert_hit(chunks.raycast(Point3::new(5.5, 10.5, -0.5), Vector3::new(-1.0, 0.0, 0.0), 20.0), [-3, 10, -1], Some(Face::PositiveX), 7.5);
+        // water isn't targetable, the ray goes through it and leaves the loaded chunks
+        assert_eq!(chunks.raycast(Point3::new(-size as f32 + 0.5, 10.5, 3.5), Vector3::new(0.0, 0.0, -1.0), 5.0), None);
+        assert_eq!(chunks.raycast(Point3::new(5.5, 10.5, 0.5), Vector3::new(-1.0, 0.0, 0.0), 100.0), None);
+    }
+}
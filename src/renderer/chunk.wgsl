@@ -0,0 +1,175 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec3f,
+    @location(1) @interpolate(flat) face: u32,
+    @location(2) world_position: vec3f,
+    @location(3) uv: vec2f,
+    @location(4) @interpolate(flat) tile: vec2f,
+}
+
+struct VertexInput {
+    @location(0) packed_vertex_data: vec2<u32>,
+    @location(1) chunk_translation: vec3i,
+}
+
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    position: vec4f,
+}
+
+struct InstanceInput {
+    @location(2) matrix_0: vec4f,
+    @location(3) matrix_1: vec4f,
+    @location(4) matrix_2: vec4f,
+    @location(5) matrix_3: vec4f,
+    @location(6) texture_offset: vec2f
+}
+
+struct FogUniform {
+    color: vec4f,
+    start: f32,
+    end: f32,
+}
+
+struct SkyUniform {
+    inverse_view_projection: mat4x4<f32>,
+    zenith_color: vec4f,
+    horizon_color: vec4f,
+    sun_direction: vec4f,
+    ambient: f32,
+}
+
+@group(1) @binding(0) var<uniform> camera: CameraUniform;
+@group(1) @binding(1) var<uniform> fog: FogUniform;
+@group(1) @binding(2) var<uniform> sky: SkyUniform;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // positions and uvs are stored in 1/16ths of a block
+    let position = vec3f(
+        f32(in.packed_vertex_data.x & 1023u),
+        f32(in.packed_vertex_data.x >> 10u & 1023u),
+        f32(in.packed_vertex_data.x >> 20u & 1023u)
+    ) / 16.0;
+
+    let face = in.packed_vertex_data.y & 7u;
+    let uv = vec2f(
+        f32(in.packed_vertex_data.y >> 3u & 31u),
+        f32(in.packed_vertex_data.y >> 8u & 31u)
+    ) / 16.0;
+    let atlas_index = in.packed_vertex_data.y >> 13u & 255u;
+    let tile = vec2f(f32(atlas_index % 16u), f32(atlas_index / 16u));
+
+    let world_position = position + vec3f(in.chunk_translation);
+    out.clip_position = camera.view_projection * vec4f(world_position, 1.0);
+
+    out.position = position;
+    out.world_position = world_position;
+    out.face = face;
+    out.uv = uv;
+    out.tile = tile;
+    return out;
+}
+
+struct ShadowUniform {
+    light_view_projections: array<mat4x4<f32>, 4>,
+    cascade_splits: vec4f,
+    cascade_texel_sizes: vec4f,
+    light_direction: vec4f,
+    cascade_count: u32,
+    enabled: u32,
+    strength: f32,
+}
+
+@group(0) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1) var s_diffuse: sampler;
+
+@group(2) @binding(0) var t_shadow: texture_depth_2d_array;
+@group(2) @binding(1) var s_shadow: sampler_comparison;
+@group(2) @binding(2) var<uniform> shadow: ShadowUniform;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let color = shade(in);
+    // alpha test for cutout blocks
+    if color.a < 0.5 {
+        discard;
+    }
+    return vec4f(color.rgb, 1.0);
+}
+
+@fragment
+fn fs_translucent(in: VertexOutput) -> @location(0) vec4f {
+    return shade(in);
+}
+
+fn shade(in: VertexOutput) -> vec4f {
+    // keep samples on tile edges from reaching into the neighboring tile
+    let tex_coords = (in.tile + clamp(in.uv, vec2f(0.0), vec2f(0.999))) / 16.0;
+    let color = textureSample(t_diffuse, s_diffuse, tex_coords);
+    let visibility = shadow_visibility(in.world_position, face_normal(in.face));
+    let brightness = sky.ambient * mix(1.0 - shadow.strength, 1.0, visibility);
+    return apply_fog(vec4f(color.rgb * brightness, color.a), in.world_position);
+}
+
+fn face_normal(face: u32) -> vec3f {
+    switch face {
+        case 0u: { return vec3f(1.0, 0.0, 0.0); }
+        case 1u: { return vec3f(-1.0, 0.0, 0.0); }
+        case 2u: { return vec3f(0.0, 0.0, 1.0); }
+        case 3u: { return vec3f(0.0, 0.0, -1.0); }
+        case 4u: { return vec3f(0.0, 1.0, 0.0); }
+        default: { return vec3f(0.0, -1.0, 0.0); }
+    }
+}
+
+fn shadow_visibility(world_position: vec3f, normal: vec3f) -> f32 {
+    if shadow.enabled == 0u {
+        return 1.0;
+    }
+
+    // faces turned away from the light are always in shadow
+    if dot(normal, shadow.light_direction.xyz) <= 0.0 {
+        return 0.0;
+    }
+
+    let distance = length(world_position - camera.position.xyz);
+    var cascade = shadow.cascade_count;
+    for (var i = 0u; i < shadow.cascade_count; i++) {
+        if distance < shadow.cascade_splits[i] {
+            cascade = i;
+            break;
+        }
+    }
+    if cascade == shadow.cascade_count {
+        return 1.0;
+    }
+
+    // normal offset to avoid shadow acne
+    let offset_position = world_position + normal * shadow.cascade_texel_sizes[cascade] * 1.5;
+    let light_space = shadow.light_view_projections[cascade] * vec4f(offset_position, 1.0);
+    let ndc = light_space.xyz / light_space.w;
+    let uv = vec2f(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let texel = 1.0 / f32(textureDimensions(t_shadow).x);
+
+    // 3x3 PCF
+    var visibility = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            visibility += textureSampleCompareLevel(t_shadow, s_shadow, uv + vec2f(f32(x), f32(y)) * texel, cascade, ndc.z);
+        }
+    }
+    return visibility / 9.0;
+}
+
+fn apply_fog(color: vec4f, world_position: vec3f) -> vec4f {
+    let distance = length(world_position - camera.position.xyz);
+    let fog_factor = smoothstep(fog.start, fog.end, distance);
+    return vec4f(mix(color.rgb, fog.color.rgb, fog_factor), color.a);
+}
+@fragment
+fn fs_wireframe(in: VertexOutput) -> @location(0) vec4f {
+    return vec4f(1.0, 1.0, 1.0, 1.0);
+}
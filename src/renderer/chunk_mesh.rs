@@ -0,0 +1,178 @@
+use cgmath::{MetricSpace, Point2, Point3};
+use wgpu::util::DeviceExt;
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use crate::{block::Block, block_entity::BlockEntity, block_vertex::{PackedBlockVertex, VertexConstant}, chunk::{Chunk, ChunkManager, CHUNK_SIZE, SUB_CHUNKS, SUB_CHUNK_HEIGHT}, mesher::{mesh_sub_chunk, MeshData, SubChunkMesh}};
+use super::sign_text::SignTextMesh;
+
+/// GPU meshes of the loaded chunks, keyed like the chunks in `ChunkManager`.
+pub struct MeshManager {
+    pub meshes: HashMap<u64, ChunkMeshes>,
+    /// time spent meshing since it was last taken, for the frame timings
+    pub mesh_time: Duration,
+}
+
+impl MeshManager {
+    pub fn new() -> Self {
+        Self { meshes: HashMap::new(), mesh_time: Duration::ZERO }
+    }
+
+    fn entry(&mut self, position: Point2<i32>) -> &mut ChunkMeshes {
+        self.meshes.entry(ChunkManager::key(position)).or_insert_with(|| ChunkMeshes::new(position))
+    }
+
+    /// Remeshes one sub chunk, `chunks` provides the blocks next to the chunk borders.
+    pub fn load_subchunk(&mut self, chunks: &ChunkManager, position: Point2<i32>, index: usize, device: &wgpu::Device) {
+        let Some(chunk) = chunks.get(position) else { return; };
+        let start = Instant::now();
+        let sub_chunk = SubChunk::new(device, chunk, index, &mesh_sub_chunk(chunk, index, chunks));
+        self.mesh_time += start.elapsed();
+        self.entry(position).sub_chunks[index] = Some(sub_chunk);
+    }
+
+    /// Remeshes the sub chunks and sign text that were marked dirty in `chunks` since the last update and drops the meshes of unloaded chunks.
+    /// Taken all at once so chunks loaded together cull the faces on their shared borders.
+    pub fn update(&mut self, chunks: &mut ChunkManager, device: &wgpu::Device) {
+        self.meshes.retain(|key, _| chunks.chunks.contains_key(key));
+        for (position, index) in std::mem::take(&mut chunks.dirty_sub_chunks) {
+            self.load_subchunk(chunks, position, index, device);
+        }
+        for position in std::mem::take(&mut chunks.dirty_block_entities) {
+            self.load_sign_text(chunks, position, device);
+        }
+    }
+
+    /// Rebuilds the text of the signs in a chunk, needed after signs are added, removed or edited.
+    pub fn load_sign_text(&mut self, chunks: &ChunkManager, position: Point2<i32>, device: &wgpu::Device) {
+        let Some(chunk) = chunks.get(position) else { return; };
+        self.entry(position).sign_text = SignTextMesh::new(device, chunk);
+    }
+
+    /// Meshed sub chunks of all loaded chunks.
+    pub fn sub_chunks(&self) -> impl Iterator<Item = &SubChunk> {
+        self.meshes.values().flat_map(|meshes| meshes.sub_chunks.iter().flatten())
+    }
+
+    pub fn memory_usage(&self, chunks: &ChunkManager) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        for chunk in chunks.chunks.values() {
+            usage.chunk_storage += std::mem::size_of::<Chunk>() + std::mem::size_of_val(&*chunk.blocks);
+            usage.chunk_storage += chunk.block_entities.capacity() * std::mem::size_of::<(Point3<u8>, Box<dyn BlockEntity>)>();
+        }
+        for sign_text in self.meshes.values().filter_map(|meshes| meshes.sign_text.as_ref()) {
+            usage.gpu_buffers += sign_text.vertex_buffer.size() + sign_text.index_buffer.size();
+        }
+        for sub_chunk in self.sub_chunks() {
+            usage.chunk_storage += sub_chunk.translucent_faces.capacity() * std::mem::size_of::<Point3<f32>>();
+            usage.gpu_buffers += sub_chunk.mesh.buffer_size() + sub_chunk.translucent_mesh.buffer_size() + sub_chunk.translation_buffer.size();
+        }
+        usage
+    }
+}
+
+/// Bytes used by the loaded chunks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryUsage {
+    /// block arrays, block entity maps and translucent face lists on the CPU
+    pub chunk_storage: usize,
+    /// mesh, translation and sign text buffers
+    pub gpu_buffers: u64,
+}
+
+/// What is drawn of one chunk.
+pub struct ChunkMeshes {
+    pub position: Point2<i32>,
+    pub sub_chunks: [Option<SubChunk>; SUB_CHUNKS],
+    pub sign_text: Option<SignTextMesh>,
+}
+
+impl ChunkMeshes {
+    pub fn new(position: Point2<i32>) -> Self {
+        Self { position, sub_chunks: Default::default(), sign_text: None }
+    }
+}
+
+pub struct SubChunk {
+    pub mesh: ChunkMesh,
+    pub translucent_mesh: ChunkMesh,
+    /// centers of the translucent faces in sub chunk space, in vertex buffer order
+    pub translucent_faces: Vec<Point3<f32>>,
+    /// camera position (in sub chunk space) the translucent faces were last sorted for
+    pub translucent_sort_position: Option<Point3<f32>>,
+    pub translation_buffer: wgpu::Buffer,
+}
+
+impl SubChunk {
+    pub fn new(device: &wgpu::Device, chunk: &Chunk, index: usize, data: &SubChunkMesh) -> Self {
+        let mesh = ChunkMesh::new(device, &data.mesh, wgpu::BufferUsages::empty());
+        let translucent_mesh = ChunkMesh::new(device, &data.translucent_mesh, wgpu::BufferUsages::COPY_DST);
+
+        let translation_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("chunk_translation_buffer"),
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&[VertexConstant { chunk_translation_offset: [chunk.position.x * CHUNK_SIZE as i32, (index * SUB_CHUNK_HEIGHT) as i32, chunk.position.y * CHUNK_SIZE as i32]}])
+        });
+
+        Self { mesh, translucent_mesh, translucent_faces: data.translucent_faces.clone(), translucent_sort_position: None, translation_buffer }
+    }
+
+    /// Reorders the translucent index buffer back to front as seen from `camera_position`.
+    pub fn sort_translucent_faces(&mut self, camera_position: Point3<f32>, queue: &wgpu::Queue) {
+        if self.translucent_faces.is_empty() { return; }
+        if let Some(sort_position) = self.translucent_sort_position {
+            if sort_position.distance2(camera_position) < TRANSLUCENT_RESORT_DISTANCE * TRANSLUCENT_RESORT_DISTANCE { return; }
+        }
+
+        let mut order: Vec<(usize, f32)> = self.translucent_faces.iter().map(|center| center.distance2(camera_position)).enumerate().collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut indices = Vec::with_capacity(order.len() * Block::FACE_INDICES.len());
+        for (face, _) in order {
+            for index in Block::FACE_INDICES {
+                indices.push(index + face as u32 * 4);
+            }
+        }
+
+        queue.write_buffer(&self.translucent_mesh.index_buffer, 0, bytemuck::cast_slice(&indices));
+        self.translucent_sort_position = Some(camera_position);
+    }
+}
+
+const TRANSLUCENT_RESORT_DISTANCE: f32 = 1.0;
+
+pub struct ChunkMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub indices: u32,
+}
+
+impl ChunkMesh {
+    fn new(device: &wgpu::Device, data: &MeshData, index_usage: wgpu::BufferUsages) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("chunk mesh vertex buffer"),
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(data.vertices.as_slice())
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("chunk mesh index buffer"),
+            usage: wgpu::BufferUsages::INDEX | index_usage,
+            contents: bytemuck::cast_slice(data.indices.as_slice())
+        });
+
+        Self { vertex_buffer, index_buffer, indices: data.indices.len() as u32 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices == 0
+    }
+
+    pub fn vertices(&self) -> u64 {
+        self.vertex_buffer.size() / std::mem::size_of::<PackedBlockVertex>() as u64
+    }
+
+    /// Bytes of GPU memory held by the buffers.
+    pub fn buffer_size(&self) -> u64 {
+        self.vertex_buffer.size() + self.index_buffer.size()
+    }
+}
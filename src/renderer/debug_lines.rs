@@ -0,0 +1,134 @@
+use wgpu::{util::DeviceExt, vertex_attr_array};
+
+use crate::chunk::{ChunkManager, CHUNK_HEIGHT, CHUNK_SIZE, SUB_CHUNK_HEIGHT};
+use super::chunk_mesh::MeshManager;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute] = &vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+pub const CHUNK_BORDER_COLOR: [f32; 3] = [1.0, 0.85, 0.0];
+pub const SUB_CHUNK_BOUNDS_COLOR: [f32; 3] = [0.0, 0.9, 0.9];
+
+pub struct DebugLines {
+    pub render_pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugLines {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("debug lines shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("debug_lines.wgsl").into())
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("debug lines pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("debug lines render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[LineVertex::desc()],
+                entry_point: "vs_main"
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL
+                })]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: super::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            multiview: None
+        });
+
+        Self { render_pipeline }
+    }
+
+    pub fn create_vertex_buffer(device: &wgpu::Device, vertices: &[LineVertex]) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("debug lines vertex buffer"),
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(vertices)
+        })
+    }
+}
+
+pub fn push_box(vertices: &mut Vec<LineVertex>, min: [f32; 3], max: [f32; 3], color: [f32; 3]) {
+    let corner = |i: usize| [
+        if i & 1 == 0 { min[0] } else { max[0] },
+        if i & 2 == 0 { min[1] } else { max[1] },
+        if i & 4 == 0 { min[2] } else { max[2] },
+    ];
+
+    for (a, b) in [(0, 1), (2, 3), (4, 5), (6, 7), (0, 2), (1, 3), (4, 6), (5, 7), (0, 4), (1, 5), (2, 6), (3, 7)] {
+        vertices.push(LineVertex { position: corner(a), color });
+        vertices.push(LineVertex { position: corner(b), color });
+    }
+}
+
+pub fn chunk_border_lines(chunks: &ChunkManager, vertices: &mut Vec<LineVertex>) {
+    for chunk in chunks.chunks.values() {
+        let x = (chunk.position.x * CHUNK_SIZE as i32) as f32;
+        let z = (chunk.position.y * CHUNK_SIZE as i32) as f32;
+        let size = CHUNK_SIZE as f32;
+
+        for (cx, cz) in [(x, z), (x + size, z), (x, z + size), (x + size, z + size)] {
+            vertices.push(LineVertex { position: [cx, 0.0, cz], color: CHUNK_BORDER_COLOR });
+            vertices.push(LineVertex { position: [cx, CHUNK_HEIGHT as f32, cz], color: CHUNK_BORDER_COLOR });
+        }
+    }
+}
+
+pub fn sub_chunk_bounds_lines(meshes: &MeshManager, vertices: &mut Vec<LineVertex>) {
+    for chunk in meshes.meshes.values() {
+        let x = (chunk.position.x * CHUNK_SIZE as i32) as f32;
+        let z = (chunk.position.y * CHUNK_SIZE as i32) as f32;
+
+        for (i, sub_chunk) in chunk.sub_chunks.iter().enumerate() {
+            if sub_chunk.is_none() { continue; }
+            let y = (i * SUB_CHUNK_HEIGHT) as f32;
+            push_box(vertices, [x, y, z], [x + CHUNK_SIZE as f32, y + SUB_CHUNK_HEIGHT as f32, z + CHUNK_SIZE as f32], SUB_CHUNK_BOUNDS_COLOR);
+        }
+    }
+}
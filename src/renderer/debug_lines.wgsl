@@ -0,0 +1,29 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) color: vec3f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3f,
+}
+
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    position: vec4f,
+}
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * vec4f(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    return vec4f(in.color, 1.0);
+}
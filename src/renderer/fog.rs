@@ -0,0 +1,18 @@
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct FogUniform {
+    pub color: [f32; 4],
+    pub start: f32,
+    pub end: f32,
+    _padding: [f32; 2],
+}
+
+impl FogUniform {
+    pub fn new(color: [f32; 3], start: f32, end: f32) -> Self {
+        Self { color: [color[0], color[1], color[2], 1.0], start, end, _padding: [0.0; 2] }
+    }
+
+    pub fn disabled(color: [f32; 3]) -> Self {
+        Self::new(color, f32::MAX, f32::MAX)
+    }
+}
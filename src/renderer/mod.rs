@@ -0,0 +1,458 @@
+use cgmath::{EuclideanSpace, MetricSpace, Point3, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{block_vertex::{PackedBlockVertex, VertexConstant}, camera::CameraUniform, chunk::{sub_chunk_origin, CHUNK_SIZE, SUB_CHUNK_HEIGHT}, settings::Settings, time::WorldTime, world::World};
+use self::{chunk_mesh::{MeshManager, SubChunk}, debug_lines::*, fog::FogUniform, selection::{selection_outline_lines, SelectionOutline}, shadow::Shadows, sign_text::SignText, sky::*, texture::Texture, vertex_layout::VertexLayout};
+
+pub mod chunk_mesh;
+pub mod debug_lines;
+pub mod fog;
+pub mod selection;
+pub mod shadow;
+pub mod sign_text;
+pub mod sky;
+pub mod texture;
+pub mod vertex_layout;
+
+/// Draws a [`World`], owning everything on the GPU: pipelines, uniforms and the chunk meshes.
+pub struct Renderer {
+    pub meshes: MeshManager,
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub wireframe_render_pipeline: wgpu::RenderPipeline,
+    pub translucent_render_pipeline: wgpu::RenderPipeline,
+    pub debug_lines: DebugLines,
+    pub camera_uniform: CameraUniform,
+    pub camera_buffer: wgpu::Buffer,
+    pub camera_bind_group: wgpu::BindGroup,
+    pub fog_uniform: FogUniform,
+    pub fog_buffer: wgpu::Buffer,
+    pub sky: Sky,
+    pub sky_uniform: SkyUniform,
+    pub sky_buffer: wgpu::Buffer,
+    pub shadows: Shadows,
+    pub sign_text: SignText,
+    pub selection_outline: SelectionOutline,
+    pub texture_atlas_bind_group: wgpu::BindGroup,
+    pub depth_texture: Texture,
+}
+
+impl Renderer {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, queue: &wgpu::Queue, time: &WorldTime) -> Self {
+        // camera uniform
+        let camera_uniform = CameraUniform::new();
+
+        // camera buffer
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("camera buffer"),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+        });
+
+        // fog uniform
+        let fog_uniform = FogUniform::disabled(time.sky_colors().horizon);
+
+        // fog buffer
+        let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fog buffer"),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            contents: bytemuck::cast_slice(&[fog_uniform]),
+        });
+
+        // sky uniform
+        let sky_uniform = SkyUniform::new();
+
+        // sky buffer
+        let sky_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sky buffer"),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            contents: bytemuck::cast_slice(&[sky_uniform]),
+        });
+
+        // camera bind group layout
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("camera bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStages::FRAGMENT
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStages::FRAGMENT
+                }
+            ]
+        });
+
+        // camera bind group
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("camera bind group"),
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: fog_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sky_buffer.as_entire_binding()
+                }
+            ]
+        });
+        
+        // shader module
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("chunk shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("chunk.wgsl").into())
+        });
+
+        let texture_atlas_bytes = include_bytes!("../textures/texture_atlas.png");
+        let texture_atlas = Texture::from_bytes(&device, &queue, texture_atlas_bytes, "texture").unwrap();
+
+        let texture_atlas_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture bind group"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+
+        let texture_atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("diffuse bind group"),
+            layout: &texture_atlas_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_atlas.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture_atlas.sampler)
+                }
+            ]
+        });
+
+        // shadows
+        let default_settings = Settings::default();
+        let shadows = Shadows::new(device, default_settings.shadow_resolution, default_settings.shadow_cascades);
+
+        // pipeline layout
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("chunk pipeline layout"),
+            bind_group_layouts: &[
+                &texture_atlas_bind_group_layout,
+                &camera_bind_group_layout,
+                &shadows.bind_group_layout,
+            ],
+            push_constant_ranges: &[]
+        });
+
+        // pipelines
+        let render_pipeline = Self::create_chunk_pipeline(device, config, &render_pipeline_layout, &shader, "fs_main", wgpu::PolygonMode::Fill, false);
+        let wireframe_render_pipeline = Self::create_chunk_pipeline(device, config, &render_pipeline_layout, &shader, "fs_wireframe", wgpu::PolygonMode::Line, false);
+        let translucent_render_pipeline = Self::create_chunk_pipeline(device, config, &render_pipeline_layout, &shader, "fs_translucent", wgpu::PolygonMode::Fill, true);
+
+        let debug_lines = DebugLines::new(device, config, &camera_bind_group_layout);
+        let sky = Sky::new(device, config, &camera_bind_group_layout);
+        let sign_text = SignText::new(device, config, &texture_atlas_bind_group_layout, &camera_bind_group_layout);
+        let selection_outline = SelectionOutline::new(device, config, &camera_bind_group_layout);
+
+        let depth_texture = Texture::create_depth_texture(&device, &config, "depth texture");
+
+        Self { camera_bind_group, camera_buffer, camera_uniform, fog_uniform, fog_buffer, sky, sky_uniform, sky_buffer, shadows, sign_text, selection_outline, meshes: MeshManager::new(), render_pipeline, wireframe_render_pipeline, translucent_render_pipeline, debug_lines, texture_atlas_bind_group, depth_texture }
+    }
+
+    fn create_chunk_pipeline(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, fs_entry_point: &str, polygon_mode: wgpu::PolygonMode, translucent: bool) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                buffers: &[
+                    PackedBlockVertex::desc(),
+                    VertexConstant::desc(),
+                ],
+                entry_point: "vs_main"
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: fs_entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(if translucent { wgpu::BlendState::ALPHA_BLENDING } else { wgpu::BlendState::REPLACE }),
+                    write_mask: wgpu::ColorWrites::ALL
+                })]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // translucent surfaces like water are visible from both sides
+                cull_mode: if translucent { None } else { Some(wgpu::Face::Back) },
+                unclipped_depth: false,
+                polygon_mode,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: !translucent,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            multiview: None
+        })
+    }
+
+    /// Recreates the size dependent targets after the surface was reconfigured.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.depth_texture = Texture::create_depth_texture(device, config, "depth texture");
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings, world: &World, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.shadows.resize(device, settings.shadow_resolution, settings.shadow_cascades);
+
+        let fog_color = world.time.sky_colors().horizon;
+        self.fog_uniform = if settings.fog {
+            let end = world.fog_end();
+            FogUniform::new(fog_color, end * settings.fog_start.clamp(0.0, 1.0), end)
+        } else {
+            FogUniform::disabled(fog_color)
+        };
+        queue.write_buffer(&self.fog_buffer, 0, bytemuck::cast_slice(&[self.fog_uniform]));
+    }
+
+    /// Remeshes what changed in the world and uploads the camera, sky and shadow uniforms for the frame.
+    pub fn prepare(&mut self, world: &mut World, settings: &Settings, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.meshes.update(&mut world.loaded_chunks, device);
+
+        self.camera_uniform.update_view_projection(&world.camera);
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+
+        self.sky_uniform.update(&world.camera, &world.time);
+        queue.write_buffer(&self.sky_buffer, 0, bytemuck::cast_slice(&[self.sky_uniform]));
+        self.shadows.update(queue, &world.camera, world.time.sun_direction(), world.fog_end(), settings.shadows);
+
+        self.sort_translucent_faces(world.camera.eye, queue);
+    }
+
+    /// Sorts the translucent faces of every sub chunk back to front for the camera at `eye`.
+    fn sort_translucent_faces(&mut self, eye: Point3<f32>, queue: &wgpu::Queue) {
+        for meshes in self.meshes.meshes.values_mut() {
+            for (i, sub_chunk) in meshes.sub_chunks.iter_mut().enumerate() {
+                let Some(sub_chunk) = sub_chunk else { continue; };
+                let origin = sub_chunk_origin(meshes.position, i);
+                sub_chunk.sort_translucent_faces(eye - origin.to_vec(), queue);
+            }
+        }
+    }
+
+    fn translucent_sub_chunks_back_to_front(&self, eye: Point3<f32>) -> Vec<&SubChunk> {
+        let half_size = Vector3::new(CHUNK_SIZE as f32, SUB_CHUNK_HEIGHT as f32, CHUNK_SIZE as f32) / 2.0;
+        let mut sub_chunks: Vec<(f32, &SubChunk)> = self.meshes.meshes.values()
+            .flat_map(|meshes| meshes.sub_chunks.iter().enumerate().filter_map(move |(i, sub_chunk)| {
+                let sub_chunk = sub_chunk.as_ref().filter(|sub_chunk| !sub_chunk.translucent_mesh.is_empty())?;
+                let center = sub_chunk_origin(meshes.position, i) + half_size;
+                Some((center.distance2(eye), sub_chunk))
+            }))
+            .collect();
+        sub_chunks.sort_by(|a, b| b.0.total_cmp(&a.0));
+        sub_chunks.into_iter().map(|(_, sub_chunk)| sub_chunk).collect()
+    }
+
+    /// Records the shadow and main passes of the world into `encoder`.
+    pub fn draw(&self, world: &World, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) -> DrawStats {
+        let controls = &world.camera_controller.controls;
+        let mut stats = DrawStats::default();
+        let mut debug_line_vertices = vec![];
+        if controls.chunk_borders_toggled {
+            chunk_border_lines(&world.loaded_chunks, &mut debug_line_vertices);
+        }
+        if controls.sub_chunk_bounds_toggled {
+            sub_chunk_bounds_lines(&self.meshes, &mut debug_line_vertices);
+        }
+        let debug_line_buffer = (!debug_line_vertices.is_empty()).then(|| DebugLines::create_vertex_buffer(device, &debug_line_vertices));
+
+        let mut selection_vertices = vec![];
+        if let Some(hit) = world.targeted_block() {
+            if let Some(block) = world.loaded_chunks.block(hit.position) {
+                selection_outline_lines(block, hit.position, &mut selection_vertices);
+            }
+        }
+        let selection_buffer = (!selection_vertices.is_empty()).then(|| DebugLines::create_vertex_buffer(device, &selection_vertices));
+
+        if self.shadows.uniform.enabled != 0 {
+            for (cascade_view, light_bind_group) in self.shadows.map.cascade_views.iter().zip(self.shadows.map.light_bind_groups.iter()) {
+                let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("shadow pass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: cascade_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None
+                });
+                shadow_pass.set_pipeline(&self.shadows.render_pipeline);
+                shadow_pass.set_bind_group(0, light_bind_group, &[]);
+
+                for sub_chunk in self.meshes.sub_chunks() {
+                    if sub_chunk.mesh.is_empty() { continue; }
+                    shadow_pass.set_vertex_buffer(0, sub_chunk.mesh.vertex_buffer.slice(..));
+                    shadow_pass.set_vertex_buffer(1, sub_chunk.translation_buffer.slice(..));
+                    shadow_pass.set_index_buffer(sub_chunk.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    shadow_pass.draw_indexed(0..sub_chunk.mesh.indices, 0, 0..1);
+                    stats.draw_calls += 1;
+                }
+            }
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store
+                    }
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None
+            });
+            render_pass.set_pipeline(&self.sky.render_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+            stats.draw_calls += 1;
+
+            if controls.f1_toggled {
+                render_pass.set_pipeline(&self.wireframe_render_pipeline);
+            } else {
+                render_pass.set_pipeline(&self.render_pipeline);
+            }
+            render_pass.set_bind_group(0, &self.texture_atlas_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.shadows.map.bind_group, &[]);
+
+            for meshes in self.meshes.meshes.values() {
+                for sub_chunk in meshes.sub_chunks.iter() {
+                    if let Some(sub_chunk) = sub_chunk {
+                        if sub_chunk.mesh.is_empty() { continue; }
+                        render_pass.set_vertex_buffer(0, sub_chunk.mesh.vertex_buffer.slice(..));
+                        render_pass.set_vertex_buffer(1, sub_chunk.translation_buffer.slice(..));
+
+                        render_pass.set_index_buffer(sub_chunk.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+                        render_pass.draw_indexed(0..sub_chunk.mesh.indices, 0, 0..1);
+                        stats.draw_calls += 1;
+                        stats.vertices += sub_chunk.mesh.vertices();
+                    }
+                }
+            }
+
+            if !controls.f1_toggled {
+                render_pass.set_pipeline(&self.sign_text.render_pipeline);
+                for sign_text in self.meshes.meshes.values().filter_map(|meshes| meshes.sign_text.as_ref()) {
+                    render_pass.set_vertex_buffer(0, sign_text.vertex_buffer.slice(..));
+                    render_pass.set_index_buffer(sign_text.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..sign_text.indices, 0, 0..1);
+                    stats.draw_calls += 1;
+                }
+
+                render_pass.set_pipeline(&self.translucent_render_pipeline);
+                render_pass.set_bind_group(2, &self.shadows.map.bind_group, &[]);
+                for sub_chunk in self.translucent_sub_chunks_back_to_front(world.camera.eye) {
+                    render_pass.set_vertex_buffer(0, sub_chunk.translucent_mesh.vertex_buffer.slice(..));
+                    render_pass.set_vertex_buffer(1, sub_chunk.translation_buffer.slice(..));
+                    render_pass.set_index_buffer(sub_chunk.translucent_mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..sub_chunk.translucent_mesh.indices, 0, 0..1);
+                    stats.draw_calls += 1;
+                    stats.vertices += sub_chunk.translucent_mesh.vertices();
+                }
+            }
+
+            if let Some(selection_buffer) = &selection_buffer {
+                render_pass.set_pipeline(&self.selection_outline.render_pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, selection_buffer.slice(..));
+                render_pass.draw(0..selection_vertices.len() as u32, 0..1);
+                stats.draw_calls += 1;
+            }
+
+            if let Some(debug_line_buffer) = &debug_line_buffer {
+                render_pass.set_pipeline(&self.debug_lines.render_pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, debug_line_buffer.slice(..));
+                render_pass.draw(0..debug_line_vertices.len() as u32, 0..1);
+                stats.draw_calls += 1;
+            }
+        }
+
+        stats
+    }
+}
+
+/// What a frame drew, shown in the debug overlay.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    /// vertices of the chunk meshes drawn in the main pass
+    pub vertices: u64,
+}
@@ -0,0 +1,89 @@
+use cgmath::{EuclideanSpace, Point3, Vector3};
+
+use crate::{block::Block, block_vertex::SUB_VOXEL_PRECISION};
+use super::debug_lines::{push_box, LineVertex};
+
+pub const SELECTION_OUTLINE_COLOR: [f32; 3] = [0.05, 0.05, 0.05];
+/// how far the outline sticks out of the block, so it isn't inside neighboring faces
+const OUTLINE_INFLATION: f32 = 0.002;
+
+/// Outline around the block under the crosshair.
+pub struct SelectionOutline {
+    pub render_pipeline: wgpu::RenderPipeline,
+}
+
+impl SelectionOutline {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("selection outline shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("selection.wgsl").into())
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("selection outline pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("selection outline render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[LineVertex::desc()],
+                entry_point: "vs_main"
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL
+                })]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: super::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                // line topologies don't support a rasterizer depth bias, the shader applies it instead
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            multiview: None
+        });
+
+        Self { render_pipeline }
+    }
+}
+
+/// Lines around every cuboid of the block's model, or the whole block for models without cuboids like plants.
+pub fn selection_outline_lines(block: &Block, position: Point3<i32>, vertices: &mut Vec<LineVertex>) {
+    let inflation = Vector3::new(OUTLINE_INFLATION, OUTLINE_INFLATION, OUTLINE_INFLATION);
+    let origin = position.cast::<f32>().unwrap();
+    let mut boxes = block.geometry().boxes.iter().map(|(min, max)| (
+        origin + min.cast::<f32>().unwrap().to_vec() / SUB_VOXEL_PRECISION as f32,
+        origin + max.cast::<f32>().unwrap().to_vec() / SUB_VOXEL_PRECISION as f32,
+    )).peekable();
+
+    if boxes.peek().is_none() {
+        push_box(vertices, (origin - inflation).into(), (origin + Vector3::new(1.0, 1.0, 1.0) + inflation).into(), SELECTION_OUTLINE_COLOR);
+    }
+    for (min, max) in boxes {
+        push_box(vertices, (min - inflation).into(), (max + inflation).into(), SELECTION_OUTLINE_COLOR);
+    }
+}
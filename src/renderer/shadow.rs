@@ -0,0 +1,306 @@
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3, Vector4};
+
+use crate::{block_vertex::{PackedBlockVertex, VertexConstant}, camera::Camera, chunk::CHUNK_HEIGHT, time::smoothstep};
+use super::{texture::Texture, vertex_layout::VertexLayout};
+
+pub const MAX_SHADOW_CASCADES: usize = 4;
+/// blend between logarithmic (1.0) and uniform (0.0) cascade splits
+const CASCADE_SPLIT_LAMBDA: f32 = 0.6;
+/// extra depth behind each cascade so blocks outside the view frustum still cast shadows into it
+const SHADOW_CASTER_MARGIN: f32 = CHUNK_HEIGHT as f32;
+const SHADOW_STRENGTH: f32 = 0.45;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct LightUniform {
+    pub view_projection: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct ShadowUniform {
+    pub light_view_projections: [[[f32; 4]; 4]; MAX_SHADOW_CASCADES],
+    pub cascade_splits: [f32; 4],
+    pub cascade_texel_sizes: [f32; 4],
+    pub light_direction: [f32; 4],
+    pub cascade_count: u32,
+    pub enabled: u32,
+    pub strength: f32,
+    _padding: f32,
+}
+
+impl ShadowUniform {
+    pub fn new() -> Self {
+        Self {
+            light_view_projections: [[[0.0; 4]; 4]; MAX_SHADOW_CASCADES],
+            cascade_splits: [0.0; 4],
+            cascade_texel_sizes: [0.0; 4],
+            light_direction: [0.0, 1.0, 0.0, 0.0],
+            cascade_count: 0,
+            enabled: 0,
+            strength: SHADOW_STRENGTH,
+            _padding: 0.0,
+        }
+    }
+}
+
+pub struct ShadowMap {
+    pub resolution: u32,
+    pub cascade_count: u32,
+    pub texture: Texture,
+    pub cascade_views: Vec<wgpu::TextureView>,
+    pub light_buffers: Vec<wgpu::Buffer>,
+    pub light_bind_groups: Vec<wgpu::BindGroup>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+pub struct Shadows {
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub light_bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub uniform: ShadowUniform,
+    pub buffer: wgpu::Buffer,
+    pub map: ShadowMap,
+}
+
+impl Shadows {
+    pub fn new(device: &wgpu::Device, resolution: u32, cascade_count: u32) -> Self {
+        let light_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("light bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStages::VERTEX
+                }
+            ]
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shadow bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStages::FRAGMENT
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                    visibility: wgpu::ShaderStages::FRAGMENT
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStages::FRAGMENT
+                }
+            ]
+        });
+
+        let uniform = ShadowUniform::new();
+        let buffer = wgpu::util::DeviceExt::create_buffer_init(device, &wgpu::util::BufferInitDescriptor {
+            label: Some("shadow buffer"),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            contents: bytemuck::cast_slice(&[uniform]),
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shadow shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into())
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shadow pipeline layout"),
+            bind_group_layouts: &[&light_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shadow render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[
+                    PackedBlockVertex::desc(),
+                    VertexConstant::desc(),
+                ],
+                entry_point: "vs_main"
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            multiview: None
+        });
+
+        let map = Self::create_map(device, &light_bind_group_layout, &bind_group_layout, &buffer, resolution, cascade_count);
+
+        Self { render_pipeline, light_bind_group_layout, bind_group_layout, uniform, buffer, map }
+    }
+
+    fn create_map(device: &wgpu::Device, light_bind_group_layout: &wgpu::BindGroupLayout, bind_group_layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer, resolution: u32, cascade_count: u32) -> ShadowMap {
+        let texture = Texture::create_shadow_map(device, resolution, cascade_count, "shadow map");
+
+        let cascade_views = (0..cascade_count).map(|i| texture.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("shadow cascade view"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: i,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })).collect();
+
+        let light_buffers: Vec<wgpu::Buffer> = (0..cascade_count).map(|_| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light buffer"),
+            size: std::mem::size_of::<LightUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        })).collect();
+
+        let light_bind_groups = light_buffers.iter().map(|light_buffer| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("light bind group"),
+            layout: light_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding()
+                }
+            ]
+        })).collect();
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow bind group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        ShadowMap { resolution, cascade_count, texture, cascade_views, light_buffers, light_bind_groups, bind_group }
+    }
+
+    /// Recreates the shadow map if the resolution or cascade count changed.
+    pub fn resize(&mut self, device: &wgpu::Device, resolution: u32, cascade_count: u32) {
+        let resolution = resolution.clamp(256, device.limits().max_texture_dimension_2d);
+        let cascade_count = cascade_count.clamp(1, MAX_SHADOW_CASCADES as u32);
+        if resolution == self.map.resolution && cascade_count == self.map.cascade_count { return; }
+
+        self.map = Self::create_map(device, &self.light_bind_group_layout, &self.bind_group_layout, &self.buffer, resolution, cascade_count);
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, sun_direction: Vector3<f32>, shadow_distance: f32, enabled: bool) {
+        // the moon casts shadows at night
+        let light_direction = if sun_direction.y >= 0.0 { sun_direction } else { -sun_direction };
+        let cascade_count = self.map.cascade_count as usize;
+
+        self.uniform.enabled = enabled as u32;
+        self.uniform.cascade_count = cascade_count as u32;
+        self.uniform.light_direction = light_direction.extend(0.0).into();
+        self.uniform.strength = SHADOW_STRENGTH * smoothstep(0.0, 0.15, light_direction.y);
+
+        let near = camera.znear;
+        let far = shadow_distance.max(near + 1.0);
+        let mut split_near = near;
+        for i in 0..cascade_count {
+            let t = (i + 1) as f32 / cascade_count as f32;
+            let split_far = CASCADE_SPLIT_LAMBDA * near * (far / near).powf(t) + (1.0 - CASCADE_SPLIT_LAMBDA) * (near + (far - near) * t);
+
+            let (view_projection, texel_size) = cascade_view_projection(camera, split_near, split_far, light_direction, self.map.resolution);
+            self.uniform.light_view_projections[i] = view_projection.into();
+            self.uniform.cascade_splits[i] = split_far;
+            self.uniform.cascade_texel_sizes[i] = texel_size;
+
+            queue.write_buffer(&self.map.light_buffers[i], 0, bytemuck::cast_slice(&[LightUniform { view_projection: view_projection.into() }]));
+            split_near = split_far;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
+
+/// Fits an orthographic light projection around the bounding sphere of a camera frustum slice,
+/// snapped to shadow map texels so the shadows don't shimmer when the camera moves.
+fn cascade_view_projection(camera: &Camera, near: f32, far: f32, light_direction: Vector3<f32>, resolution: u32) -> (Matrix4<f32>, f32) {
+    let forward = camera.direction.normalize();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+    let tan_half_fovy = (camera.fovy / 2.0).to_radians().tan();
+
+    let mut corners = [Point3::new(0.0, 0.0, 0.0); 8];
+    for (i, distance) in [near, far].into_iter().enumerate() {
+        let half_height = distance * tan_half_fovy;
+        let half_width = half_height * camera.aspect;
+        let center = camera.eye + forward * distance;
+        corners[i * 4] = center + up * half_height - right * half_width;
+        corners[i * 4 + 1] = center + up * half_height + right * half_width;
+        corners[i * 4 + 2] = center - up * half_height - right * half_width;
+        corners[i * 4 + 3] = center - up * half_height + right * half_width;
+    }
+
+    let center = corners.iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, corner| sum + corner.to_homogeneous().truncate()) / 8.0;
+    let center = Point3::new(center.x, center.y, center.z);
+    let radius = corners.iter().map(|corner| (corner - center).magnitude()).fold(0.0f32, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let light_up = if light_direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+    let eye = center + light_direction * (radius + SHADOW_CASTER_MARGIN);
+    let view = Matrix4::look_to_rh(eye, -light_direction, light_up);
+    let mut projection = Camera::OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-radius, radius, -radius, radius, 0.0, 2.0 * radius + SHADOW_CASTER_MARGIN);
+
+    let half_resolution = resolution as f32 / 2.0;
+    let origin = projection * view * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let offset_x = (origin.x * half_resolution).round() / half_resolution - origin.x;
+    let offset_y = (origin.y * half_resolution).round() / half_resolution - origin.y;
+    projection.w.x += offset_x;
+    projection.w.y += offset_y;
+
+    (projection * view, 2.0 * radius / resolution as f32)
+}
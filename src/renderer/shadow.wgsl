@@ -0,0 +1,21 @@
+struct VertexInput {
+    @location(0) packed_vertex_data: vec2<u32>,
+    @location(1) chunk_translation: vec3i,
+}
+
+struct LightUniform {
+    view_projection: mat4x4<f32>
+}
+
+@group(0) @binding(0) var<uniform> light: LightUniform;
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4f {
+    let position = vec3f(
+        f32(in.packed_vertex_data.x & 1023u),
+        f32(in.packed_vertex_data.x >> 10u & 1023u),
+        f32(in.packed_vertex_data.x >> 20u & 1023u)
+    ) / 16.0;
+
+    return light.view_projection * vec4f(position + vec3f(in.chunk_translation), 1.0);
+}
@@ -0,0 +1,60 @@
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) tex_coords: vec2f,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2f,
+    @location(1) world_position: vec3f,
+}
+
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    position: vec4f,
+}
+
+struct FogUniform {
+    color: vec4f,
+    start: f32,
+    end: f32,
+}
+
+struct SkyUniform {
+    inverse_view_projection: mat4x4<f32>,
+    zenith_color: vec4f,
+    horizon_color: vec4f,
+    sun_direction: vec4f,
+    ambient: f32,
+}
+
+@group(0) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1) var s_diffuse: sampler;
+
+@group(1) @binding(0) var<uniform> camera: CameraUniform;
+@group(1) @binding(1) var<uniform> fog: FogUniform;
+@group(1) @binding(2) var<uniform> sky: SkyUniform;
+
+const TEXT_COLOR: vec3f = vec3f(0.08, 0.06, 0.04);
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * vec4f(in.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.world_position = in.position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    // glyphs are white with their coverage in the alpha channel
+    let alpha = textureSample(t_diffuse, s_diffuse, in.tex_coords).a;
+    if alpha < 0.1 {
+        discard;
+    }
+
+    let distance = length(in.world_position - camera.position.xyz);
+    let fog_factor = smoothstep(fog.start, fog.end, distance);
+    return vec4f(mix(TEXT_COLOR * sky.ambient, fog.color.rgb, fog_factor), alpha);
+}
@@ -0,0 +1,95 @@
+use wgpu::util::DeviceExt;
+
+use crate::{chunk::Chunk, sign::{sign_text_mesh, TextVertex}};
+use super::vertex_layout::VertexLayout;
+
+/// Text of all signs in a chunk, in world space.
+pub struct SignTextMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub indices: u32,
+}
+
+impl SignTextMesh {
+    /// Uploads the text quads of every sign in the chunk, `None` if there is no text to draw.
+    pub fn new(device: &wgpu::Device, chunk: &Chunk) -> Option<Self> {
+        let (vertices, indices) = sign_text_mesh(chunk);
+        if indices.is_empty() { return None; }
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sign text vertex buffer"),
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&vertices)
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sign text index buffer"),
+            usage: wgpu::BufferUsages::INDEX,
+            contents: bytemuck::cast_slice(&indices)
+        });
+
+        Some(Self { vertex_buffer, index_buffer, indices: indices.len() as u32 })
+    }
+}
+
+pub struct SignText {
+    pub render_pipeline: wgpu::RenderPipeline,
+}
+
+impl SignText {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, texture_atlas_bind_group_layout: &wgpu::BindGroupLayout, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sign text shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("sign.wgsl").into())
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sign text pipeline layout"),
+            bind_group_layouts: &[texture_atlas_bind_group_layout, camera_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sign text render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[TextVertex::desc()],
+                entry_point: "vs_main"
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL
+                })]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: super::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            multiview: None
+        });
+
+        Self { render_pipeline }
+    }
+}
@@ -0,0 +1,100 @@
+use cgmath::{Matrix4, SquareMatrix};
+
+use crate::{camera::Camera, time::WorldTime};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct SkyUniform {
+    pub inverse_view_projection: [[f32; 4]; 4],
+    pub zenith_color: [f32; 4],
+    pub horizon_color: [f32; 4],
+    pub sun_direction: [f32; 4],
+    pub ambient: f32,
+    _padding: [f32; 3],
+}
+
+impl SkyUniform {
+    pub fn new() -> Self {
+        Self {
+            inverse_view_projection: Matrix4::identity().into(),
+            zenith_color: [0.0; 4],
+            horizon_color: [0.0; 4],
+            sun_direction: [0.0, 1.0, 0.0, 0.0],
+            ambient: 1.0,
+            _padding: [0.0; 3],
+        }
+    }
+
+    pub fn update(&mut self, camera: &Camera, time: &WorldTime) {
+        let colors = time.sky_colors();
+        let sun_direction = time.sun_direction();
+
+        self.inverse_view_projection = camera.build_sky_view_projection_matrix().invert().unwrap_or(Matrix4::identity()).into();
+        self.zenith_color = [colors.zenith[0], colors.zenith[1], colors.zenith[2], 1.0];
+        self.horizon_color = [colors.horizon[0], colors.horizon[1], colors.horizon[2], 1.0];
+        self.sun_direction = sun_direction.extend(0.0).into();
+        self.ambient = colors.ambient;
+    }
+}
+
+pub struct Sky {
+    pub render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Sky {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, camera_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sky shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("sky.wgsl").into())
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sky pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sky render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                buffers: &[],
+                entry_point: "vs_main"
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL
+                })]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: super::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            multiview: None
+        });
+
+        Self { render_pipeline }
+    }
+}
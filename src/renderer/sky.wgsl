@@ -0,0 +1,55 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2f,
+}
+
+struct SkyUniform {
+    inverse_view_projection: mat4x4<f32>,
+    zenith_color: vec4f,
+    horizon_color: vec4f,
+    sun_direction: vec4f,
+    ambient: f32,
+}
+
+@group(0) @binding(2) var<uniform> sky: SkyUniform;
+
+const SUN_COLOR: vec3f = vec3f(1.0, 0.95, 0.8);
+const MOON_COLOR: vec3f = vec3f(0.8, 0.82, 0.9);
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // fullscreen triangle
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    out.clip_position = vec4f(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let world = sky.inverse_view_projection * vec4f(in.ndc, 1.0, 1.0);
+    let direction = normalize(world.xyz / world.w);
+    let sun_direction = sky.sun_direction.xyz;
+
+    let height = clamp(direction.y, 0.0, 1.0);
+    var color = mix(sky.horizon_color.rgb, sky.zenith_color.rgb, sqrt(height));
+    if direction.y < 0.0 {
+        color = mix(sky.horizon_color.rgb, sky.horizon_color.rgb * 0.5, clamp(-direction.y * 4.0, 0.0, 1.0));
+    }
+
+    let sun_alignment = dot(direction, sun_direction);
+    color += SUN_COLOR * pow(max(sun_alignment, 0.0), 256.0) * 0.5;
+    if sun_alignment > 0.9995 {
+        color = SUN_COLOR;
+    }
+
+    if dot(direction, -sun_direction) > 0.9996 {
+        color = MOON_COLOR;
+    }
+
+    return vec4f(color, 1.0);
+}
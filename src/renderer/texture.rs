@@ -103,40 +103,40 @@ impl Texture {
         Self { texture, view, sampler }
     }
 
-    pub fn create_3d_material_texture(device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) -> Self {
+    pub fn create_shadow_map(device: &wgpu::Device, resolution: u32, cascades: u32, label: &str) -> Self {
         let size = wgpu::Extent3d {
-            width: crate::chunk::CHUNK_SIZE as u32,
-            height: crate::chunk::CHUNK_SIZE as u32,
-            depth_or_array_layers: crate::chunk::SUB_CHUNK_HEIGHT as u32,
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: cascades,
         };
 
-        let texture = device.create_texture(&Self::desc_3d_material_texture());
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            mip_level_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            sample_count: 1,
+            view_formats: &[]
+        };
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All
-            },
-            &data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(crate::chunk::CHUNK_SIZE as u32),
-                rows_per_image: Some(crate::chunk::SUB_CHUNK_HEIGHT as u32),
-            },
-            size
-        );
+        let texture = device.create_texture(&desc);
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("3d material texture"),
+            label: Some(label),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
             lod_min_clamp: 0.0,
             lod_max_clamp: 100.0,
             ..Default::default()
@@ -144,22 +144,4 @@ impl Texture {
 
         Self { texture, view, sampler }
     }
-
-    pub fn desc_3d_material_texture() -> wgpu::TextureDescriptor<'static> {
-        let size = wgpu::Extent3d {
-            width: crate::chunk::CHUNK_SIZE as u32,
-            height: crate::chunk::CHUNK_SIZE as u32,
-            depth_or_array_layers: crate::chunk::SUB_CHUNK_HEIGHT as u32,
-        };
-        wgpu::TextureDescriptor {
-            label: Some("3d material texture"),
-            size,
-            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-            mip_level_count: 1,
-            dimension: wgpu::TextureDimension::D3,
-            format: wgpu::TextureFormat::R8Uint,
-            sample_count: 1,
-            view_formats: &[]
-        }
-    }
 }
\ No newline at end of file
@@ -0,0 +1,41 @@
+use wgpu::vertex_attr_array;
+
+use crate::{block_vertex::{PackedBlockVertex, VertexConstant}, sign::TextVertex};
+
+/// Vertex buffer layout of the vertex types the library defines without knowing about wgpu.
+pub trait VertexLayout {
+    fn desc() -> wgpu::VertexBufferLayout<'static>;
+}
+
+impl VertexLayout for PackedBlockVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: &[wgpu::VertexAttribute] = &vertex_attr_array![0 => Uint32x2];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PackedBlockVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: ATTRIBUTES,
+        }
+    }
+}
+
+impl VertexLayout for VertexConstant {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: &[wgpu::VertexAttribute] = &vertex_attr_array![1 => Sint32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[i32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: ATTRIBUTES
+        }
+    }
+}
+
+impl VertexLayout for TextVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: &[wgpu::VertexAttribute] = &vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: ATTRIBUTES,
+        }
+    }
+}
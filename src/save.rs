@@ -0,0 +1,219 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use cgmath::{Point2, Point3};
+use serde::{Deserialize, Serialize};
+
+use crate::{block::{Block, BlockState, Material}, block_entity::BlockEntityKind, chunk::{Chunk, CHUNK_HEIGHT, CHUNK_SIZE}, generator::GeneratorPreset};
+
+const CHUNK_MAGIC: &[u8; 4] = b"VXCH";
+const CHUNK_FORMAT_VERSION: u8 = 1;
+
+/// What a world was created with, saved next to its chunks so later runs generate chunks that fit the saved ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldMetadata {
+    pub seed: u64,
+    pub generator: GeneratorPreset,
+}
+
+/// Layout of the metadata file. JSON rather than TOML, which can't hold seeds above `i64::MAX`.
+#[derive(Serialize, Deserialize)]
+struct MetadataFile {
+    seed: u64,
+    generator: String,
+}
+
+/// A world on disk, one file per chunk.
+pub struct WorldSave {
+    pub directory: PathBuf,
+}
+
+impl WorldSave {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    pub fn metadata_path(&self) -> PathBuf {
+        self.directory.join("world.json")
+    }
+
+    /// Reads the seed and generator the world was created with, `None` for a new world.
+    pub fn load_metadata(&self) -> anyhow::Result<Option<WorldMetadata>> {
+        let path = self.metadata_path();
+        if !path.exists() { return Ok(None); }
+
+        let text = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        let file: MetadataFile = serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        let generator = file.generator.parse().map_err(anyhow::Error::msg).with_context(|| format!("in {}", path.display()))?;
+        Ok(Some(WorldMetadata { seed: file.seed, generator }))
+    }
+
+    pub fn save_metadata(&self, metadata: WorldMetadata) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.directory).with_context(|| format!("creating {}", self.directory.display()))?;
+        let path = self.metadata_path();
+        let file = MetadataFile { seed: metadata.seed, generator: metadata.generator.name().to_string() };
+        std::fs::write(&path, serde_json::to_string_pretty(&file)?).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// The seed and generator of the world, written with the given ones, a random seed and the default generator when the world is new.
+    /// A saved world keeps what it was created with, giving anything else is an error.
+    pub fn open(&self, seed: Option<u64>, generator: Option<GeneratorPreset>) -> anyhow::Result<WorldMetadata> {
+        if let Some(metadata) = self.load_metadata()? {
+            if let Some(seed) = seed.filter(|seed| *seed != metadata.seed) {
+                bail!("{} was created with seed {}, not {seed}", self.directory.display(), metadata.seed);
+            }
+            if let Some(generator) = generator.filter(|generator| *generator != metadata.generator) {
+                bail!("{} was created with the {} generator, not {generator}", self.directory.display(), metadata.generator);
+            }
+            return Ok(metadata);
+        }
+        let metadata = WorldMetadata { seed: seed.unwrap_or_else(rand::random), generator: generator.unwrap_or_default() };
+        self.save_metadata(metadata)?;
+        Ok(metadata)
+    }
+
+    pub fn chunk_path(&self, position: Point2<i32>) -> PathBuf {
+        self.directory.join("chunks").join(format!("{}_{}.chunk", position.x, position.y))
+    }
+
+    pub fn save_chunk(&self, chunk: &Chunk) -> anyhow::Result<()> {
+        let path = self.chunk_path(chunk.position);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+        }
+        std::fs::write(&path, encode_chunk(chunk)).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Loads a saved chunk, `None` if it was never saved.
+    pub fn load_chunk(&self, position: Point2<i32>) -> anyhow::Result<Option<Chunk>> {
+        let path = self.chunk_path(position);
+        if !path.exists() { return Ok(None); }
+
+        let data = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+        let chunk = decode_chunk(&data).with_context(|| format!("decoding {}", path.display()))?;
+        if chunk.position != position {
+            bail!("{} contains chunk {:?} instead of {:?}", path.display(), chunk.position, position);
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Loads a saved chunk, generating it if it was never saved or can't be read.
+    pub fn load_or_generate(&self, position: Point2<i32>, generator: GeneratorPreset, seed: u64) -> Chunk {
+        match self.load_chunk(position) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => generator.generate(position, seed),
+            Err(error) => {
+                log::error!("{error:#}");
+                generator.generate(position, seed)
+            }
+        }
+    }
+}
+
+/// `magic, version, position, (material, state) per block, block entities`
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let mut data = Vec::with_capacity(CHUNK_MAGIC.len() + 9 + chunk.blocks.len() * 2);
+    data.extend_from_slice(CHUNK_MAGIC);
+    data.push(CHUNK_FORMAT_VERSION);
+    data.extend_from_slice(&chunk.position.x.to_le_bytes());
+    data.extend_from_slice(&chunk.position.y.to_le_bytes());
+
+    for block in chunk.blocks.iter() {
+        data.push(block.material as u8);
+        data.push(block.state.bits());
+    }
+
+    encode_block_entities(chunk, &mut data);
+    data
+}
+
+/// `count, (x, y, z, kind, length, data) per block entity`, shared with the network encoding.
+pub(crate) fn encode_block_entities(chunk: &Chunk, data: &mut Vec<u8>) {
+    data.extend_from_slice(&(chunk.block_entities.len() as u32).to_le_bytes());
+    for (position, block_entity) in chunk.block_entities.iter() {
+        let mut entity_data = vec![];
+        block_entity.serialize(&mut entity_data);
+
+        data.extend_from_slice(&[position.x, position.y, position.z, block_entity.kind() as u8]);
+        data.extend_from_slice(&(entity_data.len() as u32).to_le_bytes());
+        data.extend_from_slice(&entity_data);
+    }
+}
+
+pub fn decode_chunk(data: &[u8]) -> anyhow::Result<Chunk> {
+    let mut reader = Reader::new(data);
+    if reader.take(CHUNK_MAGIC.len())? != CHUNK_MAGIC { bail!("not a chunk file"); }
+    let version = reader.u8()?;
+    if version != CHUNK_FORMAT_VERSION { bail!("unsupported chunk format version {version}"); }
+
+    let position = Point2::new(reader.i32()?, reader.i32()?);
+    let mut chunk = Chunk::new(position);
+    for block in chunk.blocks.iter_mut() {
+        let id = reader.u8()?;
+        let material = Material::from_id(id).with_context(|| format!("unknown material {id}"))?;
+        *block = Block::with_state(material, BlockState::from_bits(reader.u8()?));
+    }
+
+    decode_block_entities(&mut reader, &mut chunk)?;
+    if !reader.data.is_empty() { bail!("{} bytes of trailing data", reader.data.len()); }
+    Ok(chunk)
+}
+
+/// Reads what `encode_block_entities` wrote into a chunk whose blocks are already decoded.
+pub(crate) fn decode_block_entities(reader: &mut Reader, chunk: &mut Chunk) -> anyhow::Result<()> {
+    for _ in 0..reader.u32()? {
+        let position = Point3::new(reader.u8()?, reader.u8()?, reader.u8()?);
+        let id = reader.u8()?;
+        let length = reader.u32()? as usize;
+        let entity_data = reader.take(length)?;
+
+        if position.x as usize >= CHUNK_SIZE || position.z as usize >= CHUNK_SIZE || position.y as usize >= CHUNK_HEIGHT {
+            bail!("block entity at {position:?} is outside the chunk");
+        }
+        let kind = BlockEntityKind::from_id(id).with_context(|| format!("unknown block entity kind {id}"))?;
+        let material = chunk[(position.x as usize, position.y as usize, position.z as usize)].material;
+        if material.block_entity() != Some(kind) {
+            bail!("{kind:?} block entity at {position:?} doesn't belong to {material:?}");
+        }
+        chunk.block_entities.insert(position, kind.deserialize(entity_data)?);
+    }
+    Ok(())
+}
+
+/// Reads little endian values from the front of a byte slice, failing instead of panicking when it runs out.
+pub(crate) struct Reader<'a> {
+    pub data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn take(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        if self.data.len() < length { bail!("unexpected end of data"); }
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+}
@@ -0,0 +1,445 @@
+use std::{collections::HashSet, io::{BufRead, ErrorKind}, net::{SocketAddr, TcpListener, ToSocketAddrs}, sync::mpsc, time::{Duration, Instant}};
+
+use anyhow::{bail, Context};
+use cgmath::{MetricSpace, Point2, Point3, Vector3};
+
+use crate::{block::{Block, Material}, chunk::{chunk_distance, chunk_local_position, chunks_around, Chunk, ChunkManager, CHUNK_HEIGHT, CHUNK_SIZE, UNLOAD_MARGIN}, generator::{spawn_chunks, GeneratorPreset}, player::PLAYER_EYE_HEIGHT, protocol::{Connection, Packet, PROTOCOL_VERSION}, raycast::MAX_REACH, save::WorldSave, time::WorldTime, timestep::{FixedTimestep, TICK_RATE}, wire::sub_chunk_block_position};
+
+/// Address the server listens on when none is given.
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:27500";
+
+/// Time between automatic saves.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Most chunks sent around a player in every direction, clients asking for more get this many.
+pub const MAX_VIEW_DISTANCE: i32 = 10;
+
+/// Fastest a player moves in blocks per second, flying diagonally up at the highest fly speed of 200.
+const MAX_PLAYER_SPEED: f32 = 350.0;
+
+/// Seconds of movement at `MAX_PLAYER_SPEED` a client can save up, movement packets can arrive in bursts.
+const MOVEMENT_BURST: f32 = 1.0;
+
+/// Farthest a block a client changes can be from its player's eyes, raycasts reach `MAX_REACH` to any point of a block.
+const MAX_EDIT_DISTANCE: f32 = MAX_REACH + 1.0;
+
+/// Longest player name in characters.
+pub const MAX_NAME_LENGTH: usize = 16;
+
+/// Chunks sent to a client per update at most, nearest first, so generating them doesn't stall the tick.
+const CHUNKS_PER_UPDATE: usize = 4;
+
+/// The name without surrounding whitespace, an error when it is empty, longer than `MAX_NAME_LENGTH` or has control characters.
+pub fn check_name(name: &str) -> anyhow::Result<&str> {
+    let name = name.trim();
+    if name.is_empty() { bail!("the name is empty"); }
+    if name.chars().count() > MAX_NAME_LENGTH { bail!("the name is longer than {MAX_NAME_LENGTH} characters"); }
+    if name.chars().any(char::is_control) { bail!("the name has control characters"); }
+    Ok(name)
+}
+
+/// How far a client got into the login.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientState {
+    Handshake,
+    Login,
+    Playing,
+}
+
+/// A connection accepted by the server.
+pub struct Client {
+    /// unique for the lifetime of the server, shown by `list`, used by `kick` and sent to the client as its player id
+    pub id: u32,
+    pub connection: Connection,
+    pub state: ClientState,
+    /// empty until the login
+    pub name: String,
+    /// feet position of the player
+    pub position: Point3<f32>,
+    /// chunks the client asked for around its player, at most `MAX_VIEW_DISTANCE`
+    pub view_distance: i32,
+    /// chunks the client has, block changes are only sent for these, they stay sent up to `UNLOAD_MARGIN` past the view distance
+    pub sent_chunks: HashSet<Point2<i32>>,
+    /// blocks the player may still move, refilled every tick up to `MOVEMENT_BURST` seconds at `MAX_PLAYER_SPEED`
+    pub movement_budget: f32,
+}
+
+impl Client {
+    /// The chunk the player stands in.
+    pub fn chunk(&self) -> Point2<i32> {
+        chunk_local_position(self.position.map(|coordinate| coordinate.floor() as i32)).0
+    }
+}
+
+/// The world without a window or GPU, ticked at the same rate as the game and shared with the clients connected over TCP.
+pub struct Server {
+    pub save: WorldSave,
+    pub loaded_chunks: ChunkManager,
+    pub time: WorldTime,
+    pub seed: u64,
+    pub generator: GeneratorPreset,
+    pub timestep: FixedTimestep,
+    pub running: bool,
+    /// feet position players log in at
+    pub spawn: Point3<f32>,
+    pub listener: TcpListener,
+    pub clients: Vec<Client>,
+    next_client_id: u32,
+}
+
+impl Server {
+    /// Loads or generates the spawn chunks and starts listening on `address`, port 0 picks a free one.
+    pub fn new(save: WorldSave, seed: u64, generator: GeneratorPreset, address: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(address).context("binding the server address")?;
+        listener.set_nonblocking(true).context("making the listener non-blocking")?;
+
+        let mut server = Self { save, loaded_chunks: ChunkManager::new(), time: WorldTime::new(WorldTime::SUNRISE + 0.05, 600.0), seed, generator, timestep: FixedTimestep::new(TICK_RATE), running: true, spawn: Point3::new(0.0, 0.0, 0.0), listener, clients: vec![], next_client_id: 1 };
+        for position in spawn_chunks() {
+            server.load_chunk(position);
+        }
+        server.spawn = spawn_position(&server.loaded_chunks);
+        // started after the spawn chunks are in, what clients change is broadcast
+        server.loaded_chunks.track_changes = true;
+        Ok(server)
+    }
+
+    /// The address clients connect to, with the port picked when bound to port 0.
+    pub fn local_address(&self) -> SocketAddr {
+        self.listener.local_addr().expect("the listener is bound")
+    }
+
+    /// A loaded chunk, loading it from the save or generating it first if needed.
+    pub fn load_chunk(&mut self, position: Point2<i32>) -> &Chunk {
+        if self.loaded_chunks.get(position).is_none() {
+            self.loaded_chunks.insert(self.save.load_or_generate(position, self.generator, self.seed));
+        }
+        self.loaded_chunks.get(position).expect("the chunk was just loaded")
+    }
+
+    /// Advances the simulation by one fixed tick.
+    pub fn update(&mut self, dt: f32) {
+        self.time.advance(dt);
+        for chunk in self.loaded_chunks.chunks.values_mut() {
+            chunk.tick_block_entities(dt);
+        }
+        for client in self.clients.iter_mut() {
+            client.movement_budget = (client.movement_budget + MAX_PLAYER_SPEED * dt).min(MAX_PLAYER_SPEED * MOVEMENT_BURST);
+        }
+    }
+
+    /// Takes every pending connection, returning how many were accepted.
+    pub fn accept_clients(&mut self) -> usize {
+        let mut accepted = 0;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    let connection = match Connection::new(stream) {
+                        Ok(connection) => connection,
+                        Err(error) => {
+                            log::error!("dropping connection from {address}: {error}");
+                            continue;
+                        }
+                    };
+                    let id = self.next_client_id;
+                    self.next_client_id += 1;
+                    log::info!("client {id} connected from {address}");
+                    self.clients.push(Client { id, connection, state: ClientState::Handshake, name: String::new(), position: self.spawn, view_distance: 0, sent_chunks: HashSet::new(), movement_budget: MAX_PLAYER_SPEED * MOVEMENT_BURST });
+                    accepted += 1;
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    log::error!("accepting a connection failed: {error}");
+                    break;
+                }
+            }
+        }
+        accepted
+    }
+
+    /// Handles what the clients sent, then sends them the blocks that changed and the chunks around them and unloads the chunks nobody is near.
+    /// Clients that closed their connection, failed, broke the protocol or don't keep up with what they're sent are dropped.
+    pub fn update_clients(&mut self) {
+        let mut failed = vec![];
+        for index in 0..self.clients.len() {
+            let result = self.clients[index].connection.receive().and_then(|packets| {
+                packets.into_iter().try_for_each(|packet| self.handle_packet(index, packet))
+            });
+            let client = &self.clients[index];
+            match result {
+                Err(error) => failed.push((client.id, format!("{error:#}"))),
+                Ok(()) if client.connection.closed => failed.push((client.id, "connection closed".to_string())),
+                Ok(()) => {}
+            }
+        }
+        for (id, reason) in failed {
+            self.disconnect(id, &reason);
+        }
+
+        self.broadcast_block_changes();
+        // nothing meshes on the server, the dirty sets would only grow
+        self.loaded_chunks.dirty_sub_chunks.clear();
+        self.loaded_chunks.dirty_block_entities.clear();
+        self.send_chunks();
+        self.unload_chunks();
+
+        let mut failed = vec![];
+        for client in self.clients.iter_mut() {
+            match client.connection.flush() {
+                Err(error) => failed.push((client.id, error.to_string())),
+                Ok(()) if client.connection.is_backed_up() => failed.push((client.id, "too far behind on the data sent to it".to_string())),
+                Ok(()) => {}
+            }
+        }
+        for (id, reason) in failed {
+            self.disconnect(id, &reason);
+        }
+    }
+
+    fn handle_packet(&mut self, index: usize, packet: Packet) -> anyhow::Result<()> {
+        let client = &mut self.clients[index];
+        match (client.state, packet) {
+            (ClientState::Handshake, Packet::Handshake { version }) => {
+                client.connection.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+                if version != PROTOCOL_VERSION { bail!("protocol version {version} isn't supported, the server speaks {PROTOCOL_VERSION}"); }
+                client.state = ClientState::Login;
+            }
+            (ClientState::Login, Packet::Login { name, view_distance }) => {
+                client.name = check_name(&name)?.to_string();
+                client.state = ClientState::Playing;
+                client.position = self.spawn;
+                client.view_distance = i32::from(view_distance).min(MAX_VIEW_DISTANCE);
+                client.connection.send(&Packet::LoginSuccess { player_id: client.id, position: self.spawn, view_distance: MAX_VIEW_DISTANCE as u8 });
+                log::info!("{} logged in as client {}", client.name, client.id);
+            }
+            (ClientState::Playing, Packet::BlockChange { position, block }) => self.change_blocks(index, [(position, block)]),
+            (ClientState::Playing, Packet::MultiBlockChange { chunk, sub_chunk, changes }) => {
+                self.change_blocks(index, changes.into_iter().map(|(index, block)| (sub_chunk_block_position(chunk, sub_chunk, index), block)));
+            }
+            (ClientState::Playing, Packet::PlayerMovement { position, .. }) => {
+                let distance = client.position.distance(position);
+                if distance > client.movement_budget {
+                    // the client is moved back to where the server has it
+                    client.connection.send(&Packet::PlayerMovement { player_id: client.id, position: client.position });
+                    return Ok(());
+                }
+                client.movement_budget -= distance;
+                client.position = position;
+                let packet = Packet::PlayerMovement { player_id: client.id, position };
+                let id = client.id;
+                for other in self.clients.iter_mut().filter(|other| other.id != id && other.state == ClientState::Playing) {
+                    other.connection.send(&packet);
+                }
+            }
+            (ClientState::Playing, Packet::ViewDistance { chunks }) => client.view_distance = i32::from(chunks).min(MAX_VIEW_DISTANCE),
+            (_, Packet::Disconnect { reason }) => bail!("left: {reason}"),
+            (state, packet) => bail!("unexpected packet {} while in {state:?}", packet.id()),
+        }
+        Ok(())
+    }
+
+    /// Sets the blocks a client changed. Blocks out of its player's reach are refused and the client is sent what the server has there instead.
+    /// Changes in chunks the client wasn't sent are dropped, it has nothing there to correct.
+    fn change_blocks(&mut self, index: usize, changes: impl IntoIterator<Item = (Point3<i32>, Block)>) {
+        let eye = self.clients[index].position + Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+        let mut refused = vec![];
+        for (position, block) in changes {
+            if !self.clients[index].sent_chunks.contains(&chunk_local_position(position).0) { continue; }
+            if position.map(|coordinate| coordinate as f32 + 0.5).distance(eye) <= MAX_EDIT_DISTANCE {
+                self.loaded_chunks.set_block(position, block);
+            } else {
+                refused.push(position);
+            }
+        }
+        for (_, packet) in Packet::block_changes(&self.loaded_chunks, refused) {
+            self.clients[index].connection.send(&packet);
+        }
+    }
+
+    /// Sends the blocks set since the last update to the clients that have their chunk, batched per sub chunk.
+    fn broadcast_block_changes(&mut self) {
+        let changed = std::mem::take(&mut self.loaded_chunks.changed_blocks);
+        for (chunk_position, packet) in Packet::block_changes(&self.loaded_chunks, changed) {
+            for client in self.clients.iter_mut().filter(|client| client.sent_chunks.contains(&chunk_position)) {
+                client.connection.send(&packet);
+            }
+        }
+    }
+
+    /// Sends every playing client the nearest chunks within the view distance it doesn't have yet, generating them if needed.
+    fn send_chunks(&mut self) {
+        for index in 0..self.clients.len() {
+            let client = &self.clients[index];
+            if client.state != ClientState::Playing { continue; }
+
+            let missing: Vec<Point2<i32>> = chunks_around(client.chunk(), client.view_distance).into_iter()
+                .filter(|position| !client.sent_chunks.contains(position))
+                .take(CHUNKS_PER_UPDATE)
+                .collect();
+
+            for position in missing {
+                self.load_chunk(position);
+                let client = &mut self.clients[index];
+                client.connection.send_chunk(self.loaded_chunks.get(position).expect("the chunk was just loaded"));
+                client.sent_chunks.insert(position);
+            }
+        }
+    }
+
+    /// Tells clients to drop the chunks they moved away from, then saves and unloads the chunks no playing client is near.
+    /// The spawn chunks stay loaded, and so does a chunk that fails to save.
+    fn unload_chunks(&mut self) {
+        for client in self.clients.iter_mut() {
+            let (center, keep) = (client.chunk(), client.view_distance + UNLOAD_MARGIN);
+            let far: Vec<Point2<i32>> = client.sent_chunks.iter().copied().filter(|position| chunk_distance(*position, center) > keep).collect();
+            for position in far {
+                client.sent_chunks.remove(&position);
+                client.connection.send(&Packet::UnloadChunk { position });
+            }
+        }
+
+        let views: Vec<(Point2<i32>, i32)> = self.clients.iter().filter(|client| client.state == ClientState::Playing).map(|client| (client.chunk(), client.view_distance + UNLOAD_MARGIN)).collect();
+        let spawn: HashSet<Point2<i32>> = spawn_chunks().collect();
+        let unused: Vec<Point2<i32>> = self.loaded_chunks.chunks.values()
+            .map(|chunk| chunk.position)
+            .filter(|position| !spawn.contains(position) && views.iter().all(|(center, keep)| chunk_distance(*position, *center) > *keep))
+            .collect();
+        for position in unused {
+            match self.save.save_chunk(self.loaded_chunks.get(position).expect("the chunk is loaded")) {
+                Ok(()) => { self.loaded_chunks.remove(position); }
+                Err(error) => log::error!("keeping chunk {position:?} loaded: {error:#}"),
+            }
+        }
+    }
+
+    /// Sends a client the reason and closes its connection, returning false if there's no client with that id.
+    pub fn disconnect(&mut self, id: u32, reason: &str) -> bool {
+        let Some(index) = self.clients.iter().position(|client| client.id == id) else { return false; };
+        let mut client = self.clients.remove(index);
+        client.connection.close(reason);
+        log::info!("client {id} ({}) disconnected: {reason}", client.connection.address);
+        true
+    }
+
+    /// Writes every loaded chunk, returning how many were written. A failed chunk doesn't stop the others from being saved.
+    pub fn save(&self) -> anyhow::Result<usize> {
+        let mut saved = 0;
+        let mut failure = None;
+        for chunk in self.loaded_chunks.chunks.values() {
+            match self.save.save_chunk(chunk) {
+                Ok(()) => saved += 1,
+                Err(error) => {
+                    log::error!("{error:#}");
+                    failure = Some(error);
+                }
+            }
+        }
+
+        match failure {
+            Some(error) => Err(error.context(format!("{} chunks weren't saved", self.loaded_chunks.chunks.len() - saved))),
+            None => Ok(saved),
+        }
+    }
+
+    /// Runs a line typed into the server console, returning the reply.
+    pub fn run_command(&mut self, line: &str) -> String {
+        let mut words = line.split_whitespace();
+        match (words.next().unwrap_or(""), words.next()) {
+            ("", _) => String::new(),
+            ("help", None) => "commands: help, list, kick <id>, save, seed, stop".to_string(),
+            ("seed", None) => format!("seed: {} ({})", self.seed, self.generator),
+            ("list", None) if self.clients.is_empty() => format!("no clients connected, listening on {}", self.local_address()),
+            ("list", None) => {
+                let clients: Vec<String> = self.clients.iter().map(|client| match client.state {
+                    ClientState::Playing => format!("{} {} ({})", client.id, client.name, client.connection.address),
+                    _ => format!("{} ({}, logging in)", client.id, client.connection.address),
+                }).collect();
+                format!("{} clients: {}", clients.len(), clients.join(", "))
+            }
+            ("kick", Some(id)) => match id.parse() {
+                Ok(id) if self.disconnect(id, "kicked") => format!("kicked client {id}"),
+                Ok(id) => format!("no client {id}, see list"),
+                Err(_) => format!("{id:?} isn't a client id"),
+            },
+            ("save", None) => match self.save() {
+                Ok(chunks) => format!("saved {chunks} chunks to {}", self.save.directory.display()),
+                Err(error) => format!("{error:#}"),
+            },
+            ("stop", None) => {
+                self.running = false;
+                "stopping".to_string()
+            }
+            _ => format!("unknown command {:?}, try help", line.trim()),
+        }
+    }
+
+    /// Ticks the world and serves clients until `stop` is run or `commands` closes, then disconnects everyone and saves.
+    /// What each command answers is passed to `reply`.
+    pub fn run(&mut self, commands: mpsc::Receiver<String>, mut reply: impl FnMut(String)) -> anyhow::Result<()> {
+        let mut last_tick = Instant::now();
+        let mut last_save = Instant::now();
+        while self.running {
+            loop {
+                match commands.try_recv() {
+                    Ok(line) => {
+                        let answer = self.run_command(&line);
+                        if !answer.is_empty() { reply(answer); }
+                    }
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        self.running = false;
+                        break;
+                    }
+                }
+            }
+
+            self.accept_clients();
+            self.update_clients();
+
+            let now = Instant::now();
+            for _ in 0..self.timestep.advance(now - last_tick) {
+                self.update(self.timestep.dt());
+            }
+            last_tick = now;
+
+            if last_save.elapsed() >= AUTOSAVE_INTERVAL {
+                last_save = Instant::now();
+                if let Err(error) = self.save() { log::error!("autosave failed: {error:#}"); }
+            }
+            std::thread::sleep(Duration::from_secs_f32(self.timestep.dt()).saturating_sub(last_tick.elapsed()));
+        }
+
+        let ids: Vec<u32> = self.clients.iter().map(|client| client.id).collect();
+        for id in ids {
+            self.disconnect(id, "server stopped");
+        }
+        let chunks = self.save()?;
+        log::info!("saved {chunks} chunks to {}", self.save.directory.display());
+        Ok(())
+    }
+}
+
+/// Feet position on top of the highest block in the middle of the spawn chunks.
+fn spawn_position(chunks: &ChunkManager) -> Point3<f32> {
+    let (x, z) = (CHUNK_SIZE as i32, CHUNK_SIZE as i32);
+    let top = (0..CHUNK_HEIGHT as i32).rev().find(|y| chunks.block(Point3::new(x, *y, z)).is_some_and(|block| block.material != Material::Air));
+    Point3::new(x as f32 + 0.5, top.map_or(0.0, |top| top as f32 + 1.0), z as f32 + 0.5)
+}
+
+/// Lines typed into stdin, read on their own thread so the tick loop doesn't block on them. The channel closes with stdin.
+pub fn stdin_commands() -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() { break; }
+        }
+    });
+    receiver
+}
+
+/// Hosts the world in `save` on `address` with the stdin admin console, until `stop` is typed or stdin closes.
+/// Console commands are answered through `reply`.
+pub fn serve(save: WorldSave, seed: u64, generator: GeneratorPreset, address: impl ToSocketAddrs, reply: impl FnMut(String)) -> anyhow::Result<()> {
+    let mut server = Server::new(save, seed, generator, address)?;
+    log::info!("serving {} with seed {seed} ({generator}) on {}, type `help` for commands", server.save.directory.display(), server.local_address());
+    server.run(stdin_commands(), reply)
+}
@@ -0,0 +1,200 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::shadow::MAX_SHADOW_CASCADES;
+
+/// Settings file, next to the world save.
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    /// vsync, waits for the display
+    Fifo,
+    /// no tearing but doesn't wait, falls back to `Fifo`
+    Mailbox,
+    /// no vsync, can tear, falls back to `Fifo`
+    Immediate,
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 3] = [PresentMode::Fifo, PresentMode::Mailbox, PresentMode::Immediate];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PresentMode::Fifo => "vsync",
+            PresentMode::Mailbox => "mailbox",
+            PresentMode::Immediate => "immediate (no vsync)",
+        }
+    }
+
+    /// The wgpu present mode if the surface supports it, `Fifo` which every surface supports otherwise.
+    pub fn supported(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let mode = match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        };
+        if supported.contains(&mode) { mode } else { wgpu::PresentMode::Fifo }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub fovy: f32,
+    pub zfar: f32,
+    /// chunks loaded and drawn around the player, a server may send fewer
+    pub render_distance: u32,
+    pub fog: bool,
+    /// fraction of the fog end distance at which fog starts
+    pub fog_start: f32,
+    pub shadows: bool,
+    pub shadow_cascades: u32,
+    pub shadow_resolution: u32,
+    pub present_mode: PresentMode,
+    pub fullscreen: bool,
+    /// inner size of the window when it isn't fullscreen
+    pub window_width: u32,
+    pub window_height: u32,
+    /// outer position of the window, left to the platform when unset
+    pub window_position: Option<(i32, i32)>,
+    /// degrees turned per pixel of mouse movement
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    /// spectator flying speed in blocks per second
+    pub fly_speed: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fovy: 45.0,
+            zfar: 160.0,
+            render_distance: 4,
+            fog: true,
+            fog_start: 0.7,
+            shadows: true,
+            shadow_cascades: 3,
+            shadow_resolution: 2048,
+            present_mode: PresentMode::Fifo,
+            fullscreen: false,
+            window_width: 1000,
+            window_height: 1000,
+            window_position: None,
+            mouse_sensitivity: 1.0 / 8.0,
+            invert_y: false,
+            fly_speed: 5.0,
+        }
+    }
+}
+
+impl Settings {
+    pub const FOVY_RANGE: std::ops::RangeInclusive<f32> = 30.0..=110.0;
+    pub const RENDER_DISTANCE_RANGE: std::ops::RangeInclusive<u32> = 2..=32;
+    pub const MOUSE_SENSITIVITY_RANGE: std::ops::RangeInclusive<f32> = 0.01..=1.0;
+    pub const FLY_SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.5..=200.0;
+
+    /// Reads the settings file, the defaults if there is none. Out of range values are fixed with a warning.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() { return Ok(Self::default()); }
+
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let mut settings: Settings = toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        for problem in settings.validate() {
+            log::warn!("{}: {problem}", path.display());
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, toml::to_string(self)?).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Clamps every value into its valid range, returning what had to be changed.
+    pub fn validate(&mut self) -> Vec<String> {
+        let defaults = Settings::default();
+        let mut problems = vec![];
+
+        clamp_float(&mut self.fovy, Self::FOVY_RANGE, "fovy", &mut problems);
+        clamp_float(&mut self.zfar, 16.0..=4096.0, "zfar", &mut problems);
+        clamp(&mut self.render_distance, Self::RENDER_DISTANCE_RANGE, "render_distance", &mut problems);
+        clamp_float(&mut self.fog_start, 0.0..=1.0, "fog_start", &mut problems);
+        clamp(&mut self.shadow_cascades, 1..=MAX_SHADOW_CASCADES as u32, "shadow_cascades", &mut problems);
+        clamp(&mut self.shadow_resolution, 256..=8192, "shadow_resolution", &mut problems);
+        if !self.shadow_resolution.is_power_of_two() {
+            problems.push(format!("shadow_resolution {} isn't a power of two", self.shadow_resolution));
+            self.shadow_resolution = self.shadow_resolution.next_power_of_two();
+        }
+        clamp(&mut self.window_width, 320..=16384, "window_width", &mut problems);
+        clamp(&mut self.window_height, 240..=16384, "window_height", &mut problems);
+        clamp_float(&mut self.mouse_sensitivity, Self::MOUSE_SENSITIVITY_RANGE, "mouse_sensitivity", &mut problems);
+        clamp_float(&mut self.fly_speed, Self::FLY_SPEED_RANGE, "fly_speed", &mut problems);
+
+        for (value, default, name) in [(&mut self.fovy, defaults.fovy, "fovy"), (&mut self.zfar, defaults.zfar, "zfar"), (&mut self.fog_start, defaults.fog_start, "fog_start"), (&mut self.mouse_sensitivity, defaults.mouse_sensitivity, "mouse_sensitivity"), (&mut self.fly_speed, defaults.fly_speed, "fly_speed")] {
+            if !value.is_finite() {
+                problems.push(format!("{name} isn't a number, using {default}"));
+                *value = default;
+            }
+        }
+        problems
+    }
+}
+
+fn clamp<T: PartialOrd + Copy + std::fmt::Display>(value: &mut T, range: std::ops::RangeInclusive<T>, name: &str, problems: &mut Vec<String>) {
+    let clamped = if *value < *range.start() { *range.start() } else if *value > *range.end() { *range.end() } else { *value };
+    if clamped != *value {
+        problems.push(format!("{name} {value} is outside {}..={}, using {clamped}", range.start(), range.end()));
+        *value = clamped;
+    }
+}
+
+/// Like `clamp` but leaves NaN alone, `validate` replaces it afterwards.
+fn clamp_float(value: &mut f32, range: std::ops::RangeInclusive<f32>, name: &str, problems: &mut Vec<String>) {
+    if value.is_finite() { clamp(value, range, name, problems); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Settings::default().validate().is_empty());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let settings = Settings { present_mode: PresentMode::Immediate, window_position: Some((-20, 40)), invert_y: true, ..Settings::default() };
+        let text = toml::to_string(&settings).unwrap();
+        assert_eq!(toml::from_str::<Settings>(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_values_use_defaults() {
+        let settings: Settings = toml::from_str("fovy = 70.0\npresent_mode = \"mailbox\"\n").unwrap();
+        assert_eq!(settings.fovy, 70.0);
+        assert_eq!(settings.present_mode, PresentMode::Mailbox);
+        assert_eq!(settings.render_distance, Settings::default().render_distance);
+    }
+
+    #[test]
+    fn validation_fixes_out_of_range_values() {
+        let mut settings: Settings = toml::from_str("fovy = 500.0\nrender_distance = 0\nshadow_resolution = 3000\nmouse_sensitivity = nan\n").unwrap();
+        let problems = settings.validate();
+        assert_eq!(problems.len(), 4);
+        assert_eq!(settings.fovy, 110.0);
+        assert_eq!(settings.render_distance, 2);
+        assert_eq!(settings.shadow_resolution, 4096);
+        assert_eq!(settings.mouse_sensitivity, Settings::default().mouse_sensitivity);
+    }
+
+    #[test]
+    fn unknown_present_modes_are_errors() {
+        assert!(toml::from_str::<Settings>("present_mode = \"triple\"\n").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{bindings_menu::BindingsMenu, settings::{PresentMode, Settings, SETTINGS_PATH}};
+
+/// Window with the graphics and gameplay settings, changes apply immediately and are saved when it's closed.
+pub struct SettingsMenu {
+    pub open: bool,
+    /// set when a value was changed since the settings were last written
+    pub unsaved: bool,
+    /// set when the window was moved or resized, the geometry is only written on exit so dragging doesn't write every frame
+    pub window_changed: bool,
+    /// result of the last save, shown at the bottom
+    pub status: Option<String>,
+}
+
+impl SettingsMenu {
+    pub fn new() -> Self {
+        Self { open: false, unsaved: false, window_changed: false, status: None }
+    }
+
+    /// Returns true when a setting was changed.
+    pub fn ui(&mut self, ctx: &egui::Context, settings: &mut Settings, bindings_menu: &mut BindingsMenu) -> bool {
+        let before = settings.clone();
+
+        egui::Window::new("Settings")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.heading("Video");
+            egui::Grid::new("video settings").num_columns(2).show(ui, |ui| {
+                ui.label("field of view");
+                ui.add(egui::Slider::new(&mut settings.fovy, Settings::FOVY_RANGE).suffix("°"));
+                ui.end_row();
+
+                ui.label("render distance");
+                ui.add(egui::Slider::new(&mut settings.render_distance, Settings::RENDER_DISTANCE_RANGE).suffix(" chunks"));
+                ui.end_row();
+
+                ui.label("present mode");
+                egui::ComboBox::from_id_source("present mode").selected_text(settings.present_mode.label()).show_ui(ui, |ui| {
+                    for mode in PresentMode::ALL {
+                        ui.selectable_value(&mut settings.present_mode, mode, mode.label());
+                    }
+                });
+                ui.end_row();
+
+                ui.label("fullscreen");
+                ui.checkbox(&mut settings.fullscreen, "");
+                ui.end_row();
+
+                ui.label("fog");
+                ui.checkbox(&mut settings.fog, "");
+                ui.end_row();
+
+                ui.label("shadows");
+                ui.checkbox(&mut settings.shadows, "");
+                ui.end_row();
+            });
+
+            ui.separator();
+            ui.heading("Controls");
+            egui::Grid::new("control settings").num_columns(2).show(ui, |ui| {
+                ui.label("mouse sensitivity");
+                ui.add(egui::Slider::new(&mut settings.mouse_sensitivity, Settings::MOUSE_SENSITIVITY_RANGE).logarithmic(true));
+                ui.end_row();
+
+                ui.label("invert mouse y");
+                ui.checkbox(&mut settings.invert_y, "");
+                ui.end_row();
+
+                ui.label("flying speed");
+                ui.add(egui::Slider::new(&mut settings.fly_speed, Settings::FLY_SPEED_RANGE).logarithmic(true).suffix(" blocks/s"));
+                ui.end_row();
+            });
+            if ui.button("Key bindings...").clicked() { bindings_menu.open = true; }
+
+            ui.separator();
+            if let Some(status) = &self.status { ui.label(status); }
+            ui.horizontal(|ui| {
+                if ui.button("Reset to defaults").clicked() {
+                    // the window isn't moved or resized by a reset
+                    *settings = Settings { window_width: settings.window_width, window_height: settings.window_height, window_position: settings.window_position, ..Settings::default() };
+                }
+                if ui.button("Done").clicked() { self.open = false; }
+            });
+        });
+
+        let changed = *settings != before;
+        if changed { self.unsaved = true; }
+        changed
+    }
+
+    /// Writes the settings if they were changed, keeping the result for the status line.
+    pub fn save(&mut self, settings: &Settings) {
+        if !self.unsaved { return; }
+        self.unsaved = false;
+        self.window_changed = false;
+        self.status = Some(match settings.save(SETTINGS_PATH) {
+            Ok(()) => format!("saved to {SETTINGS_PATH}"),
+            Err(error) => {
+                log::error!("{error:#}");
+                format!("{error:#}")
+            }
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{bail, Context};
+use cgmath::{EuclideanSpace, Point3, Vector3};
+
+use crate::{block::Block, block_entity::{BlockEntity, BlockEntityKind}, chunk::{Chunk, CHUNK_SIZE}};
+
+pub const SIGN_LINES: usize = 4;
+pub const MAX_LINE_LENGTH: usize = 15;
+
+/// Atlas tile of the first printable ascii character, followed by the other 95.
+const GLYPH_ATLAS_OFFSET: u32 = 160;
+const GLYPH_SIZE: f32 = 0.1;
+/// horizontal distance between characters, the glyphs are monospaced and narrower than their tile
+const GLYPH_ADVANCE: f32 = GLYPH_SIZE * 0.6;
+/// distance of the text from the front of the board, to avoid z-fighting
+const TEXT_OFFSET: f32 = 0.005;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sign {
+    pub lines: [String; SIGN_LINES],
+}
+
+impl Sign {
+    /// Sets a line of text, keeping only printable ascii characters and cutting it to `MAX_LINE_LENGTH`.
+    pub fn set_line(&mut self, line: usize, text: &str) {
+        self.lines[line] = text.chars().filter(|c| c.is_ascii() && !c.is_ascii_control()).take(MAX_LINE_LENGTH).collect();
+    }
+
+    pub fn deserialize(data: &[u8]) -> anyhow::Result<Self> {
+        let mut sign = Sign::default();
+        let mut data = data;
+        for line in 0..SIGN_LINES {
+            let (&length, rest) = data.split_first().context("sign data ended early")?;
+            if rest.len() < length as usize { bail!("sign line {line} is longer than the remaining data"); }
+            let (text, rest) = rest.split_at(length as usize);
+            sign.set_line(line, std::str::from_utf8(text)?);
+            data = rest;
+        }
+
+        Ok(sign)
+    }
+}
+
+impl BlockEntity for Sign {
+    fn kind(&self) -> BlockEntityKind {
+        BlockEntityKind::Sign
+    }
+
+    fn serialize(&self, data: &mut Vec<u8>) {
+        for line in self.lines.iter() {
+            data.push(line.len() as u8);
+            data.extend_from_slice(line.as_bytes());
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct TextVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+
+/// Text quads of every sign in the chunk, in world space.
+pub fn sign_text_mesh(chunk: &Chunk) -> (Vec<TextVertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for (position, entity) in chunk.block_entities.iter() {
+        let Some(sign) = entity.as_any().downcast_ref::<Sign>() else { continue; };
+        let block = chunk[(position.x as usize, position.y as usize, position.z as usize)];
+        let origin = Point3::new(chunk.position.x * CHUNK_SIZE as i32 + position.x as i32, position.y as i32, chunk.position.y * CHUNK_SIZE as i32 + position.z as i32).cast::<f32>().unwrap();
+        push_sign_text(&mut vertices, &mut indices, sign, &block, origin);
+    }
+    (vertices, indices)
+}
+
+/// Lays the lines out centered on the front of the board, which faces `NegativeZ` in the unrotated sign model.
+fn push_sign_text(vertices: &mut Vec<TextVertex>, indices: &mut Vec<u32>, sign: &Sign, block: &Block, origin: Point3<f32>) {
+    let rotation = block.model_rotation();
+    let front = 14.0 / 16.0 - TEXT_OFFSET;
+    let top = 0.5 + GLYPH_SIZE * SIGN_LINES as f32 / 2.0;
+
+    for (row, line) in sign.lines.iter().enumerate() {
+        let width = line.len() as f32 * GLYPH_ADVANCE;
+        let y = top - (row + 1) as f32 * GLYPH_SIZE;
+        for (column, character) in line.bytes().enumerate() {
+            if character == b' ' { continue; }
+
+            // the text reads towards negative x when looking at the front
+            let x = 0.5 + width / 2.0 - column as f32 * GLYPH_ADVANCE + (GLYPH_SIZE - GLYPH_ADVANCE) / 2.0;
+            let tile = GLYPH_ATLAS_OFFSET + (character - b' ') as u32;
+            let tile = Vector3::new((tile % 16) as f32, (tile / 16) as f32, 0.0) / 16.0;
+
+            let index_offset = vertices.len() as u32;
+            for (corner_x, corner_y, u, v) in [(0.0, 0.0, 0.0, 1.0), (-1.0, 0.0, 1.0, 1.0), (0.0, 1.0, 0.0, 0.0), (-1.0, 1.0, 1.0, 0.0)] {
+                let position = Point3::new(x + corner_x * GLYPH_SIZE, y + corner_y * GLYPH_SIZE, front);
+                let position = origin + rotation.rotate_point(position).to_vec();
+                vertices.push(TextVertex { position: position.into(), tex_coords: [tile.x + u / 16.0, tile.y + v / 16.0] });
+            }
+            indices.extend(Block::FACE_INDICES.iter().map(|index| index + index_offset));
+        }
+    }
+}
@@ -1,22 +1,46 @@
 use std::borrow::Borrow;
 
 use cgmath::{Point3, Rotation3};
-use winit::{raw_window_handle::HasWindowHandle, window::Window};
-use crate::{block::Material, chunk::{Chunk, World}, egui_renderer::EguiRenderer};
+use winit::{event::WindowEvent, keyboard::KeyCode, raw_window_handle::HasWindowHandle, window::Window};
+use voxel_game::client::ServerConnection;
+use crate::{bindings_menu::BindingsMenu, block::Material, chunk::{chunk_local_position, CHUNK_HEIGHT}, cli::{Cli, Mode}, generator::spawn_chunks, commands::register_builtin_commands, console::{Console, LineKind}, egui_renderer::EguiRenderer, gui::Gui, input::{Action, Binding, InputMap, CONTROLS_PATH}, pause_menu::{PauseMenu, PauseMenuAction}, player::{HOTBAR, PLAYER_HEIGHT}, renderer::Renderer, save::WorldSave, settings::{PresentMode, Settings}, settings_menu::SettingsMenu, timestep::{FixedTimestep, FrameTimings, TICK_RATE}, world::World};
 use wgpu::util::DeviceExt;
 
 pub struct State {
     pub surface: wgpu::Surface,
+    /// name, backend and driver of the GPU, recorded in benchmark reports
+    pub adapter_info: wgpu::AdapterInfo,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub window: Window,
-    pub world: World
+    pub world: World,
+    pub renderer: Renderer,
+    pub settings: Settings,
+    pub save: WorldSave,
+    /// the server the world comes from, `None` when playing a local world
+    pub server: Option<ServerConnection>,
+    /// benchmarks generate every chunk fresh and save none
+    pub benchmark: bool,
+    pub timestep: FixedTimestep,
+    pub timings: FrameTimings,
+    pub egui_renderer: EguiRenderer,
+    pub gui: Gui,
+    pub console: Console,
+    pub input_map: InputMap,
+    pub bindings_menu: BindingsMenu,
+    pub pause_menu: PauseMenu,
+    pub settings_menu: SettingsMenu,
+    /// present modes the surface supports, the setting falls back to `Fifo` when it isn't one of them
+    present_modes: Vec<wgpu::PresentMode>,
+    cursor_captured: bool,
+    /// set once the world was saved from the pause menu, the event loop quits
+    pub exit_requested: bool,
 }
 
 impl State {
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, settings: Settings, cli: &Cli, server: Option<ServerConnection>) -> Self {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -33,6 +57,8 @@ impl State {
             compatible_surface: Some(&surface)
         }).await.unwrap();
 
+        let adapter_info = adapter.get_info();
+
         let limits = wgpu::Limits {
             max_buffer_size: 1024 * 1024 * 1024,
             ..Default::default()
@@ -52,7 +78,7 @@ impl State {
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: settings.present_mode.supported(&surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![]
         };
@@ -60,36 +86,351 @@ impl State {
         surface.configure(&device, &config);
 
 
-        let mut blocks = 0;
-        let mut world = World::new(&device, &config, &queue);
-        for x in 0..2 {
-            for z in 0..2 {
-                let mut chunk = Chunk::randomized((x, z).into());
-                blocks += chunk.blocks.iter().filter(|p| p.material != Material::Air).count();
-                for i in 0..8 {
-                    chunk.load_subchunk(i, &device, &queue);
+        let save = WorldSave::new(cli.world_directory());
+        let mut world = World::new(config.width, config.height);
+        if let Some(server) = &server {
+            world.max_view_distance = server.max_view_distance;
+        }
+        world.apply_settings(&settings);
+        let mut renderer = Renderer::new(&device, &config, &queue, &world.time);
+        renderer.apply_settings(&settings, &world, &device, &queue);
+        world.seed = cli.seed();
+        world.generator = cli.generator.unwrap_or_default();
+        if let Some(server) = &server {
+            world.player.teleport(server.spawn);
+        }
+        // on a server the chunks stream in once the game runs
+        for position in spawn_chunks().filter(|_| server.is_none()) {
+            // benchmarks always run on a freshly generated world
+            let chunk = match cli.mode() {
+                Mode::Benchmark => world.generator.generate(position, world.seed),
+                _ => save.load_or_generate(position, world.generator, world.seed),
+            };
+            world.loaded_chunks.insert(chunk);
+        }
+        // meshed once all chunks are loaded so faces on chunk borders can be culled
+        renderer.meshes.update(&mut world.loaded_chunks, &device);
+        // spawning inside terrain would leave the player stuck, stand on top of it or fly out of it instead
+        if world.player.is_obstructed(&world.loaded_chunks) {
+            let (x, z) = (world.player.position.x.floor() as i32, world.player.position.z.floor() as i32);
+            let top = (0..CHUNK_HEIGHT as i32).rev().find(|y| world.loaded_chunks.block(Point3::new(x, *y, z)).is_some_and(|block| block.material != Material::Air));
+            match top {
+                Some(top) if top as f32 + 1.0 + PLAYER_HEIGHT < CHUNK_HEIGHT as f32 => {
+                    world.player.position.y = top as f32 + 1.0;
+                    world.player.previous_position = world.player.position;
+                }
+                _ => world.camera_controller.controls.spectator_toggled = true,
+            }
+        }
+        let egui_renderer = EguiRenderer::new(&window, &config, &device);
+        let mut console = Console::new();
+        register_builtin_commands(&mut console.registry);
+        let input_map = InputMap::load(CONTROLS_PATH).unwrap_or_else(|error| {
+            log::error!("{error:#}, using the default controls");
+            InputMap::default()
+        });
+        Self { window, adapter_info, device, config, queue, size, surface, world, renderer, settings, save, server, benchmark: cli.mode() == Mode::Benchmark, timestep: FixedTimestep::new(TICK_RATE), timings: FrameTimings::default(), egui_renderer, gui: Gui::new(), console, input_map, bindings_menu: BindingsMenu::new(), pause_menu: PauseMenu::new(), settings_menu: SettingsMenu::new(), present_modes: surface_caps.present_modes, cursor_captured: false, exit_requested: false }
+    }
+
+    /// Writes every loaded chunk to the world save, returning how many were written.
+    /// A failed chunk doesn't stop the others from being saved.
+    pub fn save(&self) -> anyhow::Result<usize> {
+        if self.server.is_some() { anyhow::bail!("the world is saved by the server"); }
+        let mut saved = 0;
+        let mut failure = None;
+        for chunk in self.world.loaded_chunks.chunks.values() {
+            match self.save.save_chunk(chunk) {
+                Ok(()) => saved += 1,
+                Err(error) => {
+                    log::error!("{error:#}");
+                    failure = Some(error);
                 }
+            }
+        }
+
+        match failure {
+            Some(error) => Err(error.context(format!("{} chunks weren't saved", self.world.loaded_chunks.chunks.len() - saved))),
+            None => Ok(saved),
+        }
+    }
+
+    /// Saves the world, any unsaved settings and the window geometry, for when the game is closed.
+    /// On a server nothing is saved locally, the game leaves it instead.
+    pub fn save_all(&mut self) -> anyhow::Result<usize> {
+        if self.settings_menu.window_changed { self.settings_menu.unsaved = true; }
+        self.settings_menu.save(&self.settings);
+        if let Some(server) = &mut self.server {
+            server.disconnect("quit");
+            return Ok(0);
+        }
+        self.save()
+    }
+
+    /// Reconfigures the surface and the size dependent targets, remembering the size for the next start when windowed.
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 { return; }
+        self.size = size;
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+        self.renderer.resize(&self.device, &self.config);
+        self.world.camera.aspect = size.width as f32 / size.height as f32;
+
+        if !self.settings.fullscreen && self.window.fullscreen().is_none() {
+            self.settings.window_width = size.width;
+            self.settings.window_height = size.height;
+            self.settings_menu.window_changed = true;
+        }
+    }
+
+    /// Applies the settings that belong to the window and surface rather than the world, only touching what changed.
+    pub fn apply_window_settings(&mut self) {
+        let present_mode = self.settings.present_mode.supported(&self.present_modes);
+        if present_mode == wgpu::PresentMode::Fifo && self.settings.present_mode != PresentMode::Fifo {
+            self.settings_menu.status = Some(format!("{} isn't supported here, using vsync", self.settings.present_mode.label()));
+        }
+        if present_mode != self.config.present_mode {
+            self.config.present_mode = present_mode;
+            self.surface.configure(&self.device, &self.config);
+        }
+
+        if self.settings.fullscreen != self.window.fullscreen().is_some() {
+            self.window.set_fullscreen(self.settings.fullscreen.then_some(winit::window::Fullscreen::Borderless(None)));
+        }
+    }
+
+    /// Hides and locks the cursor for looking around, or gives it back for the ui.
+    /// Platforms that can't lock it in place confine it to the window instead.
+    pub fn capture_cursor(&self, captured: bool) {
+        use winit::window::CursorGrabMode;
+        let result = if captured {
+            self.window.set_cursor_grab(CursorGrabMode::Locked).or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            self.window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(error) = result {
+            log::warn!("couldn't change the cursor grab: {error}");
+        }
+        self.window.set_cursor_visible(!captured);
+    }
 
-                world.loaded_chunks.chunks.insert(x as u64 | (z as u64) << 32, chunk);
+    /// Flies the camera in spectator mode, otherwise steps the player physics and puts the camera at its eyes.
+    fn move_player(&mut self, dt: f32) {
+        let world = &mut self.world;
+        world.player.previous_position = world.player.position;
+        world.camera.eye = world.player.eye();
+        world.player.spectator = world.camera_controller.controls.spectator_toggled;
+        if world.player.spectator {
+            world.camera_controller.update_camera(&mut world.camera, dt);
+            world.player.follow_camera(world.camera.eye);
+        } else {
+            // the player waits in place for the chunk it stands in to load instead of falling through the missing terrain
+            let position = world.player.position.map(|coordinate| coordinate.floor() as i32);
+            if world.loaded_chunks.get(chunk_local_position(position).0).is_some() {
+                world.player.update(world.camera_controller.controls.movement(), world.camera.direction, &world.loaded_chunks, dt);
             }
+            world.camera.eye = world.player.eye();
+        }
+    }
+
+    fn interact(&mut self) {
+        let controls = &mut self.world.camera_controller.controls;
+        let (break_block, place_block) = (std::mem::take(&mut controls.break_block), std::mem::take(&mut controls.place_block));
+        let material = HOTBAR[controls.selected_slot];
+
+        if break_block {
+            self.world.break_targeted_block();
+        }
+        if place_block {
+            self.world.place_targeted_block(material);
         }
-        dbg!(blocks);
-        Self { window, device, config, queue, size, surface, world }
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.world.render(&self.device, &self.queue, &self.surface, &self.window);
+        let output = self.surface.get_current_texture()?;
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("render encoder")
+        });
 
+        let draw_stats = self.renderer.draw(&self.world, &self.device, &mut encoder, &view);
+        // updated while hidden too so the frame time graph is full when it's opened
+        self.gui.update(&self.world, &self.renderer.meshes, self.timings, draw_stats);
+
+        let show_debug_overlay = self.world.camera_controller.controls.debug_overlay_toggled;
+        if show_debug_overlay || self.ui_open() {
+            let screen_descriptor = egui_wgpu::renderer::ScreenDescriptor {
+                size_in_pixels: [self.config.width, self.config.height],
+                pixels_per_point: self.window.scale_factor() as f32
+            };
+            let (gui, console, bindings_menu, input_map, pause_menu, settings_menu, settings) = (&self.gui, &mut self.console, &mut self.bindings_menu, &mut self.input_map, &mut self.pause_menu, &mut self.settings_menu, &mut self.settings);
+            let mut bindings_changed = false;
+            let mut settings_changed = false;
+            let mut pause_menu_action = None;
+            self.egui_renderer.draw(&self.device, &self.queue, &mut encoder, &self.window, &view, screen_descriptor, |ctx| {
+                if show_debug_overlay { gui.ui(ctx); }
+                if console.open { console.ui(ctx); }
+                if bindings_menu.open {
+                    bindings_changed = bindings_menu.ui(ctx, input_map);
+                } else if settings_menu.open {
+                    settings_changed = settings_menu.ui(ctx, settings, bindings_menu);
+                } else if pause_menu.open {
+                    pause_menu_action = pause_menu.ui(ctx);
+                }
+            });
+            if bindings_changed { self.save_input_map(); }
+            if settings_changed { self.apply_window_settings(); }
+            if let Some(action) = pause_menu_action { self.pause_menu_action(action); }
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.window.pre_present_notify();
+        output.present();
+
+        if !self.settings_menu.open { self.settings_menu.save(&self.settings); }
+        self.run_pending_commands();
+        self.update_cursor_capture();
         Ok(())
     }
 
-    pub fn input(&mut self, event: &winit::event::WindowEvent) {
-        self.world.camera_controller.process_events(event);
+    /// Hands a window event to the open menus, or turns it into actions through the input map.
+    pub fn input(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Focused(false) => {
+                self.pause_menu.open = true;
+                self.update_cursor_capture();
+            }
+            WindowEvent::Resized(size) => self.resize(*size),
+            WindowEvent::Moved(position) if !self.settings.fullscreen => {
+                self.settings.window_position = Some((position.x, position.y));
+                self.settings_menu.window_changed = true;
+            }
+            _ => (),
+        }
+        let binding = Binding::from_event(event);
+
+        // the bindings menu takes the next pressed input as the new binding, escape cancels
+        if let (Some((action, index)), Some((binding, true))) = (self.bindings_menu.listening, binding) {
+            self.bindings_menu.listening = None;
+            if binding != Binding::Key(KeyCode::Escape) {
+                self.input_map.rebind(action, index, binding);
+                self.save_input_map();
+            }
+            return;
+        }
+
+        if let Some((binding, true)) = binding {
+            let actions: Vec<Action> = self.input_map.actions(binding).collect();
+            for action in actions {
+                match action {
+                    Action::ToggleConsole => {
+                        self.console.open = !self.console.open;
+                        self.console.input.clear();
+                    }
+                    Action::OpenCommand if !self.ui_open() => {
+                        self.console.open = true;
+                        self.console.input = "/".to_string();
+                    }
+                    Action::OpenControls if !self.console.open => self.bindings_menu.open = !self.bindings_menu.open,
+                    Action::Pause if self.console.open => self.console.open = false,
+                    Action::Pause if self.bindings_menu.open => self.bindings_menu.open = false,
+                    Action::Pause if self.settings_menu.open => self.settings_menu.open = false,
+                    Action::Pause => self.pause_menu.open = !self.pause_menu.open,
+                    _ => continue,
+                }
+                // not passed on to egui, so the key that opened the console isn't typed into it
+                self.update_cursor_capture();
+                return;
+            }
+        }
+
+        let _ = self.egui_renderer.state.on_window_event(&self.window, event);
+        if self.ui_open() { return; }
+        if let Some((binding, pressed)) = binding {
+            for action in self.input_map.actions(binding) {
+                self.world.camera_controller.process_action(action, pressed);
+            }
+        }
+    }
+
+    /// Whether a menu that needs the cursor is open.
+    pub fn ui_open(&self) -> bool {
+        self.console.open || self.bindings_menu.open || self.settings_menu.open || self.pause_menu.open
+    }
+
+    /// The simulation doesn't run while the pause menu, or a menu opened from it, is shown.
+    pub fn paused(&self) -> bool {
+        self.pause_menu.open
     }
 
+    fn pause_menu_action(&mut self, action: PauseMenuAction) {
+        match action {
+            PauseMenuAction::Resume => self.pause_menu.open = false,
+            PauseMenuAction::Settings => self.settings_menu.open = true,
+            PauseMenuAction::SaveAndQuit => match self.save_all() {
+                Ok(_) => self.exit_requested = true,
+                Err(error) => self.pause_menu.status = Some(format!("{error:#}")),
+            },
+        }
+    }
+
+    /// Captures the cursor when no menu is open, letting go of held movement keys when it's released.
+    pub fn update_cursor_capture(&mut self) {
+        let captured = !self.ui_open();
+        if captured == self.cursor_captured { return; }
+
+        self.cursor_captured = captured;
+        self.capture_cursor(captured);
+        if !captured { self.world.camera_controller.controls.release_movement(); }
+    }
+
+    fn save_input_map(&mut self) {
+        self.bindings_menu.status = Some(match self.input_map.save(CONTROLS_PATH) {
+            Ok(()) => format!("saved to {CONTROLS_PATH}"),
+            Err(error) => format!("{error:#}"),
+        });
+    }
+
+    /// Advances the simulation by one fixed tick.
     pub fn update(&mut self, dt: f32) {
-        self.world.camera_controller.update_camera(&mut self.world.camera, dt);
-        self.world.camera_uniform.update_view_projection(&self.world.camera);
-        self.queue.write_buffer(&self.world.camera_buffer, 0, bytemuck::cast_slice(&[self.world.camera_uniform]));
+        self.world.tick(dt);
+        self.move_player(dt);
+        self.interact();
+        self.update_server();
     }
-}
\ No newline at end of file
+
+    /// Sends the player's position and block changes to the server and applies what it sent back.
+    fn update_server(&mut self) {
+        let Some(server) = &mut self.server else { return; };
+        server.send_movement(self.world.player.position);
+        server.send_view_distance(self.settings.render_distance);
+        if let Err(error) = server.update(&mut self.world.loaded_chunks) {
+            log::error!("{error:#}");
+            self.console.print(LineKind::Error, format!("{error:#}"));
+            self.console.open = true;
+        }
+        if let Some(position) = server.teleport.take() {
+            self.world.player.teleport(position);
+        }
+    }
+
+    /// Loads the chunks of a local world that came into the view distance and saves and unloads those that left it.
+    /// On a server they stream from the server instead.
+    pub fn stream_chunks(&mut self) {
+        if self.server.is_some() { return; }
+        let (save, generator, seed) = (&self.save, self.world.generator, self.world.seed);
+        if self.benchmark {
+            self.world.stream_chunks(|position| generator.generate(position, seed), |_| Ok(()));
+        } else {
+            self.world.stream_chunks(|position| save.load_or_generate(position, generator, seed), |chunk| save.save_chunk(chunk));
+        }
+    }
+
+    /// Per frame work before rendering, with the camera `alpha` of the way between the last tick and the next.
+    pub fn prepare_frame(&mut self, alpha: f32) {
+        self.world.apply_settings(&self.settings);
+        self.renderer.apply_settings(&self.settings, &self.world, &self.device, &self.queue);
+        self.world.camera.eye = self.world.player.interpolated_eye(alpha);
+        self.renderer.prepare(&mut self.world, &self.settings, &self.device, &self.queue);
+    }
+}
@@ -0,0 +1,68 @@
+use cgmath::{InnerSpace, Vector3};
+
+pub const DAY_ZENITH_COLOR: [f32; 3] = [0.22, 0.42, 0.95];
+pub const DAY_HORIZON_COLOR: [f32; 3] = [123.0 / 255.0, 164.0 / 255.0, 1.0];
+pub const SUNSET_HORIZON_COLOR: [f32; 3] = [0.95, 0.5, 0.25];
+pub const NIGHT_ZENITH_COLOR: [f32; 3] = [0.005, 0.008, 0.03];
+pub const NIGHT_HORIZON_COLOR: [f32; 3] = [0.02, 0.03, 0.07];
+pub const MIN_AMBIENT: f32 = 0.15;
+
+pub struct SkyColors {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    pub ambient: f32,
+}
+
+/// Time of day in the `0.0..1.0` range, where 0.0 is sunrise, 0.25 noon, 0.5 sunset and 0.75 midnight.
+pub struct WorldTime {
+    pub time_of_day: f32,
+    /// length of a full day in seconds
+    pub day_length: f32,
+    pub frozen: bool,
+}
+
+impl WorldTime {
+    pub const SUNRISE: f32 = 0.0;
+    pub const NOON: f32 = 0.25;
+    pub const SUNSET: f32 = 0.5;
+    pub const MIDNIGHT: f32 = 0.75;
+
+    pub fn new(time_of_day: f32, day_length: f32) -> Self {
+        Self { time_of_day: time_of_day.rem_euclid(1.0), day_length, frozen: false }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        if self.frozen || self.day_length <= 0.0 { return; }
+        self.time_of_day = (self.time_of_day + dt / self.day_length).rem_euclid(1.0);
+    }
+
+    pub fn set(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+    }
+
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = self.time_of_day * std::f32::consts::TAU;
+        Vector3::new(angle.cos(), angle.sin(), 0.25).normalize()
+    }
+
+    pub fn sky_colors(&self) -> SkyColors {
+        let sun_height = self.sun_direction().y;
+        let daylight = smoothstep(-0.15, 0.2, sun_height);
+        let sunset = (1.0 - sun_height.abs() * 4.0).clamp(0.0, 1.0) * 0.8;
+
+        let zenith = lerp3(NIGHT_ZENITH_COLOR, DAY_ZENITH_COLOR, daylight);
+        let horizon = lerp3(lerp3(NIGHT_HORIZON_COLOR, DAY_HORIZON_COLOR, daylight), SUNSET_HORIZON_COLOR, sunset);
+        let ambient = MIN_AMBIENT + (1.0 - MIN_AMBIENT) * daylight;
+
+        SkyColors { zenith, horizon, ambient }
+    }
+}
+
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp3(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
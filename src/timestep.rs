@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+/// Simulation ticks per second, independent of the frame rate.
+pub const TICK_RATE: u32 = 60;
+/// After a stall the simulation skips ahead instead of running more ticks than this in one frame.
+pub const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Turns variable frame times into a whole number of fixed length ticks.
+pub struct FixedTimestep {
+    pub tick: Duration,
+    /// time not yet simulated, less than one tick after `advance`
+    pub accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: u32) -> Self {
+        Self { tick: Duration::from_secs(1) / tick_rate, accumulator: Duration::ZERO }
+    }
+
+    /// Adds the frame's time and returns how many ticks to run, dropping the time of ticks over `MAX_TICKS_PER_FRAME`.
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        self.accumulator += frame_time;
+        let ticks = (self.accumulator.as_nanos() / self.tick.as_nanos()) as u32;
+        // the remainder is kept even when whole ticks are dropped, so the interpolation doesn't jump
+        self.accumulator -= self.tick * ticks;
+        ticks.min(MAX_TICKS_PER_FRAME)
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.tick.as_secs_f32()
+    }
+
+    /// How far rendering is between the last tick and the next one, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+/// Where the time of the last frame went.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameTimings {
+    pub frame_time: Duration,
+    pub update_time: Duration,
+    /// meshing done while preparing the frame, part of the render time
+    pub mesh_time: Duration,
+    pub render_time: Duration,
+    pub ticks: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_partial_ticks() {
+        let mut timestep = FixedTimestep::new(50);
+        assert_eq!(timestep.advance(Duration::from_millis(15)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(15)), 1);
+        assert_eq!(timestep.accumulator, Duration::from_millis(10));
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn runs_several_ticks_for_long_frames() {
+        let mut timestep = FixedTimestep::new(50);
+        assert_eq!(timestep.advance(Duration::from_millis(70)), 3);
+        assert_eq!(timestep.accumulator, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn caps_catch_up_after_stalls() {
+        let mut timestep = FixedTimestep::new(50);
+        assert_eq!(timestep.advance(Duration::from_secs(10)), MAX_TICKS_PER_FRAME);
+        assert!(timestep.accumulator < timestep.tick);
+        assert_eq!(timestep.advance(Duration::ZERO), 0);
+    }
+}
@@ -1,37 +1,106 @@
 use winit::{
-    event::*, event_loop::{ControlFlow, EventLoop}, keyboard::{KeyCode, PhysicalKey}, window::WindowBuilder
+    event::*, event_loop::{ControlFlow, EventLoop}, window::WindowBuilder
 };
 
-pub fn run() {
-    env_logger::init();
+use voxel_game::client::ServerConnection;
+
+use crate::{benchmark::Benchmark, cli::{Cli, Mode}, settings::{PresentMode, Settings, SETTINGS_PATH}, timestep::FrameTimings};
+
+/// How long joining a server may take before the game gives up.
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+pub fn run(cli: Cli) {
+    let benchmark = cli.mode() == Mode::Benchmark;
+    // benchmarks ignore the settings file so results don't depend on who ran them
+    let mut settings = if benchmark {
+        Settings { present_mode: PresentMode::Immediate, ..Settings::default() }
+    } else {
+        Settings::load(SETTINGS_PATH).unwrap_or_else(|error| {
+            log::error!("{error:#}, using the default settings");
+            Settings::default()
+        })
+    };
+    cli.apply(&mut settings);
+
+    // joined before the window opens, so a server that isn't there doesn't flash an empty window
+    let server = cli.connect.as_ref().map(|address| {
+        println!("connecting to {address}");
+        ServerConnection::connect(address.as_str(), &cli.name, settings.render_distance, CONNECT_TIMEOUT).unwrap_or_else(|error| {
+            eprintln!("error: {error:#}");
+            std::process::exit(1);
+        })
+    });
+
     let event_loop = EventLoop::new().unwrap();
-    let mut window = WindowBuilder::new()
-    .with_inner_size(winit::dpi::PhysicalSize::new(1000, 1000))
-    .build(&event_loop).unwrap();
+    let mut builder = WindowBuilder::new()
+    .with_inner_size(winit::dpi::PhysicalSize::new(settings.window_width, settings.window_height))
+    .with_fullscreen(settings.fullscreen.then_some(winit::window::Fullscreen::Borderless(None)));
+    if let Some((x, y)) = settings.window_position {
+        builder = builder.with_position(winit::dpi::PhysicalPosition::new(x, y));
+    }
+    let window = builder.build(&event_loop).unwrap();
 
-    window.set_cursor_grab(winit::window::CursorGrabMode::Confined);
-    window.set_cursor_visible(false);
-    window.set_outer_position(winit::dpi::LogicalPosition::new(900.0, 0.0));
-    let mut state = pollster::block_on(crate::state::State::new(window));
-    let mut last_render_time = std::time::Duration::ZERO;
+    let mut state = pollster::block_on(crate::state::State::new(window, settings, &cli, server));
+    state.update_cursor_capture();
+    let mut benchmark = benchmark.then(|| {
+        state.world.camera_controller.controls.spectator_toggled = true;
+        state.world.time.frozen = true;
+        Benchmark::new(&state.world, state.renderer.meshes.mesh_time, cli.benchmark_frames)
+    });
+    state.renderer.meshes.mesh_time = std::time::Duration::ZERO;
+    let mut last_frame = std::time::Instant::now();
 
     event_loop.run(move |event, elwt| {
         elwt.set_control_flow(ControlFlow::Poll);
         match event {
             Event::WindowEvent { window_id, event } if window_id == state.window.id() => {
-                state.input(&event);
+                // the benchmark flies on its own, only resizing is let through
+                if benchmark.is_none() || matches!(event, WindowEvent::Resized(_)) { state.input(&event); }
                 match event {
-                    WindowEvent::CloseRequested 
-                    | WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(KeyCode::Escape), state: ElementState::Pressed, .. }, ..} => {
+                    WindowEvent::CloseRequested => {
+                        if benchmark.is_none() { let _ = state.save_all(); }
                         elwt.exit();
                     },
                     WindowEvent::RedrawRequested if window_id == state.window.id() => {
                         let now = std::time::Instant::now();
-                        state.update(last_render_time.as_secs_f32());
+                        let frame_time = now - last_frame;
+                        last_frame = now;
+
+                        let ticks = if state.paused() { 0 } else { state.timestep.advance(frame_time) };
+                        for _ in 0..ticks {
+                            state.update(state.timestep.dt());
+                        }
+                        if let Some(benchmark) = &benchmark { benchmark.place_camera(&mut state.world); }
+                        // once a frame rather than a tick, so a benchmark loads the same chunks however fast it renders
+                        state.stream_chunks();
                         let update_time = now.elapsed();
-                    
-                        state.render();
-                        last_render_time = now.elapsed();
+
+                        state.prepare_frame(state.timestep.alpha());
+                        match state.render() {
+                            Ok(()) => (),
+                            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => state.surface.configure(&state.device, &state.config),
+                            Err(error) => log::error!("{error}"),
+                        }
+                        let render_time = now.elapsed() - update_time;
+                        let mesh_time = std::mem::take(&mut state.renderer.meshes.mesh_time);
+                        state.timings = FrameTimings { frame_time, update_time, mesh_time, render_time, ticks };
+
+                        if let Some(benchmark) = &mut benchmark {
+                            benchmark.record(state.timings, state.gui.draw_stats);
+                            if benchmark.finished() {
+                                let report = benchmark.report(&state.world, &state.config, &state.adapter_info);
+                                print!("{}", report.table());
+                                match report.write(&cli.benchmark_output) {
+                                    Ok((json, csv)) => println!("wrote {} and {}", json.display(), csv.display()),
+                                    Err(error) => log::error!("{error:#}"),
+                                }
+                                elwt.exit();
+                            }
+                        }
+
+                        if state.exit_requested {
+                            elwt.exit();
+                        }
                     }
                     _ => ()
                 }
@@ -41,8 +110,8 @@ pub fn run() {
             },
             Event::DeviceEvent { event, .. } => {
                 if let DeviceEvent::MouseMotion { delta } = event {
+                    if state.ui_open() || benchmark.is_some() { return; }
                     state.world.camera_controller.mouse_move(delta.0 as f32, delta.1 as f32, &mut state.world.camera);
-                    state.window.set_cursor_position(winit::dpi::LogicalPosition::new(0.0, 0.0));
                 }
             }
             _ => ()
@@ -0,0 +1,198 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context};
+use cgmath::{Point2, Point3};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{block::{Block, BlockState, Material}, chunk::{Chunk, CHUNK_SIZE, SUB_CHUNKS, SUB_CHUNK_HEIGHT}, save::{decode_block_entities, encode_block_entities, Reader}};
+
+/// Blocks in a sub chunk, each sub chunk is a contiguous slice of `Chunk::blocks`.
+pub const SUB_CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * SUB_CHUNK_HEIGHT;
+
+/// Largest decompressed chunk accepted, anything bigger is broken or hostile data.
+const MAX_CHUNK_DATA: usize = 16 * 1024 * 1024;
+
+// which sub chunks are sent is a bit mask in one byte
+const _: () = assert!(SUB_CHUNKS <= 8);
+
+/// A chunk for the network, zlib compressed:
+/// `position, mask of the sub chunks that aren't all air, palette and packed indices per sent sub chunk, block entities`
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&chunk.position.x.to_le_bytes());
+    data.extend_from_slice(&chunk.position.y.to_le_bytes());
+
+    let sent: Vec<(usize, &[Block])> = chunk.blocks.chunks(SUB_CHUNK_VOLUME).enumerate().filter(|(_, blocks)| !is_empty(blocks)).collect();
+    data.push(sent.iter().fold(0, |mask, (index, _)| mask | 1 << index));
+    for (_, blocks) in sent {
+        encode_sub_chunk(blocks, &mut data);
+    }
+    encode_block_entities(chunk, &mut data);
+
+    // fast beats small here, the server compresses every chunk it streams
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(data.len() / 4), Compression::fast());
+    encoder.write_all(&data).expect("writing to a vec doesn't fail");
+    encoder.finish().expect("writing to a vec doesn't fail")
+}
+
+pub fn decode_chunk(data: &[u8]) -> anyhow::Result<Chunk> {
+    let mut decompressed = vec![];
+    ZlibDecoder::new(data).take(MAX_CHUNK_DATA as u64 + 1).read_to_end(&mut decompressed).context("decompressing")?;
+    if decompressed.len() > MAX_CHUNK_DATA { bail!("decompresses to more than {MAX_CHUNK_DATA} bytes"); }
+
+    let mut reader = Reader::new(&decompressed);
+    let mut chunk = Chunk::new(Point2::new(reader.i32()?, reader.i32()?));
+    chunk.blocks.fill(Block::new(Material::Air));
+    let mask = reader.u8()?;
+    if u16::from(mask) >> SUB_CHUNKS != 0 { bail!("sub chunk mask {mask:#010b} has sub chunks above the top of the chunk"); }
+    for (index, blocks) in chunk.blocks.chunks_mut(SUB_CHUNK_VOLUME).enumerate() {
+        if mask & 1 << index != 0 {
+            decode_sub_chunk(&mut reader, blocks).with_context(|| format!("decoding sub chunk {index}"))?;
+        }
+    }
+    decode_block_entities(&mut reader, &mut chunk)?;
+
+    if !reader.data.is_empty() { bail!("{} bytes of trailing data", reader.data.len()); }
+    Ok(chunk)
+}
+
+/// Sub chunks of nothing but default air aren't sent, the decoder fills them in.
+fn is_empty(blocks: &[Block]) -> bool {
+    blocks.iter().all(|block| block.material == Material::Air && block.state.bits() == 0)
+}
+
+/// Bits an index into a palette of `length` blocks takes, none when there's only one block.
+fn index_bits(length: usize) -> u32 {
+    usize::BITS - (length - 1).leading_zeros()
+}
+
+/// `palette length, (material, state) per palette block, one index into the palette per block packed least significant bit first`
+fn encode_sub_chunk(blocks: &[Block], data: &mut Vec<u8>) {
+    // palette index by material and state, there are few enough blocks for a table
+    let mut lookup = vec![u32::MAX; Material::ALL.len() << 8];
+    let mut palette = vec![];
+    let indices: Vec<u32> = blocks.iter().map(|block| {
+        let index = &mut lookup[(block.material as usize) << 8 | block.state.bits() as usize];
+        if *index == u32::MAX {
+            *index = palette.len() as u32;
+            palette.push(*block);
+        }
+        *index
+    }).collect();
+
+    data.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+    for block in palette.iter() {
+        data.extend_from_slice(&[block.material as u8, block.state.bits()]);
+    }
+
+    let bits = index_bits(palette.len());
+    let (mut accumulator, mut filled) = (0u32, 0);
+    for index in indices {
+        accumulator |= index << filled;
+        filled += bits;
+        while filled >= 8 {
+            data.push(accumulator as u8);
+            accumulator >>= 8;
+            filled -= 8;
+        }
+    }
+    if filled > 0 { data.push(accumulator as u8); }
+}
+
+fn decode_sub_chunk(reader: &mut Reader, blocks: &mut [Block]) -> anyhow::Result<()> {
+    let length = reader.u16()? as usize;
+    if length == 0 || length > blocks.len() { bail!("palette of {length} blocks"); }
+    let palette = (0..length).map(|_| {
+        let id = reader.u8()?;
+        let material = Material::from_id(id).with_context(|| format!("unknown material {id}"))?;
+        Ok(Block::with_state(material, BlockState::from_bits(reader.u8()?)))
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+
+    let bits = index_bits(length);
+    let mut packed = reader.take((blocks.len() * bits as usize).div_ceil(8))?.iter();
+    let (mut accumulator, mut filled) = (0u32, 0);
+    for block in blocks.iter_mut() {
+        while filled < bits {
+            accumulator |= u32::from(*packed.next().expect("enough bytes were taken for every index")) << filled;
+            filled += 8;
+        }
+        let index = accumulator & ((1 << bits) - 1);
+        accumulator >>= bits;
+        filled -= bits;
+        *block = *palette.get(index as usize).with_context(|| format!("palette index {index} with only {length} blocks"))?;
+    }
+    Ok(())
+}
+
+/// The sub chunk a position inside a chunk is in and its index inside that sub chunk, the way `MultiBlockChange` addresses blocks.
+pub fn sub_chunk_index(local: (usize, usize, usize)) -> (u8, u16) {
+    let (x, y, z) = local;
+    ((y / SUB_CHUNK_HEIGHT) as u8, (x + (y % SUB_CHUNK_HEIGHT) * CHUNK_SIZE * CHUNK_SIZE + z * CHUNK_SIZE) as u16)
+}
+
+/// World position of a block addressed by `sub_chunk_index`.
+pub fn sub_chunk_block_position(chunk: Point2<i32>, sub_chunk: u8, index: u16) -> Point3<i32> {
+    let index = index as usize;
+    let y = sub_chunk as usize * SUB_CHUNK_HEIGHT + index / (CHUNK_SIZE * CHUNK_SIZE);
+    Point3::new(chunk.x * CHUNK_SIZE as i32 + (index % CHUNK_SIZE) as i32, y as i32, chunk.y * CHUNK_SIZE as i32 + (index / CHUNK_SIZE % CHUNK_SIZE) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{generator::GeneratorPreset, save};
+
+    use super::*;
+
+    #[test]
+    fn palette_indices_use_as_few_bits_as_needed() {
+        assert_eq!([1, 2, 3, 4, 5, 16, 17, SUB_CHUNK_VOLUME].map(index_bits), [0, 1, 2, 2, 3, 4, 5, 15]);
+    }
+
+    #[test]
+    fn empty_sub_chunks_are_skipped() {
+        let chunk = GeneratorPreset::Empty.generate(Point2::new(3, -4), 0);
+        let data = encode_chunk(&chunk);
+        assert!(data.len() < 64, "{} bytes", data.len());
+        assert_eq!(save::encode_chunk(&decode_chunk(&data).unwrap()), save::encode_chunk(&chunk));
+    }
+
+    #[test]
+    fn generated_chunks_are_much_smaller_than_their_blocks() {
+        // layers compress to almost nothing, noise of four materials can't get below two bits a block
+        for (preset, limit) in [(GeneratorPreset::Flat, 1024), (GeneratorPreset::Random, SUB_CHUNK_VOLUME * SUB_CHUNKS / 4 + 1024)] {
+            let chunk = preset.generate(Point2::new(-2, 5), 42);
+            let data = encode_chunk(&chunk);
+            assert!(data.len() < limit, "{preset:?} chunk took {} bytes", data.len());
+            assert_eq!(save::encode_chunk(&decode_chunk(&data).unwrap()), save::encode_chunk(&chunk));
+        }
+    }
+
+    #[test]
+    fn sub_chunk_indices_address_every_block_once() {
+        let chunk = Point2::new(-1, 2);
+        for (x, y, z) in [(0, 0, 0), (31, 31, 31), (5, 32, 7), (31, 255, 0), (12, 100, 30)] {
+            let (sub_chunk, index) = sub_chunk_index((x, y, z));
+            assert_eq!(sub_chunk as usize, y / SUB_CHUNK_HEIGHT);
+            assert_eq!(sub_chunk_block_position(chunk, sub_chunk, index), Point3::new(x as i32 - 32, y as i32, z as i32 + 64));
+        }
+    }
+
+    #[test]
+    fn corrupt_chunks_are_rejected() {
+        let data = encode_chunk(&GeneratorPreset::Flat.generate(Point2::new(0, 0), 0));
+        assert!(decode_chunk(&data[..data.len() / 2]).is_err());
+        assert!(decode_chunk(b"not zlib at all").is_err());
+
+        // a palette index past the end of the palette
+        let mut raw = vec![0; 8];
+        raw.push(1);
+        raw.extend_from_slice(&3u16.to_le_bytes());
+        raw.extend_from_slice(&[0, 0, 1, 0, 2, 0]);
+        raw.extend(std::iter::repeat_n(0xff, SUB_CHUNK_VOLUME * 2 / 8));
+        raw.extend_from_slice(&0u32.to_le_bytes());
+        let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+        encoder.write_all(&raw).unwrap();
+        let error = decode_chunk(&encoder.finish().unwrap()).unwrap_err();
+        assert!(format!("{error:#}").contains("palette index 3"), "{error:#}");
+    }
+}
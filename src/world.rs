@@ -0,0 +1,118 @@
+use cgmath::Point2;
+
+use crate::{camera::*, chunk::{chunk_distance, chunk_local_position, chunks_around, Chunk, ChunkManager, CHUNK_SIZE, UNLOAD_MARGIN}, generator::GeneratorPreset, player::Player, raycast::{RaycastHit, MAX_REACH}, settings::Settings, time::WorldTime};
+
+/// Chunks loaded per frame at most, nearest first, so generating them doesn't stall the game.
+const CHUNKS_PER_FRAME: usize = 2;
+
+/// Everything the client simulates, the renderer only reads it.
+pub struct World {
+    pub loaded_chunks: ChunkManager,
+    pub camera: Camera,
+    pub camera_controller: CameraController,
+    pub player: Player,
+    /// seed of the generator
+    pub seed: u64,
+    /// generates the chunks that aren't in the save
+    pub generator: GeneratorPreset,
+    pub time: WorldTime,
+    /// chunks loaded and drawn around the player, the render distance up to `max_view_distance`
+    pub view_distance: u32,
+    /// most chunks the server the world comes from sends around the player, no limit for local worlds
+    pub max_view_distance: u32,
+}
+
+impl World {
+    pub fn new(width: u32, height: u32) -> Self {
+        let camera = Camera::default(width, height);
+        let camera_controller = CameraController::new(5.0);
+        let player = Player::new(camera.eye);
+        let time = WorldTime::new(WorldTime::SUNRISE + 0.05, 600.0);
+
+        Self { loaded_chunks: ChunkManager::new(), camera, camera_controller, player, seed: rand::random(), generator: GeneratorPreset::default(), time, view_distance: 0, max_view_distance: u32::MAX }
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.camera.fovy = settings.fovy;
+        self.camera.zfar = settings.zfar;
+        self.camera_controller.speed = settings.fly_speed;
+        self.camera_controller.sensitivity = settings.mouse_sensitivity;
+        self.camera_controller.invert_y = settings.invert_y;
+        self.view_distance = settings.render_distance.min(self.max_view_distance);
+    }
+
+    /// Where the fog hides everything, at the edge of the loaded chunks.
+    pub fn fog_end(&self) -> f32 {
+        (self.view_distance as f32 * CHUNK_SIZE as f32).min(self.camera.zfar)
+    }
+
+    /// Loads the nearest missing chunks within the view distance of the player with `load`, then unloads the chunks past it, handing them to `unload` first.
+    /// A chunk `unload` fails on stays loaded.
+    pub fn stream_chunks(&mut self, mut load: impl FnMut(Point2<i32>) -> Chunk, mut unload: impl FnMut(&Chunk) -> anyhow::Result<()>) {
+        let center = chunk_local_position(self.player.position.map(|coordinate| coordinate.floor() as i32)).0;
+        let distance = self.view_distance as i32;
+        let missing: Vec<Point2<i32>> = chunks_around(center, distance).into_iter().filter(|position| self.loaded_chunks.get(*position).is_none()).take(CHUNKS_PER_FRAME).collect();
+        for position in missing {
+            self.loaded_chunks.insert(load(position));
+        }
+
+        let far: Vec<Point2<i32>> = self.loaded_chunks.chunks.values().map(|chunk| chunk.position).filter(|position| chunk_distance(*position, center) > distance + UNLOAD_MARGIN).collect();
+        for position in far {
+            match unload(self.loaded_chunks.get(position).expect("the chunk is loaded")) {
+                Ok(()) => { self.loaded_chunks.remove(position); }
+                Err(error) => log::error!("keeping chunk {position:?} loaded: {error:#}"),
+            }
+        }
+    }
+
+    /// Block under the crosshair, within reach of the camera.
+    pub fn targeted_block(&self) -> Option<RaycastHit> {
+        self.loaded_chunks.raycast(self.camera.eye, self.camera.direction, MAX_REACH)
+    }
+
+    /// Advances the time of day and the block entities by one fixed tick.
+    pub fn tick(&mut self, dt: f32) {
+        self.time.advance(dt);
+        for chunk in self.loaded_chunks.chunks.values_mut() {
+            chunk.tick_block_entities(dt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use super::*;
+    use crate::block::{Block, Material};
+
+    #[test]
+    fn local_worlds_keep_no_block_changes() {
+        let mut world = World::new(800, 600);
+        world.loaded_chunks.insert(GeneratorPreset::Flat.generate(Point2::new(0, 0), 0));
+        world.loaded_chunks.fill(Point3::new(0, 70, 0), Point3::new(31, 80, 31), Block::new(Material::Glass));
+        world.tick(1.0 / 60.0);
+        assert!(world.loaded_chunks.changed_blocks.is_empty());
+    }
+
+    #[test]
+    fn chunks_stream_in_and_out_around_the_player() {
+        let mut world = World::new(800, 600);
+        world.view_distance = 2;
+        world.player.position = Point3::new(10.0, 80.0, 10.0);
+        let load = |position| GeneratorPreset::Flat.generate(position, 0);
+        for _ in 0..25 {
+            world.stream_chunks(load, |_| Ok(()));
+        }
+        assert_eq!(world.loaded_chunks.chunks.len(), 25);
+        assert!(world.loaded_chunks.get(Point2::new(-2, 2)).is_some());
+
+        // a chunk past the margin is unloaded, one that fails to unload stays
+        world.player.position.x += 3.0 * CHUNK_SIZE as f32;
+        let mut unloaded = vec![];
+        world.stream_chunks(load, |chunk| if chunk.position.y == 0 { anyhow::bail!("disk full") } else { unloaded.push(chunk.position); Ok(()) });
+        assert_eq!(unloaded.len(), 8);
+        assert!(unloaded.iter().all(|position| position.x < 0 && world.loaded_chunks.get(*position).is_none()));
+        assert!(world.loaded_chunks.get(Point2::new(-2, 0)).is_some() && world.loaded_chunks.get(Point2::new(0, 2)).is_some());
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+/// A fresh world directory per test, removed when dropped.
+pub struct TempWorld(pub PathBuf);
+
+impl TempWorld {
+    pub fn new(name: &str) -> Self {
+        let directory = std::env::temp_dir().join(format!("voxel_game_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        Self(directory)
+    }
+}
+
+impl Drop for TempWorld {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
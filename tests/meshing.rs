@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use cgmath::{EuclideanSpace, Point2, Point3, Vector3};
+use proptest::prelude::*;
+use voxel_game::{block::{Block, Material, Transparency}, block_vertex::{Face, SUB_VOXEL_PRECISION}, chunk::{chunk_local_position, ChunkManager, CHUNK_SIZE, SUB_CHUNK_HEIGHT}, generator::GeneratorPreset, mesher::{mesh_sub_chunk, MeshData}};
+
+/// Corner of the region the blocks are placed in, it straddles the border between chunks 0 and 1 and between sub chunks 0 and 1.
+const REGION_MIN: Point3<i32> = Point3::new(28, 28, 0);
+const REGION_SIZE: Vector3<i32> = Vector3::new(8, 8, 4);
+
+/// Full cube materials: opaque, cutout, translucent and ones that cull faces against themselves.
+const MATERIALS: [Material; 7] = [Material::Cobblestone, Material::Dirt, Material::Grass, Material::Leaves, Material::Glass, Material::StainedGlass, Material::Water];
+
+fn region_blocks() -> impl Strategy<Value = Vec<Material>> {
+    let material = prop_oneof![2 => Just(Material::Air), 3 => proptest::sample::select(MATERIALS.as_slice())];
+    proptest::collection::vec(material, (REGION_SIZE.x * REGION_SIZE.y * REGION_SIZE.z) as usize)
+}
+
+fn world(materials: &[Material]) -> ChunkManager {
+    let mut chunks = ChunkManager::new();
+    for x in 0..2 {
+        chunks.insert(GeneratorPreset::Empty.generate(Point2::new(x, 0), 0));
+    }
+    let positions = (0..REGION_SIZE.x).flat_map(|x| (0..REGION_SIZE.y).flat_map(move |y| (0..REGION_SIZE.z).map(move |z| REGION_MIN + Vector3::new(x, y, z))));
+    for (position, material) in positions.zip(materials) {
+        let (chunk_position, local) = chunk_local_position(position);
+        chunks.get_mut(chunk_position).unwrap()[local] = Block::new(*material);
+    }
+    chunks
+}
+
+/// How often each face was meshed, by the world position of its block and its side.
+type FaceCounts = HashMap<(Point3<i32>, Face), usize>;
+
+fn faces(mesh: &MeshData, chunk_position: Point2<i32>, index: usize, faces: &mut FaceCounts) {
+    for quad in mesh.vertices.chunks(4) {
+        let vertices: Vec<_> = quad.iter().map(|vertex| vertex.unpack()).collect();
+        let face = vertices[0].face;
+        assert!(vertices.iter().all(|vertex| vertex.face == face));
+
+        // four times the center of the face, pushed a quarter block back into its block
+        let sum: Vector3<i32> = vertices.iter().map(|vertex| vertex.position.cast::<i32>().unwrap().to_vec()).sum();
+        let local = (sum - face.normal() * SUB_VOXEL_PRECISION as i32).map(|c| c.div_euclid(4 * SUB_VOXEL_PRECISION as i32));
+        let position = Point3::new(chunk_position.x * CHUNK_SIZE as i32 + local.x, (index * SUB_CHUNK_HEIGHT) as i32 + local.y, chunk_position.y * CHUNK_SIZE as i32 + local.z);
+        *faces.entry((position, face)).or_default() += 1;
+    }
+}
+
+/// Faces the meshers should produce: every side of a block whose neighbor doesn't cover it.
+fn exposed_faces(chunks: &ChunkManager, translucent: bool) -> Vec<(Point3<i32>, Face)> {
+    let mut exposed = vec![];
+    for chunk in chunks.chunks.values() {
+        let origin = chunk.world_origin();
+        for (i, block) in chunk.blocks.iter().enumerate() {
+            if block.material == Material::Air || (block.material.transparency() == Transparency::Translucent) != translucent { continue; }
+            let position = origin + Vector3::new((i % CHUNK_SIZE) as i32, (i / (CHUNK_SIZE * CHUNK_SIZE)) as i32, (i / CHUNK_SIZE % CHUNK_SIZE) as i32);
+            for face in Face::ALL {
+                let hidden = chunks.block(position + face.normal()).is_some_and(|neighbor| neighbor.material.is_opaque() || (neighbor.material == block.material && block.material.culls_same()));
+                if !hidden { exposed.push((position, face)); }
+            }
+        }
+    }
+    exposed
+}
+
+/// Opaque and translucent faces of the sub chunks around the region.
+fn mesh_region(chunks: &ChunkManager) -> (FaceCounts, FaceCounts) {
+    let (mut opaque, mut translucent) = (HashMap::new(), HashMap::new());
+    for x in 0..2 {
+        let chunk_position = Point2::new(x, 0);
+        let chunk = chunks.get(chunk_position).unwrap();
+        for index in 0..2 {
+            let sub_chunk = mesh_sub_chunk(chunk, index, chunks);
+            assert_eq!(sub_chunk.translucent_faces.len(), sub_chunk.translucent_mesh.faces());
+            faces(&sub_chunk.mesh, chunk_position, index, &mut opaque);
+            faces(&sub_chunk.translucent_mesh, chunk_position, index, &mut translucent);
+        }
+    }
+    (opaque, translucent)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn no_faces_between_solid_blocks(materials in region_blocks()) {
+        let chunks = world(&materials);
+        let (opaque, translucent) = mesh_region(&chunks);
+        for (position, face) in opaque.keys().chain(translucent.keys()) {
+            let block = chunks.block(*position).unwrap();
+            prop_assert!(block.material != Material::Air);
+            let neighbor = chunks.block(position + face.normal());
+            prop_assert!(!neighbor.is_some_and(|neighbor| neighbor.material.is_opaque()), "{:?} face of {:?} at {:?} is covered by a solid block", face, block.material, position);
+        }
+    }
+
+    #[test]
+    fn every_exposed_face_exactly_once(materials in region_blocks()) {
+        let chunks = world(&materials);
+        let (opaque, translucent) = mesh_region(&chunks);
+        for (mesh, translucent_mesh) in [(&opaque, false), (&translucent, true)] {
+            let expected = exposed_faces(&chunks, translucent_mesh);
+            for face in &expected {
+                prop_assert_eq!(mesh.get(face).copied(), Some(1), "{:?} (translucent: {})", face, translucent_mesh);
+            }
+            prop_assert_eq!(mesh.len(), expected.len(), "translucent: {}", translucent_mesh);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use std::{net::{SocketAddr, TcpStream}, sync::mpsc, thread::JoinHandle, time::{Duration, Instant}};
+
+use cgmath::{Point2, Point3};
+use common::TempWorld;
+use voxel_game::{block::{Block, Material}, chunk::{chunk_distance, chunk_local_position, chunks_around, ChunkManager, UNLOAD_MARGIN}, client::ServerConnection, generator::GeneratorPreset, protocol::{Connection, Packet, PROTOCOL_VERSION}, save::{encode_chunk, WorldSave}, server::{Server, MAX_VIEW_DISTANCE}};
+
+const SEED: u64 = 11;
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Chunks the test clients ask for around their player, few so the tests don't wait on generating chunks.
+const VIEW_DISTANCE: i32 = 3;
+
+/// A server running its own loop on a thread, as the dedicated binary does.
+struct TestServer {
+    world: TempWorld,
+    address: SocketAddr,
+    commands: mpsc::Sender<String>,
+    thread: JoinHandle<anyhow::Result<()>>,
+}
+
+impl TestServer {
+    fn start(name: &str) -> Self {
+        let world = TempWorld::new(name);
+        let (commands, receiver) = mpsc::channel();
+        let (address_sender, address) = mpsc::channel();
+        let directory = world.0.clone();
+        // the server isn't `Send`, it lives on the thread that runs it
+        let thread = std::thread::spawn(move || {
+            let mut server = Server::new(WorldSave::new(directory), SEED, GeneratorPreset::Flat, "127.0.0.1:0")?;
+            address_sender.send(server.local_address())?;
+            server.run(receiver, |_| ())
+        });
+        Self { world, address: address.recv().unwrap(), commands, thread }
+    }
+
+    fn connect(&self, name: &str) -> (ServerConnection, ChunkManager) {
+        (ServerConnection::connect(self.address, name, VIEW_DISTANCE as u32, TIMEOUT).unwrap(), ChunkManager::new())
+    }
+
+    /// Stops the server and returns the world it saved.
+    fn stop(self) -> TempWorld {
+        self.commands.send("stop".to_string()).unwrap();
+        self.thread.join().unwrap().unwrap();
+        self.world
+    }
+}
+
+/// Updates the client until `done` holds.
+fn update_until(client: &mut ServerConnection, chunks: &mut ChunkManager, done: impl Fn(&ServerConnection, &ChunkManager) -> bool) {
+    let start = Instant::now();
+    while !done(client, chunks) {
+        assert!(start.elapsed() < TIMEOUT, "timed out waiting for the server");
+        client.update(chunks).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn view_around(position: Point3<f32>, distance: i32) -> Vec<Point2<i32>> {
+    chunks_around(chunk_local_position(position.map(|coordinate| coordinate.floor() as i32)).0, distance)
+}
+
+#[test]
+fn chunks_stream_from_the_server() {
+    let server = TestServer::start("multiplayer_chunks");
+    let (mut client, mut chunks) = server.connect("alice");
+    assert_eq!(client.player_id, 1);
+    assert_eq!(client.max_view_distance, MAX_VIEW_DISTANCE as u32);
+    // on the grass of the flat world
+    let ground = client.spawn.map(|coordinate| coordinate.floor() as i32) - cgmath::Vector3::new(0, 1, 0);
+    assert_eq!(GeneratorPreset::Flat.generate(chunk_local_position(ground).0, SEED)[chunk_local_position(ground).1].material, Material::Grass);
+
+    let view = view_around(client.spawn, VIEW_DISTANCE);
+    update_until(&mut client, &mut chunks, |_, chunks| view.iter().all(|position| chunks.get(*position).is_some()));
+    assert_eq!(chunks.chunks.len(), view.len());
+    for position in view {
+        assert_eq!(encode_chunk(chunks.get(position).unwrap()), encode_chunk(&GeneratorPreset::Flat.generate(position, SEED)));
+    }
+    // every streamed chunk is waiting to be meshed
+    assert!(!chunks.dirty_sub_chunks.is_empty());
+
+    client.disconnect("done");
+    server.stop();
+}
+
+#[test]
+fn block_changes_reach_other_clients_and_the_save() {
+    let server = TestServer::start("multiplayer_blocks");
+    let (mut alice, mut alice_chunks) = server.connect("alice");
+    let (mut bob, mut bob_chunks) = server.connect("bob");
+    let position = alice.spawn.map(|coordinate| coordinate.floor() as i32);
+    update_until(&mut alice, &mut alice_chunks, |_, chunks| chunks.block(position).is_some());
+    update_until(&mut bob, &mut bob_chunks, |_, chunks| chunks.block(position).is_some());
+
+    assert!(alice_chunks.set_block(position, Block::new(Material::Cobblestone)));
+    alice.update(&mut alice_chunks).unwrap();
+    update_until(&mut bob, &mut bob_chunks, |_, chunks| chunks.block(position).unwrap().material == Material::Cobblestone);
+    // applying the server's change doesn't send it back
+    assert!(bob_chunks.changed_blocks.is_empty());
+
+    bob.disconnect("done");
+    alice.disconnect("done");
+    let world = server.stop();
+    let (chunk_position, local) = chunk_local_position(position);
+    assert_eq!(WorldSave::new(&world.0).load_chunk(chunk_position).unwrap().unwrap()[local].material, Material::Cobblestone);
+}
+
+#[test]
+fn movement_is_relayed_and_streams_chunks_around_the_player() {
+    let server = TestServer::start("multiplayer_movement");
+    let (mut alice, mut alice_chunks) = server.connect("alice");
+    let (mut bob, mut bob_chunks) = server.connect("bob");
+
+    // far faster than anyone can fly, the server moves alice back
+    alice.send_movement(Point3::new(5000.5, 70.0, 0.5));
+    update_until(&mut alice, &mut alice_chunks, |alice, _| alice.teleport.is_some());
+    assert_eq!(alice.teleport, Some(alice.spawn));
+
+    // far enough that the views don't overlap, in steps a player can fly
+    let destination = Point3::new(300.5, 70.0, -200.5);
+    for step in 1..=4 {
+        alice.send_movement(alice.spawn + (destination - alice.spawn) * (step as f32 / 4.0));
+        alice.update(&mut alice_chunks).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+    }
+    update_until(&mut bob, &mut bob_chunks, |bob, _| bob.players.get(&alice.player_id) == Some(&destination));
+    assert!(!bob.players.contains_key(&bob.player_id));
+
+    // the chunks around the spawn are unloaded again
+    let (view, spawn_view) = (view_around(destination, VIEW_DISTANCE), view_around(alice.spawn, VIEW_DISTANCE));
+    update_until(&mut alice, &mut alice_chunks, |_, chunks| view.iter().all(|position| chunks.get(*position).is_some()) && spawn_view.iter().all(|position| chunks.get(*position).is_none()));
+    update_until(&mut bob, &mut bob_chunks, |_, chunks| chunks.chunks.len() == view.len());
+    assert!(view.iter().all(|position| bob_chunks.get(*position).is_none()));
+
+    alice.disconnect("done");
+    bob.disconnect("done");
+    server.stop();
+}
+
+#[test]
+fn the_view_distance_can_change() {
+    let server = TestServer::start("multiplayer_view_distance");
+    let (mut client, mut chunks) = server.connect("alice");
+    update_until(&mut client, &mut chunks, |client, chunks| chunks.chunks.len() == view_around(client.spawn, VIEW_DISTANCE).len());
+
+    let center = chunk_local_position(client.spawn.map(|coordinate| coordinate.floor() as i32)).0;
+    client.send_view_distance(1);
+    update_until(&mut client, &mut chunks, |_, chunks| chunks.chunks.values().all(|chunk| chunk_distance(chunk.position, center) <= 1 + UNLOAD_MARGIN));
+    client.send_view_distance(4);
+    update_until(&mut client, &mut chunks, |client, chunks| view_around(client.spawn, 4).iter().all(|position| chunks.get(*position).is_some()));
+
+    client.disconnect("done");
+    server.stop();
+}
+
+#[test]
+fn block_changes_out_of_reach_are_refused() {
+    let server = TestServer::start("multiplayer_reach");
+    let (mut client, mut chunks) = server.connect("alice");
+    let view = view_around(client.spawn, VIEW_DISTANCE);
+    update_until(&mut client, &mut chunks, |_, chunks| view.iter().all(|position| chunks.get(*position).is_some()));
+
+    // in a sent chunk, but much farther than the player reaches
+    let far = client.spawn.map(|coordinate| coordinate.floor() as i32) + cgmath::Vector3::new(40, -1, 0);
+    let grass = chunks.block(far).unwrap().material;
+    assert!(chunks.set_block(far, Block::new(Material::Air)));
+    client.update(&mut chunks).unwrap();
+    update_until(&mut client, &mut chunks, |_, chunks| chunks.block(far).unwrap().material == grass);
+
+    client.disconnect("done");
+    let world = server.stop();
+    let (chunk_position, local) = chunk_local_position(far);
+    assert_eq!(WorldSave::new(&world.0).load_chunk(chunk_position).unwrap().unwrap()[local].material, grass);
+}
+
+#[test]
+fn kicked_clients_learn_why() {
+    let server = TestServer::start("multiplayer_kick");
+    let (mut client, mut chunks) = server.connect("alice");
+    server.commands.send(format!("kick {}", client.player_id)).unwrap();
+
+    let start = Instant::now();
+    let error = loop {
+        assert!(start.elapsed() < TIMEOUT, "the client wasn't kicked");
+        if let Err(error) = client.update(&mut chunks) { break error; }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(error.to_string(), "disconnected by the server: kicked");
+    assert!(!client.is_connected());
+    assert!(client.update(&mut chunks).is_ok());
+    // nothing drains the changes anymore, so they aren't recorded
+    assert!(!chunks.track_changes && chunks.changed_blocks.is_empty());
+    server.stop();
+}
+
+#[test]
+fn other_protocol_versions_are_refused() {
+    let server = TestServer::start("multiplayer_version");
+    let mut connection = Connection::new(TcpStream::connect(server.address).unwrap()).unwrap();
+    connection.send(&Packet::Handshake { version: PROTOCOL_VERSION + 1 });
+    connection.flush().unwrap();
+
+    let mut packets = vec![];
+    let start = Instant::now();
+    while !connection.closed {
+        assert!(start.elapsed() < TIMEOUT, "the server didn't close the connection");
+        packets.extend(connection.receive().unwrap());
+    }
+    match packets.as_slice() {
+        [Packet::Handshake { version: PROTOCOL_VERSION }, Packet::Disconnect { reason }] => assert!(reason.contains("isn't supported"), "{reason}"),
+        packets => panic!("unexpected answer {packets:?}"),
+    }
+    server.stop();
+}
+
+#[test]
+fn logging_in_without_a_handshake_is_refused() {
+    let server = TestServer::start("multiplayer_no_handshake");
+    let mut connection = Connection::new(TcpStream::connect(server.address).unwrap()).unwrap();
+    connection.send(&Packet::Login { name: "mallory".to_string(), view_distance: 2 });
+    connection.flush().unwrap();
+
+    let mut packets = vec![];
+    let start = Instant::now();
+    while !connection.closed {
+        assert!(start.elapsed() < TIMEOUT, "the server didn't close the connection");
+        packets.extend(connection.receive().unwrap());
+    }
+    assert!(matches!(packets.as_slice(), [Packet::Disconnect { .. }]), "{packets:?}");
+    server.stop();
+}
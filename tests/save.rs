@@ -0,0 +1,40 @@
+mod common;
+
+use common::TempWorld;
+use voxel_game::{generator::GeneratorPreset, save::{WorldMetadata, WorldSave}};
+
+#[test]
+fn new_worlds_remember_their_seed_and_generator() {
+    let world = TempWorld::new("metadata");
+    let save = WorldSave::new(&world.0);
+    assert_eq!(save.load_metadata().unwrap(), None);
+    let metadata = WorldMetadata { seed: u64::MAX, generator: GeneratorPreset::Flat };
+    assert_eq!(save.open(Some(u64::MAX), Some(GeneratorPreset::Flat)).unwrap(), metadata);
+    assert_eq!(save.load_metadata().unwrap(), Some(metadata));
+
+    assert_eq!(save.open(None, None).unwrap(), metadata);
+    assert_eq!(save.open(Some(u64::MAX), None).unwrap(), metadata);
+    let error = save.open(Some(1), None).unwrap_err().to_string();
+    assert!(error.ends_with(&format!("was created with seed {}, not 1", u64::MAX)), "{error}");
+    let error = save.open(None, Some(GeneratorPreset::Empty)).unwrap_err().to_string();
+    assert!(error.ends_with("was created with the flat generator, not empty"), "{error}");
+}
+
+#[test]
+fn worlds_opened_without_a_seed_get_a_random_one() {
+    let world = TempWorld::new("metadata_random");
+    let save = WorldSave::new(&world.0);
+    let metadata = save.open(None, None).unwrap();
+    assert_eq!(metadata.generator, GeneratorPreset::default());
+    assert_eq!(save.open(None, None).unwrap(), metadata);
+}
+
+#[test]
+fn broken_metadata_is_an_error() {
+    let world = TempWorld::new("metadata_broken");
+    let save = WorldSave::new(&world.0);
+    std::fs::create_dir_all(&world.0).unwrap();
+    std::fs::write(save.metadata_path(), r#"{ "seed": 3, "generator": "mountains" }"#).unwrap();
+    assert!(save.load_metadata().is_err());
+    assert!(save.open(Some(3), None).is_err());
+}
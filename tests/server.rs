@@ -0,0 +1,218 @@
+mod common;
+
+use std::{io::Read, net::TcpStream, sync::mpsc, time::{Duration, Instant}};
+
+use cgmath::{Point2, Point3, Vector3};
+use common::TempWorld;
+use voxel_game::{block::{Block, Material}, chunk::chunk_local_position, generator::{spawn_chunks, GeneratorPreset}, protocol::{Connection, Packet, PROTOCOL_VERSION}, save::WorldSave, server::{check_name, ClientState, Server, MAX_NAME_LENGTH, MAX_VIEW_DISTANCE}};
+
+fn server(world: &TempWorld) -> Server {
+    Server::new(WorldSave::new(&world.0), 7, GeneratorPreset::Flat, "127.0.0.1:0").unwrap()
+}
+
+/// Lets the server accept and read until `done` holds, loopback connections take a moment to show up.
+fn poll_until(server: &mut Server, done: impl Fn(&Server) -> bool) {
+    let start = Instant::now();
+    while !done(server) {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the server");
+        server.accept_clients();
+        server.update_clients();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Whether the server closed the connection after its goodbye, read with a timeout so a hanging server fails the test.
+fn closed_by_server(stream: &mut TcpStream) -> bool {
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.read_to_end(&mut vec![]).is_ok()
+}
+
+#[test]
+fn clients_connect_and_disconnect() {
+    let world = TempWorld::new("connect");
+    let mut server = server(&world);
+    let first = TcpStream::connect(server.local_address()).unwrap();
+    let mut second = TcpStream::connect(server.local_address()).unwrap();
+    poll_until(&mut server, |server| server.clients.len() == 2);
+    assert_eq!(server.clients.iter().map(|client| client.id).collect::<Vec<_>>(), [1, 2]);
+    assert!(server.run_command("list").starts_with("2 clients: 1 (127.0.0.1:"));
+
+    drop(first);
+    poll_until(&mut server, |server| server.clients.len() == 1);
+
+    assert_eq!(server.run_command("kick 2"), "kicked client 2");
+    assert!(server.clients.is_empty());
+    assert!(closed_by_server(&mut second));
+    assert_eq!(server.run_command("kick 2"), "no client 2, see list");
+}
+
+#[test]
+fn generates_spawn_and_requested_chunks() {
+    let world = TempWorld::new("generate");
+    let mut server = server(&world);
+    for position in spawn_chunks() {
+        assert!(server.loaded_chunks.get(position).is_some());
+    }
+    let chunk = server.load_chunk(Point2::new(-5, 3));
+    assert_eq!(chunk.position, Point2::new(-5, 3));
+    assert_eq!(server.run_command("save"), format!("saved {} chunks to {}", spawn_chunks().count() + 1, world.0.display()));
+    assert!(WorldSave::new(&world.0).load_chunk(Point2::new(-5, 3)).unwrap().is_some());
+}
+
+#[test]
+fn stop_disconnects_clients_and_saves() {
+    let world = TempWorld::new("stop");
+    let (commands, receiver) = mpsc::channel();
+    let (address_sender, address) = mpsc::channel();
+    let directory = world.0.clone();
+    // the server isn't `Send`, it lives on the thread that runs it
+    let thread = std::thread::spawn(move || {
+        let mut server = Server::new(WorldSave::new(directory), 7, GeneratorPreset::Flat, "127.0.0.1:0").unwrap();
+        address_sender.send(server.local_address()).unwrap();
+        server.run(receiver, |_| ())
+    });
+
+    let mut client = TcpStream::connect(address.recv().unwrap()).unwrap();
+    commands.send("stop".to_string()).unwrap();
+    thread.join().unwrap().unwrap();
+
+    assert!(closed_by_server(&mut client));
+    let save = WorldSave::new(&world.0);
+    for position in spawn_chunks() {
+        assert!(save.load_chunk(position).unwrap().is_some());
+    }
+}
+
+#[test]
+fn closing_the_console_stops_the_server() {
+    let world = TempWorld::new("console");
+    let mut server = server(&world);
+    let (commands, receiver) = mpsc::channel();
+    commands.send("seed".to_string()).unwrap();
+    drop(commands);
+    let mut replies = vec![];
+    server.run(receiver, |reply| replies.push(reply)).unwrap();
+    assert!(!server.running);
+    assert_eq!(replies, ["seed: 7 (flat)"]);
+}
+
+#[test]
+fn chunks_nobody_is_near_are_saved_and_unloaded() {
+    let world = TempWorld::new("unload");
+    let mut server = server(&world);
+    let mut client = Connection::new(TcpStream::connect(server.local_address()).unwrap()).unwrap();
+    client.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+    client.send(&Packet::Login { name: "alice".to_string(), view_distance: 3 });
+    client.flush().unwrap();
+    let view = 7 * 7;
+    poll_until(&mut server, |server| server.clients.first().is_some_and(|client| client.state == ClientState::Playing && client.sent_chunks.len() == view));
+    let behind = Point2::new(-2, -2);
+    assert!(server.loaded_chunks.get(behind).is_some());
+
+    // a few chunks east, as far as a player flies in under a second
+    let destination = server.spawn + cgmath::Vector3::new(300.0, 0.0, 0.0);
+    client.send(&Packet::PlayerMovement { player_id: 1, position: destination });
+    client.flush().unwrap();
+    poll_until(&mut server, |server| server.loaded_chunks.get(behind).is_none());
+    assert!(!server.clients[0].sent_chunks.contains(&behind));
+    assert!(WorldSave::new(&world.0).load_chunk(behind).unwrap().is_some());
+    for position in spawn_chunks() {
+        assert!(server.loaded_chunks.get(position).is_some());
+    }
+
+    let mut packets = vec![];
+    let start = Instant::now();
+    while !packets.iter().any(|packet| matches!(packet, Packet::UnloadChunk { position } if *position == behind)) {
+        assert!(start.elapsed() < Duration::from_secs(5), "the client wasn't told to unload the chunk");
+        server.update_clients();
+        packets.extend(client.receive().unwrap());
+    }
+}
+
+#[test]
+fn movement_faster_than_a_player_flies_is_refused() {
+    let world = TempWorld::new("movement");
+    let mut server = server(&world);
+    let mut client = Connection::new(TcpStream::connect(server.local_address()).unwrap()).unwrap();
+    client.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+    client.send(&Packet::Login { name: "alice".to_string(), view_distance: 3 });
+    client.send(&Packet::PlayerMovement { player_id: 1, position: Point3::new(1e6, 70.0, 0.0) });
+    client.flush().unwrap();
+    let spawn = server.spawn;
+    poll_until(&mut server, |server| server.clients.first().is_some_and(|client| client.state == ClientState::Playing));
+
+    let start = Instant::now();
+    loop {
+        assert!(start.elapsed() < Duration::from_secs(5), "the client wasn't moved back");
+        server.update_clients();
+        let packets = client.receive().unwrap();
+        if let Some(position) = packets.iter().find_map(|packet| match packet {
+            Packet::PlayerMovement { player_id: 1, position } => Some(*position),
+            _ => None,
+        }) {
+            assert_eq!(position, spawn);
+            break;
+        }
+    }
+    assert_eq!(server.clients[0].position, spawn);
+}
+
+#[test]
+fn view_distances_are_limited() {
+    let world = TempWorld::new("view_distance");
+    let mut server = server(&world);
+    let mut client = Connection::new(TcpStream::connect(server.local_address()).unwrap()).unwrap();
+    client.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+    client.send(&Packet::Login { name: "alice".to_string(), view_distance: 200 });
+    client.flush().unwrap();
+    poll_until(&mut server, |server| server.clients.first().is_some_and(|client| client.state == ClientState::Playing));
+    assert_eq!(server.clients[0].view_distance, MAX_VIEW_DISTANCE);
+
+    client.send(&Packet::ViewDistance { chunks: 2 });
+    client.flush().unwrap();
+    poll_until(&mut server, |server| server.clients[0].view_distance == 2);
+}
+
+#[test]
+fn names_are_checked() {
+    let world = TempWorld::new("names");
+    let mut server = server(&world);
+    for name in ["", "a name far too long", "al\nice"] {
+        let mut client = Connection::new(TcpStream::connect(server.local_address()).unwrap()).unwrap();
+        client.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+        client.send(&Packet::Login { name: name.to_string(), view_distance: 3 });
+        client.flush().unwrap();
+        let start = Instant::now();
+        loop {
+            assert!(start.elapsed() < Duration::from_secs(5), "{name:?} was let in");
+            server.accept_clients();
+            server.update_clients();
+            if client.receive().unwrap_or_default().iter().any(|packet| matches!(packet, Packet::Disconnect { .. })) { break; }
+        }
+        assert!(server.clients.is_empty());
+    }
+    assert_eq!(check_name(" alice ").unwrap(), "alice");
+    assert!(check_name(&"é".repeat(MAX_NAME_LENGTH)).is_ok());
+}
+
+#[test]
+fn block_changes_in_chunks_not_sent_are_dropped() {
+    let world = TempWorld::new("unsent");
+    let mut server = server(&world);
+    let mut client = Connection::new(TcpStream::connect(server.local_address()).unwrap()).unwrap();
+    client.send(&Packet::Handshake { version: PROTOCOL_VERSION });
+    client.send(&Packet::Login { name: "alice".to_string(), view_distance: 0 });
+    client.flush().unwrap();
+    poll_until(&mut server, |server| server.clients.first().is_some_and(|client| client.sent_chunks.len() == 1));
+
+    // the ground under the player, and the block next to it across the border of the only chunk the client has
+    let ground = server.spawn.map(|coordinate| coordinate.floor() as i32) - Vector3::new(0, 1, 0);
+    let unsent = ground - Vector3::new(1, 0, 0);
+    assert_ne!(chunk_local_position(unsent).0, chunk_local_position(ground).0);
+    let grass = server.loaded_chunks.block(unsent).unwrap().material;
+    client.send(&Packet::BlockChange { position: unsent, block: Block::new(Material::Air) });
+    client.send(&Packet::BlockChange { position: ground, block: Block::new(Material::Air) });
+    client.flush().unwrap();
+    poll_until(&mut server, |server| server.loaded_chunks.block(ground).unwrap().material == Material::Air);
+    assert_eq!(server.loaded_chunks.block(unsent).unwrap().material, grass);
+}
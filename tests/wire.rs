@@ -0,0 +1,165 @@
+use std::io::Write;
+
+use cgmath::{Point2, Point3};
+use flate2::{write::ZlibEncoder, Compression};
+use proptest::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use voxel_game::{block::{Block, BlockState, Material}, chunk::{Chunk, ChunkManager, CHUNK_HEIGHT, CHUNK_SIZE, SUB_CHUNKS}, generator::GeneratorPreset, protocol::Packet, sign::Sign, wire::{decode_chunk, encode_chunk, sub_chunk_block_position, SUB_CHUNK_VOLUME}};
+
+/// How one sub chunk of a fuzzed chunk is filled.
+#[derive(Debug, Clone)]
+enum Fill {
+    Empty,
+    Uniform(Block),
+    /// random picks from a small palette, a few bits per block
+    Palette(Vec<Block>, u64),
+    /// random materials and states, hundreds of distinct blocks and wide indices
+    Noise(u64),
+    /// air with a few blocks set
+    Sparse(Vec<(u16, Block)>),
+}
+
+fn block() -> impl Strategy<Value = Block> {
+    (proptest::sample::select(Material::ALL.as_slice()), any::<u8>()).prop_map(|(material, bits)| Block::with_state(material, BlockState::from_bits(bits)))
+}
+
+fn fill() -> impl Strategy<Value = Fill> {
+    prop_oneof![
+        Just(Fill::Empty),
+        block().prop_map(Fill::Uniform),
+        (proptest::collection::vec(block(), 2..40), any::<u64>()).prop_map(|(palette, seed)| Fill::Palette(palette, seed)),
+        any::<u64>().prop_map(Fill::Noise),
+        proptest::collection::vec((0..SUB_CHUNK_VOLUME as u16, block()), 1..20).prop_map(Fill::Sparse),
+    ]
+}
+
+fn chunk(position: Point2<i32>, fills: &[Fill], sign: Option<(u8, u8, u8, String)>) -> Chunk {
+    let mut chunk = GeneratorPreset::Empty.generate(position, 0);
+    for (blocks, fill) in chunk.blocks.chunks_mut(SUB_CHUNK_VOLUME).zip(fills) {
+        match fill {
+            Fill::Empty => {}
+            Fill::Uniform(block) => blocks.fill(*block),
+            Fill::Palette(palette, seed) => {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                blocks.iter_mut().for_each(|block| *block = palette[rng.gen_range(0..palette.len())]);
+            }
+            Fill::Noise(seed) => {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                blocks.iter_mut().for_each(|block| *block = Block::with_state(Material::ALL[rng.gen_range(0..Material::ALL.len())], BlockState::from_bits(rng.gen())));
+            }
+            Fill::Sparse(changes) => changes.iter().for_each(|(index, block)| blocks[*index as usize] = *block),
+        }
+    }
+    if let Some((x, y, z, text)) = sign {
+        let local = (x as usize % CHUNK_SIZE, y as usize, z as usize % CHUNK_SIZE);
+        // through air, the fill may already have put a sign without its block entity there
+        chunk.set_block(local, Block::new(Material::Air));
+        chunk.set_block(local, Block::new(Material::Sign));
+        chunk.block_entity_mut::<Sign>(local).unwrap().set_line(1, &text);
+    }
+    chunk
+}
+
+/// Every block as `(material, state)`, blocks don't compare directly.
+fn blocks(chunk: &Chunk) -> Vec<(u8, u8)> {
+    chunk.blocks.iter().map(|block| (block.material as u8, block.state.bits())).collect()
+}
+
+fn sign_lines(chunk: &Chunk) -> Vec<(Point3<u8>, [String; 4])> {
+    let mut signs: Vec<_> = chunk.block_entities.keys().map(|key| {
+        let sign = chunk.block_entity::<Sign>((key.x as usize, key.y as usize, key.z as usize)).unwrap();
+        (*key, sign.lines.clone())
+    }).collect();
+    signs.sort_by_key(|(key, _)| (key.x, key.y, key.z));
+    signs
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn chunks_round_trip(x in any::<i32>(), z in any::<i32>(), fills in proptest::collection::vec(fill(), SUB_CHUNKS), sign in proptest::option::of((any::<u8>(), any::<u8>(), any::<u8>(), "[ -~]{0,20}"))) {
+        let chunk = chunk(Point2::new(x, z), &fills, sign);
+        let data = encode_chunk(&chunk);
+        let decoded = decode_chunk(&data).unwrap();
+        prop_assert_eq!(decoded.position, chunk.position);
+        prop_assert!(blocks(&decoded) == blocks(&chunk), "blocks differ after the round trip");
+        prop_assert_eq!(sign_lines(&decoded), sign_lines(&chunk));
+
+        // the same chunk through a `ChunkData` packet
+        let frame = Packet::encode_chunk_data(&chunk);
+        match Packet::decode(&frame[4..]).unwrap() {
+            Packet::ChunkData { chunk: decoded } => prop_assert!(blocks(&decoded) == blocks(&chunk)),
+            packet => prop_assert!(false, "decoded {:?}", packet.id()),
+        }
+    }
+
+    #[test]
+    fn block_changes_round_trip(changes in proptest::collection::vec((0..2 * CHUNK_SIZE as i32, 0..CHUNK_HEIGHT as i32, 0..CHUNK_SIZE as i32, block()), 1..300)) {
+        let mut server = ChunkManager::new();
+        let mut client = ChunkManager::new();
+        for x in 0..2 {
+            server.insert(GeneratorPreset::Flat.generate(Point2::new(x, 0), 0));
+            client.insert(GeneratorPreset::Flat.generate(Point2::new(x, 0), 0));
+        }
+        server.track_changes = true;
+        for (x, y, z, block) in changes.iter() {
+            server.set_block(Point3::new(*x, *y, *z), *block);
+        }
+
+        let changed = std::mem::take(&mut server.changed_blocks);
+        let packets = Packet::block_changes(&server, changed.iter().copied());
+        // at most one packet per sub chunk
+        let mut sub_chunks: Vec<_> = changed.iter().map(|position| (position.x / CHUNK_SIZE as i32, position.y / 32)).collect();
+        sub_chunks.sort();
+        sub_chunks.dedup();
+        prop_assert!(packets.len() <= sub_chunks.len());
+
+        for (_, packet) in packets {
+            let frame = packet.encode();
+            match Packet::decode(&frame[4..]).unwrap() {
+                Packet::BlockChange { position, block } => { client.set_block(position, block); }
+                Packet::MultiBlockChange { chunk, sub_chunk, changes } => {
+                    prop_assert!(changes.len() > 1);
+                    prop_assert_eq!(frame.len(), 4 + 1 + 8 + 1 + 2 + 4 * changes.len());
+                    for (index, block) in changes {
+                        client.set_block(sub_chunk_block_position(chunk, sub_chunk, index), block);
+                    }
+                }
+                packet => prop_assert!(false, "decoded {:?}", packet.id()),
+            }
+        }
+        for x in 0..2 {
+            let position = Point2::new(x, 0);
+            prop_assert!(blocks(client.get(position).unwrap()) == blocks(server.get(position).unwrap()), "chunk {:?} differs", position);
+            prop_assert_eq!(sign_lines(client.get(position).unwrap()), sign_lines(server.get(position).unwrap()));
+        }
+    }
+
+    #[test]
+    fn arbitrary_chunk_data_is_rejected_without_panicking(data in proptest::collection::vec(any::<u8>(), 0..2048)) {
+        let _ = decode_chunk(&data);
+        let _ = Packet::decode(&data);
+    }
+
+    #[test]
+    fn garbage_behind_valid_compression_does_not_panic(raw in proptest::collection::vec(any::<u8>(), 0..4096), mask in any::<u8>()) {
+        // past the checksum the parser sees the garbage itself, which can happen to be a valid chunk like an empty mask followed by no block entities
+        let mut data = vec![0; 8];
+        data.push(mask);
+        data.extend(raw);
+        let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+        encoder.write_all(&data).unwrap();
+        let _ = decode_chunk(&encoder.finish().unwrap());
+    }
+
+    #[test]
+    fn corrupted_chunks_are_rejected_without_panicking(fills in proptest::collection::vec(fill(), SUB_CHUNKS), flips in proptest::collection::vec((any::<prop::sample::Index>(), 1..=255u8), 1..4)) {
+        let mut data = encode_chunk(&chunk(Point2::new(1, 2), &fills, None));
+        for (index, mask) in flips {
+            let index = index.index(data.len());
+            data[index] ^= mask;
+        }
+        let _ = decode_chunk(&data);
+    }
+}